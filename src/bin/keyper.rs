@@ -0,0 +1,30 @@
+use clap::{Command, Arg, value_parser};
+use std::path::PathBuf;
+
+use bedrock::keyper::{Keyper, KeyperConfig};
+
+#[tokio::main]
+async fn main() {
+    let matches = Command::new("Keyper")
+        .version("1.0")
+        .about("A keyper server that answers PRF requests of bedrock vault clients")
+        .arg(
+            Arg::new("config")
+                .short('c')
+                .long("config")
+                .help("Path to the keyper's TOML configuration file")
+                .required(true)
+                .value_parser(value_parser!(PathBuf))
+        )
+        .get_matches();
+
+    let config_path = matches.get_one::<PathBuf>("config").expect("invalid args: config is required");
+
+    let config = KeyperConfig::load(config_path)
+        .unwrap_or_else(|e| panic!("Failed to load keyper config at {:?}: {}", config_path, e));
+    let keyper = Keyper::from_config(&config)
+        .unwrap_or_else(|e| panic!("Invalid keyper config at {:?}: {}", config_path, e));
+
+    println!("Keyper listening on {}", config.listen);
+    warp::serve(keyper.routes()).run(config.listen).await;
+}
@@ -14,7 +14,7 @@ async fn main() {
                 .short('m')
                 .long("mode")
                 .help("Sets the operation mode")
-                .value_parser(["reload", "init"])
+                .value_parser(["reload", "init", "rotate", "change-pin"])
                 .required(true)
         )
         .arg(
@@ -25,6 +25,13 @@ async fn main() {
                 .required(true)
                 .value_parser(value_parser!(String))
         )
+        .arg(
+            Arg::new("new-pincode")
+                .short('n')
+                .long("new-pincode")
+                .help("6-digit numeric pincode replacing the current one")
+                .value_parser(value_parser!(String))
+        )
         .arg(
             Arg::new("secret")
                 .short('s')
@@ -45,10 +52,10 @@ async fn main() {
         "reload" => {
             //read the vault file
             let vault = fs::read(&vault_path)
-                .expect(format!("Failed to read vault file at {:?}", vault_path).as_str());
+                .unwrap_or_else(|_| panic!("Failed to read vault file at {:?}", vault_path));
             println!("Reloading secret from vault using pincode {}", pin);
             let client = bedrock::BedrockClient::new_debug(
-                "https://zkbricks-vault-worker.rohit-fd0.workers.dev/decrypt",
+                "https://zkbricks-vault-worker.rohit-fd0.workers.dev",
                 "alice@gmail.com",
             );
             let recovered_secret = client.recover(vault, pin.as_bytes()).await.unwrap();
@@ -59,13 +66,37 @@ async fn main() {
 
             println!("Creating a vault with pincode {}", pin);
             let client = bedrock::BedrockClient::new_debug(
-                "https://zkbricks-vault-worker.rohit-fd0.workers.dev/decrypt", 
+                "https://zkbricks-vault-worker.rohit-fd0.workers.dev", 
                 "alice@gmail.com"
             );
 
             let vault_data = client.initialize(pin.as_bytes(), secret.as_bytes()).await.unwrap();
             fs::write(vault_path, vault_data).expect("Failed to write vault file");
         },
+        "rotate" => {
+            let vault = fs::read(&vault_path)
+                .unwrap_or_else(|_| panic!("Failed to read vault file at {:?}", vault_path));
+            println!("Re-encrypting vault under the keyper's current key");
+            let client = bedrock::BedrockClient::new_debug(
+                "https://zkbricks-vault-worker.rohit-fd0.workers.dev",
+                "alice@gmail.com",
+            );
+            let vault_data = client.rotate(vault, pin.as_bytes()).await.unwrap();
+            fs::write(vault_path, vault_data).expect("Failed to write vault file");
+        },
+        "change-pin" => {
+            let new_pin = matches.get_one::<String>("new-pincode").expect("invalid args: new-pincode is required");
+
+            let vault = fs::read(&vault_path)
+                .unwrap_or_else(|_| panic!("Failed to read vault file at {:?}", vault_path));
+            println!("Changing the vault's pincode to {}", new_pin);
+            let client = bedrock::BedrockClient::new_debug(
+                "https://zkbricks-vault-worker.rohit-fd0.workers.dev",
+                "alice@gmail.com",
+            );
+            let vault_data = client.change_pin(vault, pin.as_bytes(), new_pin.as_bytes()).await.unwrap();
+            fs::write(vault_path, vault_data).expect("Failed to write vault file");
+        },
         _ => unreachable!(), // This won't happen due to value_parser restriction
     }
 }
@@ -0,0 +1,318 @@
+//! Pedersen's distributed key generation (joint-Feldman VSS), by which the keypers
+//! obtain a (t, n) Shamir sharing of a single key k, without any party learning k.
+//! Each keyper deals a random polynomial, broadcasting Feldman commitments to its
+//! coefficients and sending each other keyper its evaluation privately; a keyper's
+//! share of k is the sum of the evaluations it received from the qualified dealers.
+//! As noted by Gennaro et al., a rushing adversary can bias the distribution of the
+//! resulting public key, which is harmless for the OPRF keys we generate here.
+//! Keyper i (0-based) holds the evaluation at x = i + 1, as in `sss`.
+//!
+//! The shares are refreshed proactively, as proposed by Herzberg et al.: at the start of
+//! each epoch, every keyper deals a sharing of zero in the same way, and adds the shares it
+//! receives to its own. The joint key is unchanged, but the new shares are unrelated to the
+//! old ones, so that shares leaked in past epochs cannot be combined with current ones.
+
+#![allow(dead_code)]
+
+use ark_ec::Group;
+use ark_ff::{UniformRand, Zero};
+use ark_poly::{Polynomial, univariate::DensePolynomial};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::rand::Rng;
+
+use super::jkkx16::JKKX16Error;
+use super::sss;
+
+/// Broadcast by a dealer: commitments g^{a_0}, ..., g^{a_{t-1}} to its polynomial.
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Dealing<C: Group> {
+    pub dealer: usize,
+    pub commitments: Vec<C>,
+}
+
+/// A keyper's output of the DKG.
+#[derive(Clone, Default, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct KeyShare<C: Group> {
+    /// index of the keyper
+    pub index: usize,
+    /// number of refreshes since the key generation
+    pub epoch: u32,
+    /// the keyper's share k_i of the joint key k
+    pub secret_share: C::ScalarField,
+    /// g^k
+    pub public_key: C,
+    /// g^{k_j} for every keyper j, against which their evaluations are verified
+    pub verification_keys: Vec<C>,
+}
+
+/// Samples the dealer's polynomial of degree t - 1, returning the dealing to
+/// broadcast and the n private shares, where the j-th share is meant for keyper j.
+pub fn deal<C: Group, R: Rng>(
+    dealer: usize,
+    threshold: usize,
+    num_parties: usize,
+    rng: &mut R,
+) -> Result<(Dealing<C>, Vec<C::ScalarField>), JKKX16Error> {
+    if threshold == 0 || threshold > num_parties {
+        return Err(JKKX16Error::InvalidThresholdError(threshold, num_parties));
+    }
+
+    let p = DensePolynomial {
+        coeffs: (0..threshold).map(|_| C::ScalarField::rand(rng)).collect::<Vec<_>>()
+    };
+
+    let generator = C::generator();
+    let commitments = p.coeffs.iter()
+        .map(|a| generator.mul(a))
+        .collect();
+
+    let shares = (1..=num_parties)
+        .map(|j| p.evaluate(&C::ScalarField::from(j as u64)))
+        .collect();
+
+    Ok((Dealing { dealer, commitments }, shares))
+}
+
+/// Checks the share that keyper `party` received against the dealer's commitments,
+/// i.e., g^{s} == prod_k C_k^{(party + 1)^k}. A keyper complains about any dealer
+/// whose share fails this check, and that dealer is then disqualified by all keypers.
+pub fn verify_share<C: Group>(
+    dealing: &Dealing<C>,
+    party: usize,
+    share: &C::ScalarField,
+) -> bool {
+    let share = (C::ScalarField::from((party + 1) as u64), *share);
+    sss::verify_share::<C>(&dealing.commitments, &C::generator(), &share)
+}
+
+/// Combines the dealings of the qualified dealers into the keyper's share of the
+/// joint key, where `shares[d]` is the share the keyper received with `dealings[d]`.
+/// All keypers must agree on the set of qualified dealers, e.g. by excluding every
+/// dealer against which a complaint was broadcast.
+pub fn finalize<C: Group>(
+    party: usize,
+    threshold: usize,
+    num_parties: usize,
+    dealings: &[Dealing<C>],
+    shares: &[C::ScalarField],
+) -> Result<KeyShare<C>, JKKX16Error> {
+    assert_eq!(dealings.len(), shares.len(), "expected one share per dealing");
+
+    if dealings.is_empty() {
+        return Err(JKKX16Error::NotEnoughResponsesError(0, 1));
+    }
+
+    for (dealing, share) in dealings.iter().zip(shares.iter()) {
+        if dealing.commitments.len() != threshold || !verify_share(dealing, party, share) {
+            return Err(JKKX16Error::InvalidDealingError(dealing.dealer));
+        }
+    }
+
+    let secret_share = shares.iter().sum();
+
+    // the commitments to the joint polynomial are the sums of the dealers' commitments
+    let commitments: Vec<C> = (0..threshold)
+        .map(|k| dealings.iter().map(|dealing| dealing.commitments[k]).sum())
+        .collect();
+
+    let verification_keys = (0..num_parties)
+        .map(|j| evaluate_commitments::<C>(&commitments, j))
+        .collect();
+
+    Ok(KeyShare {
+        index: party,
+        epoch: 0,
+        secret_share,
+        public_key: commitments[0],
+        verification_keys,
+    })
+}
+
+/// Samples the dealer's sharing of zero for the next epoch: a polynomial of degree t - 1 with
+/// a zero constant term, returned as in `deal`. The first commitment is then the identity,
+/// which the receiving keypers check, lest a dealer shift the joint key.
+pub fn deal_refresh<C: Group, R: Rng>(
+    dealer: usize,
+    threshold: usize,
+    num_parties: usize,
+    rng: &mut R,
+) -> Result<(Dealing<C>, Vec<C::ScalarField>), JKKX16Error> {
+    let generator = C::generator();
+    let (shares, commitments) = sss::share_verifiable::<C, R>(C::ScalarField::zero(), threshold, num_parties, &generator, rng)?;
+
+    Ok((Dealing { dealer, commitments }, shares.into_iter().map(|(_, y)| y).collect()))
+}
+
+/// Adds the sharings of zero dealt by the qualified dealers to the keyper's share, moving it
+/// to the next epoch; `shares[d]` is the share the keyper received with `dealings[d]`. As in
+/// `finalize`, all keypers must agree on the set of qualified dealers; the verification keys
+/// are updated with the dealers' commitments, while the public key stays the same.
+pub fn refresh<C: Group>(
+    key_share: &KeyShare<C>,
+    threshold: usize,
+    dealings: &[Dealing<C>],
+    shares: &[C::ScalarField],
+) -> Result<KeyShare<C>, JKKX16Error> {
+    assert_eq!(dealings.len(), shares.len(), "expected one share per dealing");
+
+    if dealings.is_empty() {
+        return Err(JKKX16Error::NotEnoughResponsesError(0, 1));
+    }
+
+    for (dealing, share) in dealings.iter().zip(shares.iter()) {
+        if dealing.commitments.len() != threshold
+            || !dealing.commitments[0].is_zero()
+            || !verify_share(dealing, key_share.index, share) {
+            return Err(JKKX16Error::InvalidDealingError(dealing.dealer));
+        }
+    }
+
+    let verification_keys = key_share.verification_keys.iter()
+        .enumerate()
+        .map(|(j, vk)| {
+            let delta: C = dealings.iter()
+                .map(|dealing| evaluate_commitments::<C>(&dealing.commitments, j))
+                .sum();
+            delta + vk
+        })
+        .collect();
+
+    Ok(KeyShare {
+        index: key_share.index,
+        epoch: key_share.epoch + 1,
+        secret_share: key_share.secret_share + shares.iter().sum::<C::ScalarField>(),
+        public_key: key_share.public_key,
+        verification_keys,
+    })
+}
+
+/// Computes g^{p(party + 1)} from the commitments to the coefficients of p.
+fn evaluate_commitments<C: Group>(commitments: &[C], party: usize) -> C {
+    sss::evaluate_commitments::<C>(commitments, &C::ScalarField::from((party + 1) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::{Fr, G1Projective};
+    use ark_std::test_rng;
+
+    use crate::crypto::ppss::lagrange::interpolate_in_exponent;
+
+    #[test]
+    fn test_dkg() {
+        let rng = &mut test_rng();
+        let (threshold, num_parties) = (3, 5);
+
+        let (mut dealings, mut shares): (Vec<_>, Vec<_>) = (0..num_parties)
+            .map(|dealer| deal::<G1Projective, _>(dealer, threshold, num_parties, rng).unwrap())
+            .unzip();
+
+        // dealer 2 sends keyper 4 a share inconsistent with its commitments
+        shares[2][4] += Fr::from(1u64);
+        assert!(!verify_share(&dealings[2], 4, &shares[2][4]));
+        let received: Vec<Fr> = shares.iter().map(|s| s[4]).collect();
+        assert!(matches!(
+            finalize(4, threshold, num_parties, &dealings, &received),
+            Err(JKKX16Error::InvalidDealingError(2))
+        ));
+
+        // upon keyper 4's complaint, all keypers disqualify dealer 2
+        dealings.remove(2);
+        shares.remove(2);
+
+        let key_shares: Vec<KeyShare<G1Projective>> = (0..num_parties)
+            .map(|party| {
+                let received: Vec<Fr> = shares.iter().map(|s| s[party]).collect();
+                finalize(party, threshold, num_parties, &dealings, &received).unwrap()
+            })
+            .collect();
+
+        // the keypers agree on the public key, and hold a sharing of its secret key
+        let joint_key: Fr = shares.iter().map(|s| sss::recover(&[
+            (Fr::from(1u64), s[0]), (Fr::from(2u64), s[1]), (Fr::from(3u64), s[2])
+        ]).unwrap()).sum();
+        let generator = G1Projective::generator();
+        for key_share in key_shares.iter() {
+            assert_eq!(key_shares[0].public_key, key_share.public_key);
+            assert_eq!(key_shares[0].verification_keys, key_share.verification_keys);
+            assert_eq!(key_share.verification_keys[key_share.index], generator * key_share.secret_share);
+        }
+        assert_eq!(key_shares[0].public_key, generator * joint_key);
+
+        let points: Vec<(Fr, _)> = [4, 1, 3].iter()
+            .map(|&j| (Fr::from((j + 1) as u64), generator * key_shares[j].secret_share))
+            .collect();
+        assert_eq!(key_shares[0].public_key, interpolate_in_exponent::<G1Projective>(&points, &Fr::zero()).unwrap());
+    }
+
+    #[test]
+    fn test_refresh() {
+        let rng = &mut test_rng();
+        let (threshold, num_parties) = (3, 5);
+
+        let (dealings, shares): (Vec<_>, Vec<_>) = (0..num_parties)
+            .map(|dealer| deal::<G1Projective, _>(dealer, threshold, num_parties, rng).unwrap())
+            .unzip();
+        let key_shares: Vec<KeyShare<G1Projective>> = (0..num_parties)
+            .map(|party| {
+                let received: Vec<Fr> = shares.iter().map(|s| s[party]).collect();
+                finalize(party, threshold, num_parties, &dealings, &received).unwrap()
+            })
+            .collect();
+
+        let (mut dealings, mut shares): (Vec<_>, Vec<_>) = (0..num_parties)
+            .map(|dealer| deal_refresh::<G1Projective, _>(dealer, threshold, num_parties, rng).unwrap())
+            .unzip();
+
+        // dealer 1 deals a sharing of a non-zero value, which would shift the joint key
+        let (shifted, shifted_shares) = deal::<G1Projective, _>(1, threshold, num_parties, rng).unwrap();
+        let received: Vec<Fr> = shares.iter().zip(shifted_shares.iter()).enumerate()
+            .map(|(d, (s, t))| if d == 1 { *t } else { s[0] })
+            .collect();
+        let mut shifted_dealings = dealings.clone();
+        shifted_dealings[1] = shifted;
+        assert!(matches!(
+            refresh(&key_shares[0], threshold, &shifted_dealings, &received),
+            Err(JKKX16Error::InvalidDealingError(1))
+        ));
+
+        // dealer 3 sends keyper 2 a bad share, and is disqualified
+        shares[3][2] += Fr::from(1u64);
+        let received: Vec<Fr> = shares.iter().map(|s| s[2]).collect();
+        assert!(matches!(
+            refresh(&key_shares[2], threshold, &dealings, &received),
+            Err(JKKX16Error::InvalidDealingError(3))
+        ));
+        dealings.remove(3);
+        shares.remove(3);
+
+        let refreshed: Vec<KeyShare<G1Projective>> = key_shares.iter()
+            .map(|key_share| {
+                let received: Vec<Fr> = shares.iter().map(|s| s[key_share.index]).collect();
+                refresh(key_share, threshold, &dealings, &received).unwrap()
+            })
+            .collect();
+
+        let generator = G1Projective::generator();
+        let joint_key = |key_shares: &[&KeyShare<G1Projective>]| {
+            let shares: Vec<(Fr, Fr)> = key_shares.iter()
+                .map(|k| (Fr::from((k.index + 1) as u64), k.secret_share))
+                .collect();
+            generator * sss::recover(&shares).unwrap()
+        };
+
+        // the shares change, but still combine to the same key
+        for (old, new) in key_shares.iter().zip(refreshed.iter()) {
+            assert_eq!(1, new.epoch);
+            assert_ne!(old.secret_share, new.secret_share);
+            assert_eq!(old.public_key, new.public_key);
+            assert_eq!(refreshed[0].verification_keys, new.verification_keys);
+            assert_eq!(new.verification_keys[new.index], generator * new.secret_share);
+        }
+        assert_eq!(key_shares[0].public_key, joint_key(&[&refreshed[4], &refreshed[1], &refreshed[3]]));
+
+        // shares from different epochs do not
+        assert_ne!(key_shares[0].public_key, joint_key(&[&key_shares[4], &refreshed[1], &refreshed[3]]));
+    }
+}
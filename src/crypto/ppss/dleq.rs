@@ -0,0 +1,217 @@
+//! Chaum-Pedersen proof of discrete-log equality, made non-interactive with Fiat-Shamir.
+//! A keyper attaches this proof to its PRF evaluation to show that the blinded output
+//! was computed with the same secret key that stands behind its public key. A variant proves
+//! the equality between a base group and the target group of a pairing, for the evaluations
+//! of the pairing-based PRF in `toprf`.
+
+use ark_ec::{pairing::{Pairing, PairingOutput}, Group};
+use ark_ff::fields::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_std::{ops::*, Zero};
+
+use blake2::Blake2s;
+use digest::Digest;
+
+enum HashDomainSeparator {
+    Nonce = 0,
+    Challenge = 1,
+    NonceInTarget = 2,
+    ChallengeInTarget = 3,
+}
+
+/// Proves that log_g(y) == log_h(z) for the statement (g, y, h, z).
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct DleqProof<C: Group> {
+    pub challenge: C::ScalarField,
+    pub response: C::ScalarField,
+}
+
+/// Computes a proof that `y = g^x` and `z = h^x` for the same secret `x`.
+/// The nonce is derived deterministically from the secret and the statement,
+/// so that keypers do not need a source of randomness to answer requests.
+pub fn prove<C: Group>(
+    g: &C,
+    h: &C,
+    x: &C::ScalarField,
+) -> Result<DleqProof<C>, SerializationError> {
+    let y = g.mul(x);
+    let z = h.mul(x);
+
+    // k := H(x || g || y || h || z);
+    let mut k = hash_to_scalar::<C>(HashDomainSeparator::Nonce as u8, &[*g, y, *h, z], &[*x])?;
+    if k.is_zero() {
+        k = C::ScalarField::from(1u64);
+    }
+
+    // A := g^k, B := h^k
+    let a = g.mul(k);
+    let b = h.mul(k);
+
+    // c := H(g || y || h || z || A || B);
+    let challenge = hash_to_scalar::<C>(HashDomainSeparator::Challenge as u8, &[*g, y, *h, z, a, b], &[])?;
+
+    // s := k - c·x
+    let response = k - challenge * x;
+
+    Ok(DleqProof { challenge, response })
+}
+
+/// Checks a proof that `y` and `z` have the same discrete log w.r.t. `g` and `h`.
+pub fn verify<C: Group>(
+    g: &C,
+    y: &C,
+    h: &C,
+    z: &C,
+    proof: &DleqProof<C>,
+) -> Result<bool, SerializationError> {
+    // A = g^s · y^c, B = h^s · z^c
+    let a = g.mul(proof.response) + y.mul(proof.challenge);
+    let b = h.mul(proof.response) + z.mul(proof.challenge);
+
+    let challenge = hash_to_scalar::<C>(HashDomainSeparator::Challenge as u8, &[*g, *y, *h, *z, a, b], &[])?;
+
+    Ok(challenge == proof.challenge)
+}
+
+/// Computes a proof that `y = g^x` in G1 and `z = h^x` in the target group of the pairing `E`,
+/// for the same secret `x`; the nonce is derived deterministically, as in `prove`.
+pub fn prove_in_target<E: Pairing>(
+    g: &E::G1,
+    h: &PairingOutput<E>,
+    x: &E::ScalarField,
+) -> Result<DleqProof<E::G1>, SerializationError> {
+    let y = g.mul(x);
+    let z = *h * x;
+
+    // k := H(x || g || y || h || z);
+    let mut k = hash_in_target_to_scalar::<E>(HashDomainSeparator::NonceInTarget as u8, &[*g, y], &[*h, z], &[*x])?;
+    if k.is_zero() {
+        k = E::ScalarField::from(1u64);
+    }
+
+    // A := g^k, B := h^k
+    let a = g.mul(k);
+    let b = *h * k;
+
+    // c := H(g || y || A || h || z || B);
+    let challenge = hash_in_target_to_scalar::<E>(HashDomainSeparator::ChallengeInTarget as u8, &[*g, y, a], &[*h, z, b], &[])?;
+
+    // s := k - c·x
+    let response = k - challenge * x;
+
+    Ok(DleqProof { challenge, response })
+}
+
+/// Checks a proof that `y` in G1 and `z` in the target group of the pairing `E` have the
+/// same discrete log w.r.t. `g` and `h`.
+pub fn verify_in_target<E: Pairing>(
+    g: &E::G1,
+    y: &E::G1,
+    h: &PairingOutput<E>,
+    z: &PairingOutput<E>,
+    proof: &DleqProof<E::G1>,
+) -> Result<bool, SerializationError> {
+    // A = g^s · y^c, B = h^s · z^c
+    let a = g.mul(proof.response) + y.mul(proof.challenge);
+    let b = *h * proof.response + *z * proof.challenge;
+
+    let challenge = hash_in_target_to_scalar::<E>(HashDomainSeparator::ChallengeInTarget as u8, &[*g, *y, a], &[*h, *z, b], &[])?;
+
+    Ok(challenge == proof.challenge)
+}
+
+fn hash_in_target_to_scalar<E: Pairing>(
+    domain_separator: u8,
+    group_inputs: &[E::G1],
+    target_inputs: &[PairingOutput<E>],
+    scalar_inputs: &[E::ScalarField],
+) -> Result<E::ScalarField, SerializationError> {
+    let mut hash_input = Vec::new();
+
+    domain_separator.serialize_compressed(&mut hash_input)?;
+
+    for input in group_inputs {
+        input.serialize_compressed(&mut hash_input)?;
+    }
+    for input in target_inputs {
+        input.serialize_compressed(&mut hash_input)?;
+    }
+    for input in scalar_inputs {
+        input.serialize_compressed(&mut hash_input)?;
+    }
+
+    let hash_digest = Blake2s::digest(&hash_input);
+    Ok(E::ScalarField::from_le_bytes_mod_order(&hash_digest))
+}
+
+fn hash_to_scalar<C: Group>(
+    domain_separator: u8,
+    group_inputs: &[C],
+    scalar_inputs: &[C::ScalarField],
+) -> Result<C::ScalarField, SerializationError> {
+    let mut hash_input = Vec::new();
+
+    domain_separator.serialize_compressed(&mut hash_input)?;
+
+    for input in group_inputs {
+        input.serialize_compressed(&mut hash_input)?;
+    }
+    for input in scalar_inputs {
+        input.serialize_compressed(&mut hash_input)?;
+    }
+
+    let hash_digest = Blake2s::digest(&hash_input);
+    Ok(C::ScalarField::from_le_bytes_mod_order(&hash_digest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::{Bls12_381, Fr, G1Affine, G1Projective, G2Affine};
+    use ark_ff::UniformRand;
+    use ark_std::test_rng;
+
+    #[test]
+    fn test_prove_and_verify() {
+        let rng = &mut test_rng();
+        let g = G1Projective::generator();
+        let h = g.mul(Fr::rand(rng));
+        let x = Fr::rand(rng);
+
+        let y = g.mul(x);
+        let z = h.mul(x);
+
+        let proof = prove::<G1Projective>(&g, &h, &x).unwrap();
+        assert!(verify::<G1Projective>(&g, &y, &h, &z, &proof).unwrap());
+    }
+
+    #[test]
+    fn test_reject_different_exponents() {
+        let rng = &mut test_rng();
+        let g = G1Projective::generator();
+        let h = g.mul(Fr::rand(rng));
+        let x = Fr::rand(rng);
+
+        let y = g.mul(x);
+        // z is computed with a different exponent than y
+        let z = h.mul(x + Fr::from(1u64));
+
+        let proof = prove::<G1Projective>(&g, &h, &x).unwrap();
+        assert!(!verify::<G1Projective>(&g, &y, &h, &z, &proof).unwrap());
+    }
+
+    #[test]
+    fn test_prove_and_verify_in_target() {
+        let rng = &mut test_rng();
+        let g = G1Projective::generator();
+        let h = Bls12_381::pairing(G1Affine::rand(rng), G2Affine::rand(rng));
+        let x = Fr::rand(rng);
+
+        let y = g.mul(x);
+        let proof = prove_in_target::<Bls12_381>(&g, &h, &x).unwrap();
+        assert!(verify_in_target::<Bls12_381>(&g, &y, &h, &(h * x), &proof).unwrap());
+
+        // z is computed with a different exponent than y
+        assert!(!verify_in_target::<Bls12_381>(&g, &y, &h, &(h * (x + Fr::from(1u64))), &proof).unwrap());
+    }
+}
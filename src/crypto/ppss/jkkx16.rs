@@ -4,35 +4,62 @@
 
 use std::fmt;
 use ark_crypto_primitives::Error;
-use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use ark_std::{ops::*, Zero, rand::Rng, hash::Hash, marker::PhantomData, vec::Vec};
-use ark_bls12_381::{
-    g1::Config as G1Config,
-    g2::Config as G2Config,
-    G1Affine, G2Affine, G1Projective, G2Projective,
-    Bls12_381, Fq, Fq2, Fr
-};
-use ark_ec::{
-    CurveGroup, CurveConfig, AffineRepr, short_weierstrass::{Affine, Projective},
-    hashing::{HashToCurve, HashToCurveError, curve_maps::wb::WBMap, map_to_curve_hasher::MapToCurveBasedHasher}
-};
-use ark_ff::{Field, fields::PrimeField, UniformRand, field_hashers::DefaultFieldHasher, BigInteger256};
+use ark_ec::{Group, hashing::HashToCurveError};
+use ark_ff::{Field, fields::PrimeField, UniformRand};
 
 use blake2::Blake2s;
 use digest::Digest;
+use hkdf::Hkdf;
 use sha2::Sha256;
 
-use super::PpssPcheme;
+use super::{PpssPcheme, Reconstruction, ServerKey};
+use super::{dleq, dleq::DleqProof};
+use super::pow::ProofOfWork;
+use super::token::Token;
+use super::lagrange::DuplicateCoordinateError;
 use super::sss;
+use super::suite::{Bls12381G1, CurveSuite};
+
+/// Scalars of the group of the suite `S`.
+type Scalar<S> = <<S as CurveSuite>::Group as Group>::ScalarField;
 
 /// Error enum to wrap underlying failures in arkworks operations, or wrap errors from dependencies.
 /// Inspired by this excellent post: <https://blog.burntsushi.net/rust-error-handling>
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum JKKX16Error {
     /// Error when creating setup parameters
     SetupError,
     /// Happens when the infinity bit is set in an encoding point, but the rest of the bytes aren't correctly zero'd
     InvalidPinError,
+    /// Happens when a keyper's PRF evaluation does not come with a valid proof
+    /// w.r.t. its public key; carries the position of the offending keyper
+    InvalidProofError(usize),
+    /// Happens when a keyper answers with a public key other than the one
+    /// pinned in the ciphertext at keygen; carries the index of the keyper
+    KeyMismatchError(usize),
+    /// Happens when fewer keypers respond than the threshold of the sharing;
+    /// carries the number of responses and the threshold
+    NotEnoughResponsesError(usize, usize),
+    /// Happens when the same keyper index appears in more than one response
+    DuplicateResponseError(usize),
+    /// Happens when a request asks for a key version the server does not hold
+    UnknownKeyVersionError(u32),
+    /// Happens when a dealer's share in the distributed key generation does not
+    /// match its commitments; carries the index of the dealer
+    InvalidDealingError(usize),
+    /// Happens when a share decrypted with a keyper's response does not lie on the
+    /// polynomial committed to in the ciphertext, while other shares do, or shares its
+    /// x-coordinate with another one; carries the index of the keyper
+    InvalidShareError(usize),
+    /// Happens when asked for a sharing whose threshold is zero or exceeds the
+    /// number of shares; carries the threshold and the number of shares
+    InvalidThresholdError(usize, usize),
+    /// Happens when too many combinations of keyper keys fail to match the joint key
+    /// to try them all; carries the number of combinations tried
+    SearchLimitError(usize),
     /// Error coming from `ark_serialize` upon deserialization
     SerializationError(ark_serialize::SerializationError),
     /// Error coming from `ark_ec` upon hashing to curve
@@ -48,6 +75,24 @@ impl fmt::Display for JKKX16Error {
                 write!(f, "Error during setup"),
             JKKX16Error::InvalidPinError => 
                 write!(f, "Error recovering secret, likely due to invalid pin."),
+            JKKX16Error::InvalidProofError(server) =>
+                write!(f, "Keyper {} returned a PRF evaluation that does not match its public key.", server),
+            JKKX16Error::KeyMismatchError(server) =>
+                write!(f, "Keyper {} answered with a public key other than the one pinned at keygen.", server),
+            JKKX16Error::NotEnoughResponsesError(received, threshold) =>
+                write!(f, "Received {} keyper responses, but {} are needed to reconstruct.", received, threshold),
+            JKKX16Error::DuplicateResponseError(server) =>
+                write!(f, "Received more than one response from keyper {}.", server),
+            JKKX16Error::UnknownKeyVersionError(version) =>
+                write!(f, "The keyper does not hold a key of version {}.", version),
+            JKKX16Error::InvalidDealingError(dealer) =>
+                write!(f, "Dealer {} sent a key share that does not match its commitments.", dealer),
+            JKKX16Error::InvalidShareError(server) =>
+                write!(f, "The share decrypted with the response of keyper {} does not match the commitments or repeats another share's x-coordinate.", server),
+            JKKX16Error::InvalidThresholdError(threshold, num_shares) =>
+                write!(f, "Cannot share with threshold {} among {} parties.", threshold, num_shares),
+            JKKX16Error::SearchLimitError(tried) =>
+                write!(f, "None of the {} combinations of keyper keys tried matches the joint key.", tried),
             JKKX16Error::SerializationError(ref err) => 
                 err.fmt(f),
             JKKX16Error::HashingError(ref err) => 
@@ -56,61 +101,127 @@ impl fmt::Display for JKKX16Error {
     }
 }
 
-pub struct JKKX16 {
-    _group: PhantomData<G1Config>,
+/// The scheme over the curve of the suite `S`.
+pub struct JKKX16Scheme<S: CurveSuite> {
+    _suite: PhantomData<S>,
 }
 
+/// The scheme over BLS12-381 G1, which vaults use unless configured otherwise.
+pub type JKKX16 = JKKX16Scheme<Bls12381G1>;
+
 #[derive(Clone, Debug)]
-pub struct Parameters<C: CurveGroup> {
-    pub generator: C::Affine,
+pub struct Parameters<C: Group> {
+    pub generator: C,
 }
 
 pub type SecretKey = [u8; 16];
 
+/// The key a keyper used at keygen, as pinned in the ciphertext.
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PinnedKey<C: Group> {
+    /// index of the keyper, which is also the position of its share
+    pub server: usize,
+    /// version of the keyper's key, which the keyper needs to re-derive it
+    pub key_version: u32,
+    pub public_key: C,
+}
+
 #[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
-pub struct Ciphertext<C: CurveGroup> {
+pub struct Ciphertext<C: Group> {
     encrypted_shares: Vec<(C::ScalarField, C::ScalarField)>,
+    /// Feldman commitments to the coefficients of the sharing polynomial, in a base
+    /// derived from the secret, against which each decrypted share is checked once
+    /// the secret is reconstructed. In the generator instead, they would let a single
+    /// keyper confirm a pin guess by checking the share it decrypts with its own key.
+    commitments: Vec<C>,
+    /// keys of the keypers at keygen, tagged with the keyper index
+    server_keys: Vec<PinnedKey<C>>,
+    /// number of keyper responses needed to reconstruct
+    threshold: usize,
     hash: C::ScalarField, 
 }
 
+impl<C: Group> Ciphertext<C> {
+    /// Returns the key pinned for the keyper with the given index.
+    pub fn server_key(&self, server: usize) -> Option<&PinnedKey<C>> {
+        self.server_keys
+            .iter()
+            .find(|pinned| pinned.server == server)
+    }
+
+    /// Returns the number of keypers the shares were split across.
+    pub fn num_servers(&self) -> usize {
+        self.encrypted_shares.len()
+    }
+
+    /// Returns the number of keyper responses needed to reconstruct.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
 #[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
-pub struct PrfInput<C: CurveGroup> {
-    pub blinded_prf_input: C::Affine,
+pub struct PrfInput<C: Group> {
+    pub blinded_prf_input: C,
     pub client_id: Vec<u8>,
+    /// version of the server key to evaluate with; the server's current key if unset
+    pub key_version: Option<u32>,
+    /// solution to the keyper's puzzle, for keypers that demand one
+    pub proof_of_work: Option<ProofOfWork>,
+    /// anonymous token paying for the evaluation, for keypers that demand one
+    pub token: Option<Token<C>>,
+}
+
+impl<C: Group> PrfInput<C> {
+    /// The part of the request that proofs of work and tokens are bound to: the blinded
+    /// input, the client id and the key version, so that neither can be reused for other
+    /// requests, nor for the same request under another of the keyper's keys.
+    pub fn binding(&self) -> Result<Vec<u8>, SerializationError> {
+        let mut input = Vec::new();
+        self.blinded_prf_input.serialize_compressed(&mut input)?;
+        self.client_id.serialize_compressed(&mut input)?;
+        self.key_version.serialize_compressed(&mut input)?;
+        Ok(input)
+    }
 }
 
 #[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
-pub struct PrfOutput<C: CurveGroup> {
-    pub public_key: C::Affine,
-    pub blinded_prf_output: C::Affine,
+pub struct PrfOutput<C: Group> {
+    pub key_version: u32,
+    pub public_key: C,
+    pub blinded_prf_output: C,
+    pub proof: DleqProof<C>,
 }
 
 #[derive(Clone, Debug)]
-pub struct ClientState<C: CurveGroup> {
+pub struct ClientState<C: Group> {
     pub blind_scalar: C::ScalarField,
+    pub blinded_prf_input: C,
     pub client_id: Vec<u8>,
     pub password: Vec<u8>,
 }
 
+#[allow(clippy::enum_variant_names)]
 enum HashDomainSeparator {
-    ServerKeyDerivation = 0,
     MaskDerivation = 1,
     DataKeyDerivation = 2,
     ReconstructionCheckDerivation = 3,
+    CommitmentBaseDerivation = 4,
 }
 
-impl PpssPcheme for JKKX16
+impl<S: CurveSuite> PpssPcheme for JKKX16Scheme<S>
 {
-    type Parameters = Parameters<G1Projective>;
+    type Parameters = Parameters<S::Group>;
+    type ServerKey = ServerKey;
     type SecretKey = SecretKey;
-    type PrfInput = PrfInput<G1Projective>;
-    type PrfOutput = PrfOutput<G1Projective>;
-    type Ciphertext = Ciphertext<G1Projective>;
-    type ClientState = ClientState<G1Projective>;
+    type PrfInput = PrfInput<S::Group>;
+    type PrfOutput = PrfOutput<S::Group>;
+    type Ciphertext = Ciphertext<S::Group>;
+    type ClientState = ClientState<S::Group>;
 
     /// Generates the public parameters for the scheme.
     fn setup<R: Rng>(_rng: &mut R) -> Result<Self::Parameters, Error> {
-        let generator = Affine::<G1Config>::generator().into();
+        let generator = S::Group::generator();
         Ok(Parameters { generator })
     }
 
@@ -121,11 +232,12 @@ impl PpssPcheme for JKKX16
         password: &[u8],
         rng: &mut R,
     ) -> Result<(Self::ClientState, Self::PrfInput), Error> {
-        let (blind, prf_input) = oprf_input(client_id, password, rng)
-            .map_err(|e| JKKX16Error::HashingError(e))?;
+        let (blind, prf_input) = oprf_input::<S, _>(client_id, password, rng)
+            .map_err(JKKX16Error::HashingError)?;
 
         let state = ClientState { 
             blind_scalar: blind,
+            blinded_prf_input: prf_input.blinded_prf_input,
             client_id: client_id.to_vec(),
             password: password.to_vec(),
         };
@@ -135,12 +247,11 @@ impl PpssPcheme for JKKX16
 
     fn server_process_keygen_request(
         pp: &Self::Parameters,
-        seed: &[u8; 32],
+        key: &ServerKey,
         client_id: &[u8],
         input: &Self::PrfInput,
     ) -> Result<Self::PrfOutput, Error> {
-        evaluate_prf(pp, seed, client_id, input)
-            .map_err(|e| JKKX16Error::SerializationError(e).into())
+        Ok(evaluate_prf::<S>(pp, key, client_id, input)?)
     }
 
     fn client_keygen<R: Rng>(
@@ -151,47 +262,58 @@ impl PpssPcheme for JKKX16
         threshold: usize,
         rng: &mut R,
     ) -> Result<(Self::SecretKey, Self::Ciphertext), Error> {
-        assert!(server_responses.len() == num_servers);
-        let secret = Fr::rand(rng);
-        let shares = sss::share(secret, threshold, num_servers);
+        // every keyper holds a share, so keygen needs a response from each of them
+        if server_responses.len() != num_servers {
+            return Err(JKKX16Error::NotEnoughResponsesError(server_responses.len(), num_servers).into());
+        }
+        let secret = Scalar::<S>::rand(rng);
+        let base = commitment_base::<S::Group>(&secret)?;
+        let (shares, commitments) = sss::share_verifiable::<S::Group, _>(secret, threshold, num_servers, &base, rng)?;
 
         let mut encrypted_shares = Vec::new();
+        let mut server_keys = Vec::new();
         for (i, server_output) in server_responses.iter().enumerate() {
-            let prf_output: G1Affine = server_output.blinded_prf_output
-                .mul(&state.blind_scalar.inverse().expect("blind should not be zero"))
-                .into();
+            let prf_output = unblind_prf_output(pp, state, i, server_output)?;
 
             // e := H(password || prf_output);
-            let mask_i = hash_to_fr(
+            let mask_i = hash_to_fr::<S::Group>(
                 HashDomainSeparator::MaskDerivation as u8,
                 &[prf_output],
                 &[],
-                &[state.password.clone()]
-            ).map_err(|e| JKKX16Error::SerializationError(e))?;
+                std::slice::from_ref(&state.password)
+            ).map_err(JKKX16Error::SerializationError)?;
 
             encrypted_shares.push((shares[i].0, shares[i].1 + mask_i));
+            server_keys.push(PinnedKey {
+                server: i,
+                key_version: server_output.key_version,
+                public_key: server_output.public_key,
+            });
         }
 
         // H3(0, s) in the paper
         let hashed_secret = fr_to_32bytes(
-            hash_to_fr(HashDomainSeparator::DataKeyDerivation as u8, &[], &[secret], &[])
-            .map_err(|e| JKKX16Error::SerializationError(e))?
+            hash_to_fr::<S::Group>(HashDomainSeparator::DataKeyDerivation as u8, &[], &[secret], &[])
+            .map_err(JKKX16Error::SerializationError)?
         );
         let mut r = [0u8; 16]; r.copy_from_slice(&hashed_secret[0..16]);
         let mut key = [0u8; 16]; key.copy_from_slice(&hashed_secret[16..32]);
 
-        // H3(1, pw, e, s, r) in the paper
-        let mut ys: Vec<Fr> = Vec::new();
-        ys.extend(encrypted_shares.iter().map(|(x, y)| *y).collect::<Vec<Fr>>());
-        ys.extend(shares.iter().map(|(x, y)| *y).collect::<Vec<Fr>>());
-        let c = hash_to_fr(
+        // H3(1, pw, e, s, r) in the paper, additionally binding the pinned keys and the commitments
+        let mut ys: Vec<Scalar<S>> = Vec::new();
+        ys.extend(encrypted_shares.iter().map(|(x, y)| *y).collect::<Vec<Scalar<S>>>());
+        ys.extend(shares.iter().map(|(x, y)| *y).collect::<Vec<Scalar<S>>>());
+        let pks: Vec<S::Group> = server_keys.iter().map(|pinned| pinned.public_key)
+            .chain(commitments.iter().copied())
+            .collect();
+        let c = hash_to_fr::<S::Group>(
             HashDomainSeparator::ReconstructionCheckDerivation as u8,
-            &[],
+            &pks,
             &ys,
             &[state.password.to_vec(), r.to_vec()]
-        ).map_err(|e| JKKX16Error::SerializationError(e))?;
+        ).map_err(JKKX16Error::SerializationError)?;
 
-        let ctxt: Ciphertext<G1Projective> = Ciphertext { encrypted_shares, hash: c };
+        let ctxt: Ciphertext<S::Group> = Ciphertext { encrypted_shares, commitments, server_keys, threshold, hash: c };
 
         Ok((key, ctxt))
     }
@@ -202,11 +324,12 @@ impl PpssPcheme for JKKX16
         password: &[u8],
         rng: &mut R
     ) -> Result<(Self::ClientState, Self::PrfInput), Error> {
-        let (blind, prf_input) = oprf_input(client_id, password, rng)
-            .map_err(|e| JKKX16Error::HashingError(e))?;
+        let (blind, prf_input) = oprf_input::<S, _>(client_id, password, rng)
+            .map_err(JKKX16Error::HashingError)?;
 
         let state = ClientState { 
             blind_scalar: blind,
+            blinded_prf_input: prf_input.blinded_prf_input,
             client_id: client_id.to_vec(),
             password: password.to_vec(),
         };
@@ -216,141 +339,325 @@ impl PpssPcheme for JKKX16
 
     fn server_process_reconstruct_request(
         pp: &Self::Parameters,
-        seed: &[u8; 32],
+        key: &ServerKey,
         client_id: &[u8],
         input: &Self::PrfInput,
     ) -> Result<Self::PrfOutput, Error> {
-        evaluate_prf(pp, seed, client_id, input)
-            .map_err(|e| JKKX16Error::SerializationError(e).into())
+        Ok(evaluate_prf::<S>(pp, key, client_id, input)?)
     }
 
     fn client_reconstruct(
         pp: &Self::Parameters,
         state: &Self::ClientState,
-        server_responses: &[Self::PrfOutput],
+        server_responses: &[(usize, Self::PrfOutput)],
         ciphertext: &Self::Ciphertext,
-    ) -> Result<Self::SecretKey, Error> {
-        
-        let mut shares = Vec::new();
-        for (i, server_output) in server_responses.iter().enumerate() {
-            let prf_output: G1Affine = server_output.blinded_prf_output
-                .mul(&state.blind_scalar.inverse().expect("blind should not be zero"))
-                .into();
+    ) -> Result<Reconstruction<Self::SecretKey>, Error> {
+        let CheckedResponses { passed: shares, responded, .. } = check_responses(server_responses, false, |i, server_output| {
+            decrypt_share(pp, state, ciphertext, i, server_output)
+        })?;
 
-            // e := H(password || prf_output);
-            let mask_i = hash_to_fr(
-                HashDomainSeparator::MaskDerivation as u8,
-                &[prf_output],
-                &[],
-                &[state.password.clone()]
-            ).map_err(|e| JKKX16Error::SerializationError(e))?;
-            
-            let e_i = ciphertext.encrypted_shares[i];
-            shares.push((e_i.0, e_i.1 - mask_i));
+        if shares.len() < ciphertext.threshold {
+            return Err(JKKX16Error::NotEnoughResponsesError(shares.len(), ciphertext.threshold).into());
         }
 
-        // interpolate the shares to get the secret
-        let secret = sss::recover(&shares);
+        // a bad share among the first threshold ones fails the reconstruction check
+        // just as a wrong pin does; those beyond are checked against the commitments,
+        // which only the reconstructed secret opens
+        let (key, secret) = reconstruct_from_shares(state, &shares[..ciphertext.threshold], ciphertext)?;
+        let base = commitment_base::<S::Group>(&secret)?;
+        if let Some(&i) = invalid_shares(ciphertext, &base, &shares[ciphertext.threshold..]).first() {
+            return Err(JKKX16Error::InvalidShareError(i).into());
+        }
 
-        let hashed_secret = fr_to_32bytes(
-            hash_to_fr(
-                HashDomainSeparator::DataKeyDerivation as u8, &[], &[secret], &[]
-            ).map_err(|e| JKKX16Error::SerializationError(e))?
-        );
-        let mut r = [0u8; 16]; r.copy_from_slice(&hashed_secret[0..16]);
-        let mut key = [0u8; 16]; key.copy_from_slice(&hashed_secret[16..32]);
+        Ok(Reconstruction {
+            key,
+            missing_servers: missing_servers(ciphertext.num_servers(), &responded),
+            inconsistent_servers: vec![],
+        })
+    }
+
+    fn client_reconstruct_robust(
+        pp: &Self::Parameters,
+        state: &Self::ClientState,
+        server_responses: &[(usize, Self::PrfOutput)],
+        ciphertext: &Self::Ciphertext,
+    ) -> Result<Reconstruction<Self::SecretKey>, Error> {
+
+        // responses that fail the key or proof checks are excluded up front
+        let CheckedResponses { passed: shares, responded, failed: mut inconsistent_servers } =
+            check_responses(server_responses, true, |i, server_output| {
+                decrypt_share(pp, state, ciphertext, i, server_output)
+            })?;
+
+        if shares.len() < ciphertext.threshold {
+            return Err(JKKX16Error::NotEnoughResponsesError(shares.len(), ciphertext.threshold).into());
+        }
+
+        // the proofs tie each remaining share to its keyper's pinned key, so the shares
+        // are all right or, with a wrong pin, all wrong: the first threshold ones are
+        // interpolated once, and any beyond not on the committed polynomial is inconsistent
+        let (key, secret) = reconstruct_from_shares(state, &shares[..ciphertext.threshold], ciphertext)?;
+        let base = commitment_base::<S::Group>(&secret)?;
+        inconsistent_servers.extend(invalid_shares(ciphertext, &base, &shares[ciphertext.threshold..]));
+        inconsistent_servers.sort();
+
+        Ok(Reconstruction {
+            key,
+            missing_servers: missing_servers(ciphertext.num_servers(), &responded),
+            inconsistent_servers,
+        })
+    }
+
+}
 
-        let mut ys: Vec<Fr> = Vec::new();
-        ys.extend(ciphertext.encrypted_shares.iter().map(|(x, y)| *y).collect::<Vec<Fr>>());
-        ys.extend(shares.iter().map(|(x, y)| *y).collect::<Vec<Fr>>());
+/// Checks the response of the keyper with index `server` against its pinned key,
+/// and uses the unblinded PRF output to decrypt the keyper's share.
+fn decrypt_share<C: Group>(
+    pp: &Parameters<C>,
+    state: &ClientState<C>,
+    ciphertext: &Ciphertext<C>,
+    server: usize,
+    server_output: &PrfOutput<C>,
+) -> Result<(C::ScalarField, C::ScalarField), JKKX16Error> {
+    // the keyper must still hold the key it used at keygen
+    let pinned = ciphertext.server_key(server).ok_or(JKKX16Error::KeyMismatchError(server))?;
+    if pinned.public_key != server_output.public_key || pinned.key_version != server_output.key_version {
+        return Err(JKKX16Error::KeyMismatchError(server));
+    }
+
+    let prf_output = unblind_prf_output(pp, state, server, server_output)?;
+
+    // e := H(password || prf_output);
+    let mask = hash_to_fr::<C>(
+        HashDomainSeparator::MaskDerivation as u8,
+        &[prf_output],
+        &[],
+        std::slice::from_ref(&state.password)
+    ).map_err(JKKX16Error::SerializationError)?;
+
+    let (x, y) = *ciphertext.encrypted_shares.get(server).ok_or(JKKX16Error::InvalidShareError(server))?;
+    Ok((x, y - mask))
+}
 
-        let c = hash_to_fr(HashDomainSeparator::ReconstructionCheckDerivation as u8, &[], &ys, &[state.password.to_vec(), r.to_vec()])?;
+/// Returns the indices of the keypers whose decrypted shares do not lie on the committed polynomial.
+fn invalid_shares<C: Group>(
+    ciphertext: &Ciphertext<C>,
+    base: &C,
+    shares: &[(usize, sss::Share<C::ScalarField>)],
+) -> Vec<usize> {
+    shares.iter()
+        .filter(|(_, share)| !sss::verify_share::<C>(&ciphertext.commitments, base, share))
+        .map(|(i, _)| *i)
+        .collect()
+}
+
+/// The keypers' responses, each checked on its own, e.g. against the keyper's pinned key.
+pub(super) struct CheckedResponses<T> {
+    /// output of the check on each response that passed it, with the index of its keyper
+    pub passed: Vec<(usize, T)>,
+    /// indices of all keypers that responded
+    pub responded: Vec<usize>,
+    /// indices of the keypers whose responses failed the check, in robust mode
+    pub failed: Vec<usize>,
+}
 
-        if c == ciphertext.hash {
-            return Ok(key);
-        } else {
-            return Err(JKKX16Error::InvalidPinError.into());
+/// Runs `check` on each response, given with the index of its keyper, and rejects a second
+/// response from the same keyper. The first failing response fails the reconstruction, except
+/// in robust mode, where responses that fail their keyper's key or proof checks, or that do
+/// not deserialize, are set aside.
+pub(super) fn check_responses<O, T>(
+    server_responses: &[(usize, O)],
+    robust: bool,
+    mut check: impl FnMut(usize, &O) -> Result<T, JKKX16Error>,
+) -> Result<CheckedResponses<T>, JKKX16Error> {
+    let mut checked = CheckedResponses { passed: Vec::new(), responded: Vec::new(), failed: Vec::new() };
+    for (i, server_output) in server_responses.iter() {
+        let i = *i;
+        if checked.responded.contains(&i) {
+            return Err(JKKX16Error::DuplicateResponseError(i));
         }
+        checked.responded.push(i);
+
+        match check(i, server_output) {
+            Ok(output) => checked.passed.push((i, output)),
+            Err(JKKX16Error::KeyMismatchError(_)) |
+            Err(JKKX16Error::InvalidProofError(_)) |
+            Err(JKKX16Error::SerializationError(_)) if robust => checked.failed.push(i),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(checked)
+}
+
+/// Returns the indices of the keypers, out of `num_servers`, that did not respond.
+pub(super) fn missing_servers(num_servers: usize, responded: &[usize]) -> Vec<usize> {
+    (0..num_servers)
+        .filter(|i| !responded.contains(i))
+        .collect()
+}
+
+/// Interpolates a threshold number of decrypted shares, each given with the index of its
+/// keyper, into the secret, and recomputes the shares at `xs` from the interpolated polynomial.
+/// Two shares at the same x-coordinate, which only a tampered ciphertext holds, are reported
+/// against the keyper of the later one.
+pub(super) fn recover_shares<F: PrimeField>(
+    shares: &[(usize, sss::Share<F>)],
+    xs: &[F],
+) -> Result<(F, Vec<F>), JKKX16Error> {
+    let to_share_error = |DuplicateCoordinateError(j)| JKKX16Error::InvalidShareError(shares[j].0);
+    let shares: Vec<_> = shares.iter().map(|(_, share)| *share).collect();
+
+    let secret = sss::recover(&shares).map_err(to_share_error)?;
+    let recomputed = sss::evaluate_many(&shares, xs).map_err(to_share_error)?;
+
+    Ok((secret, recomputed))
+}
+
+/// Interpolates a threshold number of decrypted shares, each given with the index of
+/// its keyper, recomputes the shares of all keypers from the interpolated polynomial,
+/// and runs the reconstruction check; outputs the data key along with the secret.
+fn reconstruct_from_shares<C: Group>(
+    state: &ClientState<C>,
+    shares: &[(usize, sss::Share<C::ScalarField>)],
+    ciphertext: &Ciphertext<C>,
+) -> Result<(SecretKey, C::ScalarField), JKKX16Error> {
+    let xs: Vec<C::ScalarField> = ciphertext.encrypted_shares.iter().map(|(x, _)| *x).collect();
+    let (secret, recomputed) = recover_shares(shares, &xs)?;
+
+    let hashed_secret = fr_to_32bytes(
+        hash_to_fr::<C>(
+            HashDomainSeparator::DataKeyDerivation as u8, &[], &[secret], &[]
+        ).map_err(JKKX16Error::SerializationError)?
+    );
+    let mut r = [0u8; 16]; r.copy_from_slice(&hashed_secret[0..16]);
+    let mut key = [0u8; 16]; key.copy_from_slice(&hashed_secret[16..32]);
+
+    let ys: Vec<C::ScalarField> = ciphertext.encrypted_shares.iter().map(|(_, y)| *y)
+        .chain(recomputed)
+        .collect();
+
+    let pks: Vec<C> = ciphertext.server_keys.iter().map(|pinned| pinned.public_key)
+        .chain(ciphertext.commitments.iter().copied())
+        .collect();
+
+    let c = hash_to_fr::<C>(HashDomainSeparator::ReconstructionCheckDerivation as u8, &pks, &ys, &[state.password.to_vec(), r.to_vec()])
+        .map_err(JKKX16Error::SerializationError)?;
+
+    if c == ciphertext.hash {
+        Ok((key, secret))
+    } else {
+        Err(JKKX16Error::InvalidPinError)
     }
+}
 
+/// The base of the commitments, g^{H(secret)}, which is known only to those who
+/// know the secret.
+fn commitment_base<C: Group>(secret: &C::ScalarField) -> Result<C, JKKX16Error> {
+    let exponent = hash_to_fr::<C>(HashDomainSeparator::CommitmentBaseDerivation as u8, &[], &[*secret], &[])
+        .map_err(JKKX16Error::SerializationError)?;
+    Ok(C::generator().mul(exponent))
 }
 
-fn oprf_input<R: Rng>(
+fn oprf_input<S: CurveSuite, R: Rng>(
     client_id: &[u8],
     password: &[u8],
     rng: &mut R
-) -> Result<(Fr, PrfInput<G1Projective>), HashToCurveError> {
+) -> Result<(Scalar<S>, PrfInput<S::Group>), HashToCurveError> {
     // hash the password to a group element
-    let password_hash: Affine::<G1Config> = hash_to_g1point(&password.to_vec())?;
+    let password_hash = S::hash_to_curve(password)?;
 
     // sample a non-zero random scalar
-    let mut blind = Fr::zero();
+    let mut blind = Scalar::<S>::zero();
     while blind.is_zero() {
-        blind = Fr::rand(rng);
+        blind = Scalar::<S>::rand(rng);
     }
 
-    let blinded_prf_input = password_hash.mul(&blind).into();
+    let blinded_prf_input = password_hash.mul(&blind);
 
-    let input = PrfInput { blinded_prf_input, client_id: client_id.to_vec() };
+    let input = PrfInput { blinded_prf_input, client_id: client_id.to_vec(), key_version: None, proof_of_work: None, token: None };
 
     Ok((blind, input))
 }
 
-fn evaluate_prf(
-    pp: &Parameters<G1Projective>,
-    seed: &[u8; 32],
+fn evaluate_prf<S: CurveSuite>(
+    pp: &Parameters<S::Group>,
+    key: &ServerKey,
     client_id: &[u8],
-    input: &PrfInput<G1Projective>
-) -> Result<PrfOutput<G1Projective>, ark_serialize::SerializationError> {
-    let (client_secret_key, client_public_key) = {
-        // TODO: do proper HKDF here. For now, Hash seed and client_id.
-        // k := H(seed || client_id);
-        let client_secret_key = hash_to_fr(
-            HashDomainSeparator::ServerKeyDerivation as u8,
-            &[],
-            &[],
-            &[seed.to_vec(), client_id.to_vec()]
-        )?;
-        
-        let client_public_key = pp.generator.mul(&client_secret_key).into();
-
-        (client_secret_key, client_public_key)
-    };
+    input: &PrfInput<S::Group>
+) -> Result<PrfOutput<S::Group>, JKKX16Error> {
+    if let Some(version) = input.key_version {
+        if version != key.version {
+            return Err(JKKX16Error::UnknownKeyVersionError(version));
+        }
+    }
+
+    let client_secret_key = derive_client_key::<S>(key, client_id);
+    let client_public_key = pp.generator.mul(&client_secret_key);
+
+    // prove that the evaluation used the secret key behind client_public_key
+    let proof = dleq::prove::<S::Group>(&pp.generator, &input.blinded_prf_input, &client_secret_key)
+        .map_err(JKKX16Error::SerializationError)?;
 
     let prf_output = PrfOutput {
-        blinded_prf_output: input.blinded_prf_input.mul(&client_secret_key).into(),
+        key_version: key.version,
+        blinded_prf_output: input.blinded_prf_input.mul(&client_secret_key),
         public_key: client_public_key,
+        proof,
     };
     Ok(prf_output)
 }
 
-const DST_G1: &str = "BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_";
-
-// Adapted from https://github.com/ArnaudBrousseau/bls_on_arkworks
-/// ([spec link](https://www.ietf.org/archive/id/draft-irtf-cfrg-bls-signature-05.html#section-1.3))
-/// A cryptographic hash function that takes as input an arbitrary octet string and returns a point on an
-/// elliptic curve. Functions of this kind are defined in [hash-to-curve-spec](https://datatracker.ietf.org/doc/html/draft-irtf-cfrg-hash-to-curve-16).
-fn hash_to_g1point(msg: impl AsRef<[u8]>) -> Result<Affine<G1Config>, HashToCurveError> {
-    let g1_mapper = MapToCurveBasedHasher::<
-        Projective<G1Config>,
-        DefaultFieldHasher<Sha256, 128>,
-        WBMap<G1Config>,
-    >::new(DST_G1.as_bytes())?;
-    let q: Affine<G1Config> = g1_mapper.hash(msg.as_ref())?;
-    Ok(q)
+/// Derives the server's per-client PRF key as k := HKDF-SHA256(seed, version || client_id),
+/// salted per curve. We expand to 48 bytes before reducing mod r, as in hash-to-field,
+/// so that the bias of the reduction is negligible.
+pub(super) fn derive_client_key<S: CurveSuite>(key: &ServerKey, client_id: &[u8]) -> Scalar<S> {
+    let hkdf = Hkdf::<Sha256>::new(Some(S::SERVER_KEY_SALT), &key.seed);
+
+    // the version has fixed length, so the info string is unambiguous
+    let mut info = key.version.to_be_bytes().to_vec();
+    info.extend_from_slice(client_id);
+
+    let mut okm = [0u8; 48];
+    hkdf.expand(&info, &mut okm).expect("48 bytes is a valid HKDF-SHA256 output length");
+
+    Scalar::<S>::from_be_bytes_mod_order(&okm)
+}
+
+/// Checks the keyper's proof of correct evaluation and strips the client's blind,
+/// yielding H(pw)^k for the keyper at position `server` in the list of responses.
+pub(super) fn unblind_prf_output<C: Group>(
+    pp: &Parameters<C>,
+    state: &ClientState<C>,
+    server: usize,
+    server_output: &PrfOutput<C>,
+) -> Result<C, JKKX16Error> {
+    let valid = dleq::verify::<C>(
+        &pp.generator,
+        &server_output.public_key,
+        &state.blinded_prf_input,
+        &server_output.blinded_prf_output,
+        &server_output.proof,
+    ).map_err(JKKX16Error::SerializationError)?;
+
+    if !valid {
+        return Err(JKKX16Error::InvalidProofError(server));
+    }
+
+    Ok(server_output.blinded_prf_output.mul(state.blind_scalar.inverse().expect("blind should not be zero")))
 }
 
-fn hash_to_fr(
+pub(super) fn hash_to_fr<C: Group>(
     domain_separator: u8,
-    affine_inputs: &[G1Affine],
-    scalar_inputs: &[Fr],
+    group_inputs: &[C],
+    scalar_inputs: &[C::ScalarField],
     bytearray_inputs: &[Vec<u8>]
-) -> Result<Fr, ark_serialize::SerializationError> {
+) -> Result<C::ScalarField, ark_serialize::SerializationError> {
     let mut hash_input = Vec::new();
 
     domain_separator.serialize_compressed(&mut hash_input)?;
 
-    for input in affine_inputs {
+    for input in group_inputs {
         input.serialize_compressed(&mut hash_input)?;
     }
     for input in scalar_inputs {
@@ -364,12 +671,12 @@ fn hash_to_fr(
     assert!(hash_digest.len() >= 32);
 
     let mut trimmed_hash_digest = [0u8; 32];
-    trimmed_hash_digest.copy_from_slice(&hash_digest.as_slice());
+    trimmed_hash_digest.copy_from_slice(hash_digest.as_slice());
     
-    Ok(Fr::from_le_bytes_mod_order(&trimmed_hash_digest))
+    Ok(C::ScalarField::from_le_bytes_mod_order(&trimmed_hash_digest))
 }
 
-fn fr_to_32bytes(fr: Fr) -> [u8; 32] {
+pub(super) fn fr_to_32bytes<F: PrimeField>(fr: F) -> [u8; 32] {
     let mut bytes = Vec::new();
     fr
         .serialize_compressed(&mut bytes)
@@ -378,4 +685,121 @@ fn fr_to_32bytes(fr: Fr) -> [u8; 32] {
     let mut padded_bytes = [0u8; 32];
     padded_bytes.copy_from_slice(&bytes);
     padded_bytes
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_std::test_rng;
+
+    #[test]
+    fn test_tampered_ciphertext_rejected() {
+        let pin = b"198837";
+        let client_id = b"alice@gmail.com";
+        let seeds: Vec<ServerKey> = (1..=5).map(|i| ServerKey::new(1, [i as u8; 32])).collect();
+
+        let rng = &mut test_rng();
+        let pp = JKKX16::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = JKKX16::client_generate_keygen_request(&pp, client_id, pin, rng).unwrap();
+        let prf_outs: Vec<_> = seeds.iter()
+            .map(|seed| JKKX16::server_process_keygen_request(&pp, seed, client_id, &prf_input).unwrap())
+            .collect();
+        let (key, ctxt) = JKKX16::client_keygen(&pp, &client_state, &prf_outs, 5, 3, rng).unwrap();
+
+        let err = JKKX16::client_keygen(&pp, &client_state, &prf_outs[..4], 5, 3, rng).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::NotEnoughResponsesError(4, 5))));
+
+        let (client_state, prf_input) = JKKX16::client_generate_reconstruct_request(&pp, client_id, pin, rng).unwrap();
+        let responses: Vec<_> = (0..5)
+            .map(|i| (i, JKKX16::server_process_reconstruct_request(&pp, &seeds[i], client_id, &prf_input).unwrap()))
+            .collect();
+        assert_eq!(key, JKKX16::client_reconstruct(&pp, &client_state, &responses, &ctxt).unwrap().key);
+
+        // the reconstruction check covers the encrypted shares and the commitments, so
+        // tampering with either fails it like a wrong pin, rather than blaming a keyper
+        let mut corrupted = ctxt.clone();
+        corrupted.encrypted_shares[4].1 += Scalar::<Bls12381G1>::from(1u64);
+        let err = JKKX16::client_reconstruct(&pp, &client_state, &responses, &corrupted).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidPinError)));
+        let mut corrupted = ctxt.clone();
+        corrupted.commitments[1] = corrupted.commitments[0];
+        let err = JKKX16::client_reconstruct(&pp, &client_state, &responses, &corrupted).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidPinError)));
+
+        // with a wrong pin, the reconstruction check fails
+        let (client_state, prf_input) = JKKX16::client_generate_reconstruct_request(&pp, client_id, b"000000", rng).unwrap();
+        let responses: Vec<_> = (0..3)
+            .map(|i| (i, JKKX16::server_process_reconstruct_request(&pp, &seeds[i], client_id, &prf_input).unwrap()))
+            .collect();
+        let err = JKKX16::client_reconstruct(&pp, &client_state, &responses, &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidPinError)));
+    }
+
+    #[test]
+    fn test_single_keyper_cannot_check_guess() {
+        let pin = b"198837";
+        let client_id = b"alice@gmail.com";
+        let seeds: Vec<ServerKey> = (1..=3).map(|i| ServerKey::new(1, [i as u8; 32])).collect();
+
+        let rng = &mut test_rng();
+        let pp = JKKX16::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = JKKX16::client_generate_keygen_request(&pp, client_id, pin, rng).unwrap();
+        let prf_outs: Vec<_> = seeds.iter()
+            .map(|seed| JKKX16::server_process_keygen_request(&pp, seed, client_id, &prf_input).unwrap())
+            .collect();
+        let (_, ctxt) = JKKX16::client_keygen(&pp, &client_state, &prf_outs, 3, 2, rng).unwrap();
+
+        // keyper 0 decrypts its share under each guess with its own key, but the commitments
+        // are in a base it does not know, so checking in the generator fails for any guess
+        let generator = <Bls12381G1 as CurveSuite>::Group::generator();
+        for guess in [&pin[..], b"000000"] {
+            let (client_state, prf_input) = JKKX16::client_generate_reconstruct_request(&pp, client_id, guess, rng).unwrap();
+            let response = JKKX16::server_process_reconstruct_request(&pp, &seeds[0], client_id, &prf_input).unwrap();
+            let share = decrypt_share(&pp, &client_state, &ctxt, 0, &response).unwrap();
+            assert!(!sss::verify_share::<<Bls12381G1 as CurveSuite>::Group>(&ctxt.commitments, &generator, &share));
+        }
+
+        // with a threshold number of responses, the client learns the base and the share checks
+        let (client_state, prf_input) = JKKX16::client_generate_reconstruct_request(&pp, client_id, pin, rng).unwrap();
+        let shares: Vec<_> = (0..2)
+            .map(|i| {
+                let response = JKKX16::server_process_reconstruct_request(&pp, &seeds[i], client_id, &prf_input).unwrap();
+                (i, decrypt_share(&pp, &client_state, &ctxt, i, &response).unwrap())
+            })
+            .collect();
+        let (_, secret) = reconstruct_from_shares(&client_state, &shares, &ctxt).unwrap();
+        let base = commitment_base::<<Bls12381G1 as CurveSuite>::Group>(&secret).unwrap();
+        assert!(sss::verify_share::<<Bls12381G1 as CurveSuite>::Group>(&ctxt.commitments, &base, &shares[0].1));
+    }
+
+    #[test]
+    fn test_duplicate_share_reported() {
+        let pin = b"198837";
+        let client_id = b"alice@gmail.com";
+        let seeds: Vec<ServerKey> = (1..=3).map(|i| ServerKey::new(1, [i as u8; 32])).collect();
+
+        let rng = &mut test_rng();
+        let pp = JKKX16::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = JKKX16::client_generate_keygen_request(&pp, client_id, pin, rng).unwrap();
+        let prf_outs: Vec<_> = seeds.iter()
+            .map(|seed| JKKX16::server_process_keygen_request(&pp, seed, client_id, &prf_input).unwrap())
+            .collect();
+        let (_, ctxt) = JKKX16::client_keygen(&pp, &client_state, &prf_outs, 3, 2, rng).unwrap();
+
+        // keyper 1's slot is a copy of keyper 0's, so both decrypt to the same valid share
+        let mut corrupted = ctxt.clone();
+        corrupted.encrypted_shares[1] = corrupted.encrypted_shares[0];
+        corrupted.server_keys[1] = PinnedKey { server: 1, ..corrupted.server_keys[0].clone() };
+
+        let (client_state, prf_input) = JKKX16::client_generate_reconstruct_request(&pp, client_id, pin, rng).unwrap();
+        let response = JKKX16::server_process_reconstruct_request(&pp, &seeds[0], client_id, &prf_input).unwrap();
+        let responses = vec![(0, response.clone()), (1, response)];
+
+        let err = JKKX16::client_reconstruct(&pp, &client_state, &responses, &corrupted).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidShareError(1))));
+        let err = JKKX16::client_reconstruct_robust(&pp, &client_state, &responses, &corrupted).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidShareError(1))));
+    }
+}
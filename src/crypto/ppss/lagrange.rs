@@ -1,29 +1,186 @@
-use ark_ff::Field;
-
-/// computes the Lagrange coefficient for the i-th point amongst the x-coordinates `xs`;
-/// The output coefficient is the result of evaluating the Lagrange polynomial at the point `x`.
-pub fn lagrange_coefficient<F: Field>(xs: &[F], i: usize, x: &F) -> F {
-    // source: https://en.wikipedia.org/wiki/Lagrange_polynomial
-
-    // the ith point must be within the range of the x-coordinates
-    assert!(i < xs.len(), "index out of bounds");
-    //assert that the x-coordinates are distinct
-    assert_eq!(
-        xs.iter().collect::<std::collections::HashSet<_>>().len(),
-        xs.len(),
-        "x-coordinates must be distinct"
-    );
-
-    let xi = xs[i];
-    let mut numerator = F::one();
-    let mut denominator = F::one();
-    for (j, xj) in xs.iter().enumerate() {
-        if i != j {
-            numerator *= *x - xj;
-            denominator *= xi - xj;
+use std::fmt;
+
+use ark_ec::Group;
+use ark_ff::{Field, batch_inversion, batch_inversion_and_mul};
+
+/// Happens when two points share an x-coordinate, through which no polynomial can be
+/// interpolated; carries the position of the later of the two
+#[derive(Debug, PartialEq, Eq)]
+pub struct DuplicateCoordinateError(pub usize);
+
+impl std::error::Error for DuplicateCoordinateError {}
+
+impl fmt::Display for DuplicateCoordinateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Point {} repeats the x-coordinate of an earlier point.", self.0)
+    }
+}
+
+/// Lagrange interpolation over a fixed set of distinct x-coordinates.
+/// source: https://en.wikipedia.org/wiki/Lagrange_polynomial#Barycentric_form
+///
+/// The barycentric weights w_i = 1 / prod_{j != i} (x_i - x_j) are computed once,
+/// with a single batch inversion; thereafter, the coefficients at any point cost
+/// O(n) multiplications and one more inversion, rather than n inversions.
+/// The coefficients at x = 0, where our secrets are embedded, are cached.
+#[derive(Clone, Debug)]
+pub struct Interpolator<F: Field> {
+    xs: Vec<F>,
+    weights: Vec<F>,
+    at_zero: Vec<F>,
+}
+
+impl<F: Field> Interpolator<F> {
+    pub fn new(xs: &[F]) -> Result<Self, DuplicateCoordinateError> {
+        // the x-coordinates must be distinct, or some weight would be 1 / 0
+        let mut seen = std::collections::HashSet::new();
+        if let Some(i) = xs.iter().position(|x| !seen.insert(x)) {
+            return Err(DuplicateCoordinateError(i));
+        }
+
+        let mut weights: Vec<F> = xs.iter().enumerate()
+            .map(|(i, xi)| xs.iter().enumerate()
+                .filter(|(j, _)| i != *j)
+                .fold(F::one(), |acc, (_, xj)| acc * (*xi - xj)))
+            .collect();
+        batch_inversion(&mut weights);
+
+        let at_zero = coefficients(xs, &weights, &F::zero());
+        Ok(Interpolator { xs: xs.to_vec(), weights, at_zero })
+    }
+
+    /// computes the Lagrange coefficients of all points, evaluated at the point `x`;
+    /// the ith coefficient is computed w.r.t. xs[i].
+    pub fn coefficients(&self, x: &F) -> Vec<F> {
+        if x.is_zero() {
+            return self.at_zero.clone();
+        }
+        coefficients(&self.xs, &self.weights, x)
+    }
+
+    /// evaluates the polynomial through the points (xs[i], ys[i]) at the point `x`.
+    pub fn interpolate(&self, ys: &[F], x: &F) -> F {
+        if x.is_zero() {
+            return weighted_sum(&self.at_zero, ys);
+        }
+        weighted_sum(&self.coefficients(x), ys)
+    }
+
+    /// interpolates the points (xs[i], g^{ys[i]}) in the exponent: given the values of a
+    /// polynomial p "hidden" in the group, outputs g^{p(x)} without learning any y_i.
+    pub fn interpolate_in_exponent<C: Group<ScalarField = F>>(&self, ys: &[C], x: &F) -> C {
+        assert_eq!(self.xs.len(), ys.len(), "one value per x-coordinate is needed");
+        if x.is_zero() {
+            return ys.iter().zip(self.at_zero.iter()).map(|(y, λ)| *y * λ).sum();
+        }
+        ys.iter().zip(self.coefficients(x).iter()).map(|(y, λ)| *y * λ).sum()
+    }
+}
+
+/// the coefficients at x are l(x) * w_i / (x - x_i), where l(x) = prod_j (x - x_j);
+/// if x is one of the x-coordinates, this is the corresponding unit vector instead.
+fn coefficients<F: Field>(xs: &[F], weights: &[F], x: &F) -> Vec<F> {
+    if let Some(i) = xs.iter().position(|xi| xi == x) {
+        let mut unit = vec![F::zero(); xs.len()];
+        unit[i] = F::one();
+        return unit;
+    }
+
+    let mut differences: Vec<F> = xs.iter().map(|xi| *x - xi).collect();
+    let l: F = differences.iter().product();
+    batch_inversion_and_mul(&mut differences, &l);
+
+    differences.iter().zip(weights.iter())
+        .map(|(d_i, w_i)| *d_i * w_i)
+        .collect()
+}
+
+/// the evaluation is a weighted sum of ys, with the lagrange coefficients as weights
+fn weighted_sum<F: Field>(λs: &[F], ys: &[F]) -> F {
+    assert_eq!(λs.len(), ys.len(), "one value per x-coordinate is needed");
+    λs.iter().zip(ys.iter())
+        .fold(F::zero(), |acc, (&λ_i, &y_i)| { acc + λ_i * y_i })
+}
+
+/// interpolates the points (x_i, g^{y_i}) in the exponent: given the values of a
+/// polynomial p "hidden" in the group, outputs g^{p(x)} without learning any y_i.
+pub fn interpolate_in_exponent<C: Group>(
+    points: &[(C::ScalarField, C)],
+    x: &C::ScalarField,
+) -> Result<C, DuplicateCoordinateError> {
+    let xs = points.iter().map(|(x, _)| *x).collect::<Vec<C::ScalarField>>();
+    let ys = points.iter().map(|(_, y)| *y).collect::<Vec<C>>();
+
+    Ok(Interpolator::new(&xs)?.interpolate_in_exponent::<C>(&ys, x))
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_std::{One, UniformRand, Zero, test_rng};
+
+    use super::*;
+
+    type F = ark_bls12_381::Fr;
+    type G = ark_bls12_381::G1Projective;
+
+    // the textbook formula, one inversion per coefficient
+    fn lagrange_coefficient(xs: &[F], i: usize, x: &F) -> F {
+        let (numerator, denominator) = xs.iter().enumerate()
+            .filter(|(j, _)| i != *j)
+            .fold((F::one(), F::one()), |(n, d), (_, xj)| (n * (*x - xj), d * (xs[i] - xj)));
+        numerator * denominator.inverse().unwrap()
+    }
+
+    #[test]
+    fn test_coefficients_match_textbook_formula() {
+        let mut rng = test_rng();
+        let xs: Vec<F> = (0..20).map(|_| F::rand(&mut rng)).collect();
+        let interpolator = Interpolator::new(&xs).unwrap();
+
+        for x in [F::zero(), F::from(7u64), F::rand(&mut rng)] {
+            let expected: Vec<F> = (0..xs.len()).map(|i| lagrange_coefficient(&xs, i, &x)).collect();
+            assert_eq!(expected, interpolator.coefficients(&x));
         }
+
+        // at one of the x-coordinates, the coefficients single out that point
+        let ys: Vec<F> = (0..xs.len()).map(|_| F::rand(&mut rng)).collect();
+        assert_eq!(ys[3], interpolator.interpolate(&ys, &xs[3]));
+    }
+
+    #[test]
+    fn test_interpolate_polynomial() {
+        let mut rng = test_rng();
+        let n = 200;
+
+        // a random polynomial of degree n - 1, evaluated at x = 1, ..., n
+        let coefficients: Vec<F> = (0..n).map(|_| F::rand(&mut rng)).collect();
+        let p = |x: &F| coefficients.iter().rev().fold(F::zero(), |acc, a| acc * x + a);
+        let xs: Vec<F> = (1..=n as u64).map(F::from).collect();
+        let ys: Vec<F> = xs.iter().map(p).collect();
+
+        let interpolator = Interpolator::new(&xs).unwrap();
+        assert_eq!(coefficients[0], interpolator.interpolate(&ys, &F::zero()));
+        let x = F::rand(&mut rng);
+        assert_eq!(p(&x), interpolator.interpolate(&ys, &x));
+
+        // the same, with the values hidden in the group
+        let g = G::generator();
+        let hidden: Vec<G> = ys.iter().map(|y| g * y).collect();
+        assert_eq!(g * coefficients[0], interpolator.interpolate_in_exponent::<G>(&hidden, &F::zero()));
+        assert_eq!(g * p(&x), interpolator.interpolate_in_exponent::<G>(&hidden, &x));
+
+        let points: Vec<_> = xs.iter().copied().zip(hidden.iter().copied()).collect();
+        assert_eq!(g * p(&x), interpolate_in_exponent::<G>(&points, &x).unwrap());
+        assert_eq!(hidden[0], interpolate_in_exponent::<G>(&points, &xs[0]).unwrap());
+    }
+
+    #[test]
+    fn test_duplicate_xs() {
+        let xs = [F::from(1u64), F::from(2u64), F::from(1u64)];
+        assert_eq!(Some(DuplicateCoordinateError(2)), Interpolator::new(&xs).err());
+
+        let g = G::generator();
+        let points: Vec<_> = xs.iter().map(|x| (*x, g)).collect();
+        assert_eq!(Err(DuplicateCoordinateError(2)), interpolate_in_exponent::<G>(&points, &F::zero()));
     }
-    numerator * denominator
-    .inverse()
-    .expect("denominator in lagrange interpolation should not be zero")
 }
@@ -3,12 +3,49 @@ use ark_serialize::CanonicalSerialize;
 use ark_std::rand::Rng;
 
 pub mod jkkx16;
+pub mod toprf;
+pub mod dkg;
+pub mod stretch;
+pub mod suite;
+pub mod ristretto;
+pub mod rfc9497;
+pub mod voprf;
+pub mod pow;
+pub mod token;
+mod dleq;
 mod sss;
 mod lagrange;
 
+/// A keyper's secret seed, together with the version under which it is used;
+/// per-client keys are derived from both, so a new version yields unrelated keys.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerKey {
+    pub version: u32,
+    pub seed: [u8; 32],
+}
+
+impl ServerKey {
+    pub fn new(version: u32, seed: [u8; 32]) -> Self {
+        ServerKey { version, seed }
+    }
+}
+
+/// Outcome of a successful reconstruction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reconstruction<K> {
+    /// the reconstructed key
+    pub key: K,
+    /// indices of the keypers that did not take part in the reconstruction
+    pub missing_servers: Vec<usize>,
+    /// indices of the keypers whose responses or shares were found to be
+    /// inconsistent with the reconstructed key, and were therefore excluded
+    pub inconsistent_servers: Vec<usize>,
+}
+
 #[allow(dead_code)]
 pub trait PpssPcheme {
     type Parameters: Clone + Send + Sync;
+    type ServerKey: Clone + Send + Sync;
     type SecretKey: CanonicalSerialize + Clone + Default;
     type PrfInput: CanonicalSerialize + Clone + Default + Send + Sync;
     type PrfOutput: CanonicalSerialize + Clone + Default + Send + Sync;
@@ -27,10 +64,10 @@ pub trait PpssPcheme {
     ) -> Result<(Self::ClientState, Self::PrfInput), Error>;
 
     /// Computes a PRF evaluation on the server side,
-    /// using a 256-bit server key and a unique client identifier.
+    /// using the server's versioned key and a unique client identifier.
     fn server_process_keygen_request(
         pp: &Self::Parameters,
-        seed: &[u8; 32],
+        key: &Self::ServerKey,
         client_id: &[u8],
         input: &Self::PrfInput,
     ) -> Result<Self::PrfOutput, Error>;
@@ -54,30 +91,44 @@ pub trait PpssPcheme {
 
     fn server_process_reconstruct_request(
         pp: &Self::Parameters,
-        seed: &[u8; 32],
+        key: &Self::ServerKey,
         client_id: &[u8],
         input: &Self::PrfInput,
     ) -> Result<Self::PrfOutput, Error>;
 
+    /// Recovers the key from the responses of any threshold number of keypers,
+    /// where each response is tagged with the index of the keyper that sent it.
     fn client_reconstruct(
         pp: &Self::Parameters,
         state: &Self::ClientState,
-        server_responses: &[Self::PrfOutput],
+        server_responses: &[(usize, Self::PrfOutput)],
+        ciphertext: &Self::Ciphertext,
+    ) -> Result<Reconstruction<Self::SecretKey>, Error>;
+
+    /// Error-tolerant variant of `client_reconstruct`: excludes the responses that fail
+    /// the checks of their own keyper's key, reconstructs from the others, and reports the
+    /// keypers that were excluded or are inconsistent with the result. Failing the
+    /// reconstruction check then indicates a wrong password.
+    fn client_reconstruct_robust(
+        pp: &Self::Parameters,
+        state: &Self::ClientState,
+        server_responses: &[(usize, Self::PrfOutput)],
         ciphertext: &Self::Ciphertext,
-    ) -> Result<Self::SecretKey, Error>;
+    ) -> Result<Reconstruction<Self::SecretKey>, Error>;
 }
 
 
 #[cfg(test)]
 mod test {
-    use crate::crypto::ppss::{jkkx16::*, *};
+    use crate::crypto::ppss::{jkkx16::*, toprf::ThresholdOprf, *};
+    use crate::crypto::ppss::{rfc9497::{P256Sha256, Ristretto255Sha512}, voprf::VoprfScheme};
     use ark_std::test_rng;
 
     #[test]
     fn test_ppss_one_server() {
         let pin = "198837";
         let client_id = b"alice@gmail.com";
-        let server_seed = [0u8; 32];
+        let server_key = ServerKey::new(1, [0u8; 32]);
         
         let rng = &mut test_rng();
         let pp = JKKX16::setup::<_>(rng).unwrap();
@@ -86,7 +137,7 @@ mod test {
             &pp, client_id, pin.as_bytes(), rng
         ).unwrap();
         let prf_output = JKKX16::server_process_keygen_request(
-            &pp, &server_seed, client_id, &prf_input
+            &pp, &server_key, client_id, &prf_input
         ).unwrap();
         let (key, ciphertext) = JKKX16::client_keygen(
             &pp, &client_state, &[prf_output], 1, 1, rng
@@ -96,11 +147,11 @@ mod test {
             &pp, client_id, pin.as_bytes(), rng
         ).unwrap();
         let prf_output = JKKX16::server_process_reconstruct_request(
-            &pp, &server_seed, client_id, &prf_input
+            &pp, &server_key, client_id, &prf_input
         ).unwrap();
         let reconstructed_key = JKKX16::client_reconstruct(
-            &pp, &client_state, &[prf_output], &ciphertext
-        ).unwrap();
+            &pp, &client_state, &[(0, prf_output)], &ciphertext
+        ).unwrap().key;
 
         assert_eq!(key, reconstructed_key);
     }
@@ -109,9 +160,9 @@ mod test {
     fn test_ppss_multiple_servers() {
         let pin = "198837";
         let client_id = b"alice@gmail.com";
-        let seed1 = [1u8; 32];
-        let seed2 = [2u8; 32];
-        let seed3 = [3u8; 32];
+        let seed1 = ServerKey::new(1, [1u8; 32]);
+        let seed2 = ServerKey::new(1, [2u8; 32]);
+        let seed3 = ServerKey::new(1, [3u8; 32]);
 
         
         let rng = &mut test_rng();
@@ -129,8 +180,401 @@ mod test {
         let prf_out2 = JKKX16::server_process_reconstruct_request(&pp, &seed2, client_id, &prf_input).unwrap();
         let prf_out3 = JKKX16::server_process_reconstruct_request(&pp, &seed3, client_id, &prf_input).unwrap();
 
-        let reconstructed_key = JKKX16::client_reconstruct(&pp, &client_state, &[prf_out1, prf_out2, prf_out3], &ctxt).unwrap();
+        let reconstructed_key = JKKX16::client_reconstruct(&pp, &client_state, &[(0, prf_out1), (1, prf_out2), (2, prf_out3)], &ctxt).unwrap().key;
 
         assert_eq!(key, reconstructed_key);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_ppss_cheating_server() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+        let seed1 = ServerKey::new(1, [1u8; 32]);
+        let seed2 = ServerKey::new(1, [2u8; 32]);
+
+        let rng = &mut test_rng();
+        let pp = JKKX16::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = JKKX16::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+
+        let prf_out1 = JKKX16::server_process_keygen_request(&pp, &seed1, client_id, &prf_input).unwrap();
+        let mut prf_out2 = JKKX16::server_process_keygen_request(&pp, &seed2, client_id, &prf_input).unwrap();
+
+        // the second server evaluates with a key other than the one it advertises
+        prf_out2.blinded_prf_output = prf_out1.blinded_prf_output;
+
+        let err = JKKX16::client_keygen(&pp, &client_state, &[prf_out1, prf_out2], 2, 2, rng).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidProofError(1))));
+    }
+
+    #[test]
+    fn test_ppss_impersonated_server() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+        let seed1 = ServerKey::new(1, [1u8; 32]);
+        let seed2 = ServerKey::new(1, [2u8; 32]);
+        let impostor_seed = ServerKey::new(1, [3u8; 32]);
+
+        let rng = &mut test_rng();
+        let pp = JKKX16::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = JKKX16::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+
+        let prf_out1 = JKKX16::server_process_keygen_request(&pp, &seed1, client_id, &prf_input).unwrap();
+        let prf_out2 = JKKX16::server_process_keygen_request(&pp, &seed2, client_id, &prf_input).unwrap();
+
+        let (_, ctxt) = JKKX16::client_keygen(&pp, &client_state, &[prf_out1, prf_out2], 2, 2, rng).unwrap();
+
+        // a different keyper answers in place of the second one, with a valid proof for its own key
+        let prf_out1 = JKKX16::server_process_reconstruct_request(&pp, &seed1, client_id, &prf_input).unwrap();
+        let prf_out2 = JKKX16::server_process_reconstruct_request(&pp, &impostor_seed, client_id, &prf_input).unwrap();
+
+        let err = JKKX16::client_reconstruct(&pp, &client_state, &[(0, prf_out1), (1, prf_out2)], &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::KeyMismatchError(1))));
+    }
+
+    #[test]
+    fn test_ppss_threshold_reconstruction() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+        let seeds: Vec<ServerKey> = (1..=5).map(|i| ServerKey::new(1, [i as u8; 32])).collect();
+
+        let rng = &mut test_rng();
+        let pp = JKKX16::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = JKKX16::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let prf_outs: Vec<_> = seeds.iter()
+            .map(|seed| JKKX16::server_process_keygen_request(&pp, seed, client_id, &prf_input).unwrap())
+            .collect();
+        let (key, ctxt) = JKKX16::client_keygen(&pp, &client_state, &prf_outs, 5, 3, rng).unwrap();
+
+        // keypers 1 and 3 are down
+        let (client_state, prf_input) = JKKX16::client_generate_reconstruct_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let responses: Vec<_> = [4, 0, 2].iter()
+            .map(|&i| (i, JKKX16::server_process_reconstruct_request(&pp, &seeds[i], client_id, &prf_input).unwrap()))
+            .collect();
+
+        let reconstruction = JKKX16::client_reconstruct(&pp, &client_state, &responses, &ctxt).unwrap();
+        assert_eq!(key, reconstruction.key);
+        assert_eq!(vec![1, 3], reconstruction.missing_servers);
+
+        // two responses are not enough for a 3-of-5 sharing
+        let err = JKKX16::client_reconstruct(&pp, &client_state, &responses[..2], &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::NotEnoughResponsesError(2, 3))));
+    }
+
+    #[test]
+    fn test_ppss_robust_reconstruction() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+        let seeds: Vec<ServerKey> = (1..=5).map(|i| ServerKey::new(1, [i as u8; 32])).collect();
+
+        let rng = &mut test_rng();
+        let pp = JKKX16::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = JKKX16::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let prf_outs: Vec<_> = seeds.iter()
+            .map(|seed| JKKX16::server_process_keygen_request(&pp, seed, client_id, &prf_input).unwrap())
+            .collect();
+        let (key, ctxt) = JKKX16::client_keygen(&pp, &client_state, &prf_outs, 5, 3, rng).unwrap();
+
+        let (client_state, prf_input) = JKKX16::client_generate_reconstruct_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let mut responses: Vec<_> = (0..5)
+            .map(|i| (i, JKKX16::server_process_reconstruct_request(&pp, &seeds[i], client_id, &prf_input).unwrap()))
+            .collect();
+
+        // keyper 1 has been replaced by an impostor, and keyper 3 returns
+        // an evaluation under a key other than its own
+        responses[1].1 = JKKX16::server_process_reconstruct_request(&pp, &ServerKey::new(1, [9u8; 32]), client_id, &prf_input).unwrap();
+        responses[3].1.blinded_prf_output = responses[2].1.blinded_prf_output;
+
+        // the plain reconstruction rejects the bad response outright
+        assert!(JKKX16::client_reconstruct(&pp, &client_state, &responses, &ctxt).is_err());
+
+        let reconstruction = JKKX16::client_reconstruct_robust(&pp, &client_state, &responses, &ctxt).unwrap();
+        assert_eq!(key, reconstruction.key);
+        assert!(reconstruction.missing_servers.is_empty());
+        assert_eq!(vec![1, 3], reconstruction.inconsistent_servers);
+
+        // with a wrong pin, the reconstruction check fails
+        let (client_state, prf_input) = JKKX16::client_generate_reconstruct_request(&pp, client_id, b"000000", rng).unwrap();
+        let responses: Vec<_> = (0..5)
+            .map(|i| (i, JKKX16::server_process_reconstruct_request(&pp, &seeds[i], client_id, &prf_input).unwrap()))
+            .collect();
+        let err = JKKX16::client_reconstruct_robust(&pp, &client_state, &responses, &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidPinError)));
+    }
+
+    #[test]
+    fn test_ppss_key_versions() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+        let key_v1 = ServerKey::new(1, [1u8; 32]);
+        let key_v2 = ServerKey::new(2, [1u8; 32]);
+
+        let rng = &mut test_rng();
+        let pp = JKKX16::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = JKKX16::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let prf_out = JKKX16::server_process_keygen_request(&pp, &key_v1, client_id, &prf_input).unwrap();
+        let (key, ctxt) = JKKX16::client_keygen(&pp, &client_state, &[prf_out], 1, 1, rng).unwrap();
+        assert_eq!(1, ctxt.server_key(0).unwrap().key_version);
+
+        // the same seed under a new version yields an unrelated key
+        let (client_state, mut prf_input) = JKKX16::client_generate_reconstruct_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let prf_out = JKKX16::server_process_reconstruct_request(&pp, &key_v2, client_id, &prf_input).unwrap();
+        let err = JKKX16::client_reconstruct(&pp, &client_state, &[(0, prf_out)], &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::KeyMismatchError(0))));
+
+        // requests name the version recorded at keygen
+        prf_input.key_version = Some(1);
+        let err = JKKX16::server_process_reconstruct_request(&pp, &key_v2, client_id, &prf_input).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::UnknownKeyVersionError(1))));
+
+        let prf_out = JKKX16::server_process_reconstruct_request(&pp, &key_v1, client_id, &prf_input).unwrap();
+        let reconstruction = JKKX16::client_reconstruct(&pp, &client_state, &[(0, prf_out)], &ctxt).unwrap();
+        assert_eq!(key, reconstruction.key);
+    }
+
+    /// Runs the distributed key generation among `num_parties` keypers.
+    fn toprf_key_shares<R: Rng>(version: u32, threshold: usize, num_parties: usize, rng: &mut R) -> Vec<toprf::ServerKeyShare> {
+        let (dealings, shares): (Vec<_>, Vec<_>) = (0..num_parties)
+            .map(|dealer| dkg::deal::<ark_bls12_381::G1Projective, _>(dealer, threshold, num_parties, rng).unwrap())
+            .unzip();
+
+        (0..num_parties)
+            .map(|party| {
+                let received: Vec<_> = shares.iter().map(|s| s[party]).collect();
+                let key_share = dkg::finalize(party, threshold, num_parties, &dealings, &received).unwrap();
+                toprf::ServerKeyShare::new(version, key_share)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_toprf_threshold_reconstruction() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+        let rng = &mut test_rng();
+        let key_shares = toprf_key_shares(1, 3, 5, rng);
+        let pp = ThresholdOprf::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = ThresholdOprf::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let prf_outs: Vec<_> = key_shares.iter()
+            .map(|share| ThresholdOprf::server_process_keygen_request(&pp, share, client_id, &prf_input).unwrap())
+            .collect();
+        let (key, ctxt) = ThresholdOprf::client_keygen(&pp, &client_state, &prf_outs, 5, 3, rng).unwrap();
+
+        // any 3 keypers combine to the same key; keypers 1 and 3 are down
+        let (client_state, prf_input) = ThresholdOprf::client_generate_reconstruct_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let responses: Vec<_> = [4, 0, 2].iter()
+            .map(|&i| (i, ThresholdOprf::server_process_reconstruct_request(&pp, &key_shares[i], client_id, &prf_input).unwrap()))
+            .collect();
+
+        let reconstruction = ThresholdOprf::client_reconstruct(&pp, &client_state, &responses, &ctxt).unwrap();
+        assert_eq!(key, reconstruction.key);
+        assert_eq!(vec![1, 3], reconstruction.missing_servers);
+
+        let err = ThresholdOprf::client_reconstruct(&pp, &client_state, &responses[..2], &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::NotEnoughResponsesError(2, 3))));
+
+        // evaluations are bound to the client id they were limited under, so evaluations
+        // counted against another client fail alice's checks
+        let responses: Vec<_> = [4, 0, 2].iter()
+            .map(|&i| (i, ThresholdOprf::server_process_reconstruct_request(&pp, &key_shares[i], b"mallory", &prf_input).unwrap()))
+            .collect();
+        let err = ThresholdOprf::client_reconstruct(&pp, &client_state, &responses, &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidProofError(4))));
+
+        let err = ThresholdOprf::client_keygen(&pp, &client_state, &prf_outs[..4], 5, 3, rng).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::NotEnoughResponsesError(4, 5))));
+        let err = ThresholdOprf::client_keygen(&pp, &client_state, &prf_outs, 5, 6, rng).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidThresholdError(6, 5))));
+    }
+
+    #[test]
+    fn test_toprf_robust_reconstruction() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+        let rng = &mut test_rng();
+        let key_shares = toprf_key_shares(1, 3, 5, rng);
+        let pp = ThresholdOprf::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = ThresholdOprf::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let prf_outs: Vec<_> = key_shares.iter()
+            .map(|share| ThresholdOprf::server_process_keygen_request(&pp, share, client_id, &prf_input).unwrap())
+            .collect();
+        let (key, ctxt) = ThresholdOprf::client_keygen(&pp, &client_state, &prf_outs, 5, 3, rng).unwrap();
+
+        let (client_state, prf_input) = ThresholdOprf::client_generate_reconstruct_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let mut responses: Vec<_> = (0..5)
+            .map(|i| (i, ThresholdOprf::server_process_reconstruct_request(&pp, &key_shares[i], client_id, &prf_input).unwrap()))
+            .collect();
+
+        // keyper 1 answers with a share of another key, and keyper 3 without a valid proof
+        let impostor_shares = toprf_key_shares(1, 3, 5, rng);
+        responses[1].1 = ThresholdOprf::server_process_reconstruct_request(&pp, &impostor_shares[1], client_id, &prf_input).unwrap();
+        responses[3].1.blinded_prf_output = responses[2].1.blinded_prf_output;
+
+        assert!(ThresholdOprf::client_reconstruct(&pp, &client_state, &responses, &ctxt).is_err());
+
+        let reconstruction = ThresholdOprf::client_reconstruct_robust(&pp, &client_state, &responses, &ctxt).unwrap();
+        assert_eq!(key, reconstruction.key);
+        assert_eq!(vec![1, 3], reconstruction.inconsistent_servers);
+
+        // with a wrong pin, the combined evaluation fails the check
+        let (client_state, prf_input) = ThresholdOprf::client_generate_reconstruct_request(&pp, client_id, b"000000", rng).unwrap();
+        let responses: Vec<_> = (0..5)
+            .map(|i| (i, ThresholdOprf::server_process_reconstruct_request(&pp, &key_shares[i], client_id, &prf_input).unwrap()))
+            .collect();
+        let err = ThresholdOprf::client_reconstruct_robust(&pp, &client_state, &responses, &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidPinError)));
+    }
+
+    #[test]
+    fn test_toprf_search_limit() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+        let rng = &mut test_rng();
+        let key_shares = toprf_key_shares(1, 5, 11, rng);
+        let pp = ThresholdOprf::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = ThresholdOprf::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let prf_outs: Vec<_> = key_shares.iter()
+            .map(|share| ThresholdOprf::server_process_keygen_request(&pp, share, client_id, &prf_input).unwrap())
+            .collect();
+        let (_, ctxt) = ThresholdOprf::client_keygen(&pp, &client_state, &prf_outs, 11, 5, rng).unwrap();
+
+        // none of the 462 subsets of keys from another key generation combines to the
+        // joint key, and the client gives up rather than trying them all
+        let impostor_shares = toprf_key_shares(1, 5, 11, rng);
+        let (client_state, prf_input) = ThresholdOprf::client_generate_reconstruct_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let responses: Vec<_> = (0..11)
+            .map(|i| (i, ThresholdOprf::server_process_reconstruct_request(&pp, &impostor_shares[i], client_id, &prf_input).unwrap()))
+            .collect();
+        let err = ThresholdOprf::client_reconstruct_robust(&pp, &client_state, &responses, &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::SearchLimitError(256))));
+    }
+
+    #[test]
+    fn test_toprf_independent_keys() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+
+        // keypers 3 and 4 come from a different key generation
+        let rng = &mut test_rng();
+        let mut key_shares = toprf_key_shares(1, 3, 5, rng);
+        key_shares[3..].clone_from_slice(&toprf_key_shares(1, 3, 5, rng)[3..]);
+        let pp = ThresholdOprf::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = ThresholdOprf::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let prf_outs: Vec<_> = key_shares.iter()
+            .map(|share| ThresholdOprf::server_process_keygen_request(&pp, share, client_id, &prf_input).unwrap())
+            .collect();
+        let err = ThresholdOprf::client_keygen(&pp, &client_state, &prf_outs, 5, 3, rng).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::KeyMismatchError(3))));
+    }
+
+    /// Moves the keypers to the next epoch, with every keyper dealing a sharing of zero.
+    fn toprf_refresh<R: Rng>(key_shares: &[toprf::ServerKeyShare], threshold: usize, rng: &mut R) -> Vec<toprf::ServerKeyShare> {
+        let num_parties = key_shares.len();
+        let (dealings, shares): (Vec<_>, Vec<_>) = (0..num_parties)
+            .map(|dealer| dkg::deal_refresh::<ark_bls12_381::G1Projective, _>(dealer, threshold, num_parties, rng).unwrap())
+            .unzip();
+
+        key_shares.iter()
+            .map(|share| {
+                let received: Vec<_> = shares.iter().map(|s| s[share.key_share.index]).collect();
+                let key_share = dkg::refresh(&share.key_share, threshold, &dealings, &received).unwrap();
+                toprf::ServerKeyShare::new(share.version, key_share)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_toprf_refresh() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+        let rng = &mut test_rng();
+        let key_shares = toprf_key_shares(1, 3, 5, rng);
+        let pp = ThresholdOprf::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = ThresholdOprf::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let prf_outs: Vec<_> = key_shares.iter()
+            .map(|share| ThresholdOprf::server_process_keygen_request(&pp, share, client_id, &prf_input).unwrap())
+            .collect();
+        let (key, ctxt) = ThresholdOprf::client_keygen(&pp, &client_state, &prf_outs, 5, 3, rng).unwrap();
+
+        // the vault outlives two epochs, without being re-encrypted
+        let refreshed = toprf_refresh(&toprf_refresh(&key_shares, 3, rng), 3, rng);
+        let (client_state, prf_input) = ThresholdOprf::client_generate_reconstruct_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let responses: Vec<_> = [4, 0, 2].iter()
+            .map(|&i| (i, ThresholdOprf::server_process_reconstruct_request(&pp, &refreshed[i], client_id, &prf_input).unwrap()))
+            .collect();
+        let reconstruction = ThresholdOprf::client_reconstruct(&pp, &client_state, &responses, &ctxt).unwrap();
+        assert_eq!(key, reconstruction.key);
+        assert_eq!(vec![1, 3], reconstruction.missing_servers);
+
+        // a share leaked in the first epoch does not combine with the current ones
+        let mut responses: Vec<_> = (0..5)
+            .map(|i| (i, ThresholdOprf::server_process_reconstruct_request(&pp, &refreshed[i], client_id, &prf_input).unwrap()))
+            .collect();
+        responses[1].1 = ThresholdOprf::server_process_reconstruct_request(&pp, &key_shares[1], client_id, &prf_input).unwrap();
+        let err = ThresholdOprf::client_reconstruct(&pp, &client_state, &responses, &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::KeyMismatchError(0))));
+
+        let reconstruction = ThresholdOprf::client_reconstruct_robust(&pp, &client_state, &responses, &ctxt).unwrap();
+        assert_eq!(key, reconstruction.key);
+        assert_eq!(vec![1], reconstruction.inconsistent_servers);
+    }
+
+    fn check_voprf_robust_reconstruction<S: rfc9497::Suite>() {
+        let pin = "198837";
+        let client_id = b"alice@gmail.com";
+        let seeds: Vec<ServerKey> = (1..=5).map(|i| ServerKey::new(1, [i as u8; 32])).collect();
+
+        let rng = &mut test_rng();
+        let pp = VoprfScheme::<S>::setup::<_>(rng).unwrap();
+
+        let (client_state, prf_input) = VoprfScheme::<S>::client_generate_keygen_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let prf_outs: Vec<_> = seeds.iter()
+            .map(|seed| VoprfScheme::<S>::server_process_keygen_request(&pp, seed, client_id, &prf_input).unwrap())
+            .collect();
+        let (key, ctxt) = VoprfScheme::<S>::client_keygen(&pp, &client_state, &prf_outs, 5, 3, rng).unwrap();
+
+        let err = VoprfScheme::<S>::client_keygen(&pp, &client_state, &prf_outs[..4], 5, 3, rng).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::NotEnoughResponsesError(4, 5))));
+
+        let (client_state, prf_input) = VoprfScheme::<S>::client_generate_reconstruct_request(&pp, client_id, pin.as_bytes(), rng).unwrap();
+        let mut responses: Vec<_> = (0..5)
+            .map(|i| (i, VoprfScheme::<S>::server_process_reconstruct_request(&pp, &seeds[i], client_id, &prf_input).unwrap()))
+            .collect();
+
+        // keyper 1 has been replaced by an impostor, and keyper 3 returns
+        // an evaluation under a key other than its own
+        responses[1].1 = VoprfScheme::<S>::server_process_reconstruct_request(&pp, &ServerKey::new(1, [9u8; 32]), client_id, &prf_input).unwrap();
+        responses[3].1.evaluated_element = responses[2].1.evaluated_element.clone();
+
+        let err = VoprfScheme::<S>::client_reconstruct(&pp, &client_state, &responses, &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::KeyMismatchError(1))));
+
+        let reconstruction = VoprfScheme::<S>::client_reconstruct_robust(&pp, &client_state, &responses, &ctxt).unwrap();
+        assert_eq!(key, reconstruction.key);
+        assert!(reconstruction.missing_servers.is_empty());
+        assert_eq!(vec![1, 3], reconstruction.inconsistent_servers);
+
+        // with a wrong pin, the reconstruction check fails
+        let (client_state, prf_input) = VoprfScheme::<S>::client_generate_reconstruct_request(&pp, client_id, b"000000", rng).unwrap();
+        let responses: Vec<_> = (0..5)
+            .map(|i| (i, VoprfScheme::<S>::server_process_reconstruct_request(&pp, &seeds[i], client_id, &prf_input).unwrap()))
+            .collect();
+        let err = VoprfScheme::<S>::client_reconstruct_robust(&pp, &client_state, &responses, &ctxt).unwrap_err();
+        assert!(matches!(err.downcast_ref::<JKKX16Error>(), Some(JKKX16Error::InvalidPinError)));
+    }
+
+    #[test]
+    fn test_voprf_robust_reconstruction() {
+        check_voprf_robust_reconstruction::<Ristretto255Sha512>();
+        check_voprf_robust_reconstruction::<P256Sha256>();
+    }
+}
@@ -0,0 +1,110 @@
+//! Hashcash-style proofs of work, by which a client pays for each PRF evaluation it asks for.
+//! The keyper issues a challenge that it authenticates itself, so it need not remember it,
+//! and the client searches for a nonce such that SHA256(prefix || challenge || input || nonce)
+//! starts with as many zero bits as the challenge demands. The input binds the work to the
+//! blinded PRF input and the client id of a single request.
+
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use sha2::{Digest, Sha256};
+
+/// Issued by a keyper, which authenticates the issuance time and the difficulty with a tag.
+#[derive(Clone, Default, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Challenge {
+    /// issuance time, in seconds since the Unix epoch
+    pub issued_at: u64,
+    /// number of leading zero bits the solution must have
+    pub difficulty: u8,
+    /// the keyper's MAC over the fields above
+    pub tag: Vec<u8>,
+}
+
+/// A solution to the keyper's challenge, attached to a `PrfInput`.
+#[derive(Clone, Default, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct ProofOfWork {
+    pub challenge: Challenge,
+    pub nonce: u64,
+}
+
+/// The highest difficulty a client takes on: 2^32 hash evaluations on average, which
+/// take minutes; a keyper that demands more would keep the client busy for hours.
+pub const MAX_DIFFICULTY: u8 = 32;
+
+const PUZZLE_PREFIX: &[u8] = b"bedrock-keyper-puzzle";
+
+/// Searches for the first nonce that solves the challenge for the given input;
+/// takes 2^difficulty hash evaluations on average.
+pub fn solve(challenge: &Challenge, input: &[u8]) -> Result<ProofOfWork, SerializationError> {
+    let hasher = puzzle_hasher(challenge, input)?;
+
+    let nonce = (0..=u64::MAX)
+        .find(|nonce| leading_zero_bits(&digest(&hasher, *nonce)) >= challenge.difficulty as u32)
+        .expect("a solution exists for any difficulty below 64 bits");
+
+    Ok(ProofOfWork { challenge: challenge.clone(), nonce })
+}
+
+/// Checks that the proof solves its challenge for the given input; the challenge
+/// itself must be checked separately by the keyper that issued it.
+pub fn verify(proof: &ProofOfWork, input: &[u8]) -> Result<bool, SerializationError> {
+    let hasher = puzzle_hasher(&proof.challenge, input)?;
+    Ok(leading_zero_bits(&digest(&hasher, proof.nonce)) >= proof.challenge.difficulty as u32)
+}
+
+fn puzzle_hasher(challenge: &Challenge, input: &[u8]) -> Result<Sha256, SerializationError> {
+    let mut hash_input = PUZZLE_PREFIX.to_vec();
+    challenge.serialize_compressed(&mut hash_input)?;
+    // the input has variable length, so it is prefixed with it
+    (input.len() as u64).serialize_compressed(&mut hash_input)?;
+    hash_input.extend_from_slice(input);
+
+    let mut hasher = Sha256::new();
+    hasher.update(&hash_input);
+    Ok(hasher)
+}
+
+fn digest(hasher: &Sha256, nonce: u64) -> [u8; 32] {
+    let mut hasher = hasher.clone();
+    hasher.update(nonce.to_be_bytes());
+    hasher.finalize().into()
+}
+
+fn leading_zero_bits(digest: &[u8]) -> u32 {
+    let mut zeros = 0;
+    for byte in digest {
+        zeros += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    zeros
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve_and_verify() {
+        let challenge = Challenge { issued_at: 1767225600, difficulty: 12, tag: vec![1u8; 32] };
+
+        let proof = solve(&challenge, b"request").unwrap();
+        assert!(verify(&proof, b"request").unwrap());
+        assert!(leading_zero_bits(&digest(&puzzle_hasher(&challenge, b"request").unwrap(), proof.nonce)) >= 12);
+
+        // the work is bound to the input and to the challenge
+        assert!(!verify(&proof, b"another request").unwrap());
+        let other = ProofOfWork { challenge: Challenge { issued_at: 0, ..challenge.clone() }, ..proof.clone() };
+        assert!(!verify(&other, b"request").unwrap());
+
+        // a challenge of difficulty zero is solved by any nonce
+        let easy = Challenge { difficulty: 0, ..challenge };
+        assert_eq!(0, solve(&easy, b"request").unwrap().nonce);
+    }
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(0, leading_zero_bits(&[0x80, 0x00]));
+        assert_eq!(9, leading_zero_bits(&[0x00, 0x40]));
+        assert_eq!(16, leading_zero_bits(&[0x00, 0x00]));
+    }
+}
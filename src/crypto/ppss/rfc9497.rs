@@ -0,0 +1,570 @@
+//! The OPRF and VOPRF protocols of https://www.rfc-editor.org/rfc/rfc9497, for the
+//! ristretto255-SHA512 and P256-SHA256 suites. Unlike the 2HashDH variant in `jkkx16`,
+//! whose transcripts and finalization are our own, these interoperate with any other
+//! implementation of the RFC; elements and scalars are encoded as the RFC prescribes.
+//! The partially-oblivious mode (POPRF) is not implemented.
+
+#![allow(dead_code)]
+
+use ark_std::{fmt::Debug, rand::Rng};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use p256::elliptic_curve::{
+    ff::{Field, PrimeField},
+    group::{Group, GroupEncoding},
+    hash2curve::{ExpandMsg, ExpandMsgXmd, Expander, GroupDigest},
+};
+use sha2::{Digest, Sha256, Sha512};
+
+use super::jkkx16::JKKX16Error;
+
+/// Scalars and elements of the group of the suite `S`.
+pub type Scalar<S> = <<S as Suite>::Group as Group>::Scalar;
+pub type Element<S> = <S as Suite>::Group;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Oprf = 0x00,
+    Voprf = 0x01,
+}
+
+/// A ciphersuite of the RFC: a prime-order group, together with the hash functions onto it.
+/// Like arkworks' curve configs, suites are markers that derive the usual traits, so that
+/// types generic over a suite can derive them as well.
+pub trait Suite: Copy + Clone + Debug + PartialEq + Eq + Send + Sync + 'static {
+    type Group: Group + GroupEncoding;
+
+    /// identifier of the suite within the context string
+    const IDENTIFIER: &'static str;
+
+    /// The suite's hash function, e.g. in `Finalize`.
+    fn hash(input: &[u8]) -> Vec<u8>;
+
+    /// Hashes the message to a group element, following RFC 9380 under the given DST.
+    fn hash_to_group(msg: &[u8], dst: &[u8]) -> Result<Self::Group, JKKX16Error>;
+
+    /// Hashes the message to a scalar, following RFC 9380 under the given DST.
+    fn hash_to_scalar(msg: &[u8], dst: &[u8]) -> Result<Scalar<Self>, JKKX16Error>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ristretto255Sha512;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct P256Sha256;
+
+impl Suite for Ristretto255Sha512 {
+    type Group = RistrettoPoint;
+
+    const IDENTIFIER: &'static str = "ristretto255-SHA512";
+
+    fn hash(input: &[u8]) -> Vec<u8> {
+        Sha512::digest(input).to_vec()
+    }
+
+    /// hash_to_ristretto255 from RFC 9380, Appendix B.
+    fn hash_to_group(msg: &[u8], dst: &[u8]) -> Result<RistrettoPoint, JKKX16Error> {
+        Ok(RistrettoPoint::from_uniform_bytes(&expand_message_xmd_sha512(msg, dst)?))
+    }
+
+    /// Reduces 64 uniform bytes, interpreted in little-endian order, modulo the group order.
+    fn hash_to_scalar(msg: &[u8], dst: &[u8]) -> Result<curve25519_dalek::Scalar, JKKX16Error> {
+        Ok(curve25519_dalek::Scalar::from_bytes_mod_order_wide(&expand_message_xmd_sha512(msg, dst)?))
+    }
+}
+
+impl Suite for P256Sha256 {
+    type Group = p256::ProjectivePoint;
+
+    const IDENTIFIER: &'static str = "P256-SHA256";
+
+    fn hash(input: &[u8]) -> Vec<u8> {
+        Sha256::digest(input).to_vec()
+    }
+
+    /// P256_XMD:SHA-256_SSWU_RO_ from RFC 9380, Section 8.2.
+    fn hash_to_group(msg: &[u8], dst: &[u8]) -> Result<p256::ProjectivePoint, JKKX16Error> {
+        p256::NistP256::hash_from_bytes::<ExpandMsgXmd<Sha256>>(&[msg], &[dst])
+            .map_err(|_| hashing_error("P-256 hash to curve failed"))
+    }
+
+    /// hash_to_field from RFC 9380, Section 5.2, with the group order as modulus.
+    fn hash_to_scalar(msg: &[u8], dst: &[u8]) -> Result<p256::Scalar, JKKX16Error> {
+        p256::NistP256::hash_to_scalar::<ExpandMsgXmd<Sha256>>(&[msg], &[dst])
+            .map_err(|_| hashing_error("P-256 hash to scalar failed"))
+    }
+}
+
+/// expand_message_xmd from RFC 9380, Section 5.3.1, with SHA-512 and 64 bytes of output,
+/// as hash_to_ristretto255 uses it.
+pub(super) fn expand_message_xmd_sha512(msg: &[u8], dst: &[u8]) -> Result<[u8; 64], JKKX16Error> {
+    let mut uniform_bytes = [0u8; 64];
+    ExpandMsgXmd::<Sha512>::expand_message(&[msg], &[dst], 64)
+        .map_err(|_| hashing_error("expand_message_xmd failed"))?
+        .fill_bytes(&mut uniform_bytes);
+    Ok(uniform_bytes)
+}
+
+fn hashing_error(msg: &str) -> JKKX16Error {
+    JKKX16Error::HashingError(ark_ec::hashing::HashToCurveError::MapToCurveError(msg.to_string()))
+}
+
+/// A proof that the evaluated elements are the blinded elements raised to the secret key
+/// behind the public key, as a pair of scalars (c, s).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proof<S: Suite> {
+    pub c: Scalar<S>,
+    pub s: Scalar<S>,
+}
+
+impl<S: Suite> Proof<S> {
+    /// Serializes the proof as SerializeScalar(c) || SerializeScalar(s).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = serialize_scalar::<S>(&self.c);
+        bytes.extend(serialize_scalar::<S>(&self.s));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, JKKX16Error> {
+        if !bytes.len().is_multiple_of(2) {
+            return Err(deserialization_error());
+        }
+        let (c, s) = bytes.split_at(bytes.len() / 2);
+        Ok(Proof { c: deserialize_scalar::<S>(c)?, s: deserialize_scalar::<S>(s)? })
+    }
+}
+
+/// "OPRFV1-" || I2OSP(mode, 1) || "-" || identifier
+fn context_string<S: Suite>(mode: Mode) -> Vec<u8> {
+    let mut context = b"OPRFV1-".to_vec();
+    context.push(mode as u8);
+    context.push(b'-');
+    context.extend_from_slice(S::IDENTIFIER.as_bytes());
+    context
+}
+
+fn dst<S: Suite>(prefix: &str, mode: Mode) -> Vec<u8> {
+    let mut dst = prefix.as_bytes().to_vec();
+    dst.extend(context_string::<S>(mode));
+    dst
+}
+
+/// Appends I2OSP(len(bytes), 2) || bytes to the transcript.
+fn append_with_length(transcript: &mut Vec<u8>, bytes: &[u8]) {
+    transcript.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    transcript.extend_from_slice(bytes);
+}
+
+pub fn serialize_element<S: Suite>(element: &Element<S>) -> Vec<u8> {
+    element.to_bytes().as_ref().to_vec()
+}
+
+/// Decodes a group element, rejecting the identity, as the RFC requires of elements
+/// received from the other party.
+pub fn deserialize_element<S: Suite>(bytes: &[u8]) -> Result<Element<S>, JKKX16Error> {
+    let mut repr = <Element<S> as GroupEncoding>::Repr::default();
+    if repr.as_ref().len() != bytes.len() {
+        return Err(deserialization_error());
+    }
+    repr.as_mut().copy_from_slice(bytes);
+
+    let element: Option<Element<S>> = Element::<S>::from_bytes(&repr).into();
+    match element {
+        Some(element) if !bool::from(element.is_identity()) => Ok(element),
+        _ => Err(deserialization_error()),
+    }
+}
+
+pub fn serialize_scalar<S: Suite>(scalar: &Scalar<S>) -> Vec<u8> {
+    scalar.to_repr().as_ref().to_vec()
+}
+
+/// Decodes a scalar, rejecting encodings that are not reduced modulo the group order.
+pub fn deserialize_scalar<S: Suite>(bytes: &[u8]) -> Result<Scalar<S>, JKKX16Error> {
+    let mut repr = <Scalar<S> as PrimeField>::Repr::default();
+    if repr.as_ref().len() != bytes.len() {
+        return Err(deserialization_error());
+    }
+    repr.as_mut().copy_from_slice(bytes);
+
+    Option::from(Scalar::<S>::from_repr(repr)).ok_or_else(deserialization_error)
+}
+
+fn deserialization_error() -> JKKX16Error {
+    JKKX16Error::SerializationError(ark_serialize::SerializationError::InvalidData)
+}
+
+/// Samples a non-zero scalar, e.g. as the client's blind.
+pub fn random_scalar<S: Suite, R: Rng>(rng: &mut R) -> Scalar<S> {
+    loop {
+        let scalar = Scalar::<S>::random(&mut *rng);
+        if !bool::from(scalar.is_zero()) {
+            return scalar;
+        }
+    }
+}
+
+/// DeriveKeyPair from Section 3.2.1: deterministically derives the server's key pair
+/// from a 32-byte seed and public info.
+pub fn derive_key_pair<S: Suite>(
+    mode: Mode,
+    seed: &[u8; 32],
+    info: &[u8],
+) -> Result<(Scalar<S>, Element<S>), JKKX16Error> {
+    let mut derive_input = seed.to_vec();
+    append_with_length(&mut derive_input, info);
+
+    let derive_dst = dst::<S>("DeriveKeyPair", mode);
+    for counter in 0..=255u8 {
+        let mut input = derive_input.clone();
+        input.push(counter);
+
+        let secret_key = S::hash_to_scalar(&input, &derive_dst)?;
+        if !bool::from(secret_key.is_zero()) {
+            return Ok((secret_key, Element::<S>::generator() * secret_key));
+        }
+    }
+
+    Err(JKKX16Error::SetupError)
+}
+
+/// Blind from Section 3.3.1, with the blind sampled by the caller.
+pub fn blind<S: Suite>(mode: Mode, input: &[u8], blind: &Scalar<S>) -> Result<Element<S>, JKKX16Error> {
+    let input_element = S::hash_to_group(input, &dst::<S>("HashToGroup-", mode))?;
+    if bool::from(input_element.is_identity()) {
+        return Err(hashing_error("the input hashes to the identity"));
+    }
+
+    Ok(input_element * blind)
+}
+
+/// BlindEvaluate from Section 3.3.1; in VOPRF mode, the server additionally
+/// proves the evaluation with `generate_proof`.
+pub fn blind_evaluate<S: Suite>(secret_key: &Scalar<S>, blinded_element: &Element<S>) -> Element<S> {
+    *blinded_element * secret_key
+}
+
+/// Finalize from Section 3.3.1: unblinds the evaluated element and hashes it
+/// together with the input. In VOPRF mode, the proof must be verified first.
+pub fn finalize<S: Suite>(input: &[u8], blind: &Scalar<S>, evaluated_element: &Element<S>) -> Vec<u8> {
+    let blind_inverse = Option::<Scalar<S>>::from(blind.invert()).expect("blind should not be zero");
+    let unblinded_element = serialize_element::<S>(&(*evaluated_element * blind_inverse));
+
+    let mut hash_input = Vec::new();
+    append_with_length(&mut hash_input, input);
+    append_with_length(&mut hash_input, &unblinded_element);
+    hash_input.extend_from_slice(b"Finalize");
+
+    S::hash(&hash_input)
+}
+
+/// GenerateProof from Section 2.2.1, with the proof's random scalar sampled by the
+/// caller: proves that log_G(public_key) == log_{C_i}(D_i) for all i, in a single proof.
+pub fn generate_proof<S: Suite>(
+    mode: Mode,
+    secret_key: &Scalar<S>,
+    public_key: &Element<S>,
+    blinded_elements: &[Element<S>],
+    evaluated_elements: &[Element<S>],
+    r: &Scalar<S>,
+) -> Result<Proof<S>, JKKX16Error> {
+    // ComputeCompositesFast: the server knows the key, so Z = k * M
+    let (m, _) = compute_composites::<S>(mode, public_key, blinded_elements, evaluated_elements)?;
+    let z = m * secret_key;
+
+    let t2 = Element::<S>::generator() * r;
+    let t3 = m * r;
+
+    let c = challenge::<S>(mode, public_key, &m, &z, &t2, &t3)?;
+    let s = *r - c * secret_key;
+
+    Ok(Proof { c, s })
+}
+
+/// VerifyProof from Section 2.2.2.
+pub fn verify_proof<S: Suite>(
+    mode: Mode,
+    public_key: &Element<S>,
+    blinded_elements: &[Element<S>],
+    evaluated_elements: &[Element<S>],
+    proof: &Proof<S>,
+) -> Result<bool, JKKX16Error> {
+    let (m, z) = compute_composites::<S>(mode, public_key, blinded_elements, evaluated_elements)?;
+
+    let t2 = Element::<S>::generator() * proof.s + *public_key * proof.c;
+    let t3 = m * proof.s + z * proof.c;
+
+    Ok(challenge::<S>(mode, public_key, &m, &z, &t2, &t3)? == proof.c)
+}
+
+/// ComputeComposites from Section 2.2.1: combines the pairs (C_i, D_i) into a single
+/// pair (M, Z), under weights derived from all of them.
+fn compute_composites<S: Suite>(
+    mode: Mode,
+    public_key: &Element<S>,
+    blinded_elements: &[Element<S>],
+    evaluated_elements: &[Element<S>],
+) -> Result<(Element<S>, Element<S>), JKKX16Error> {
+    // a batch proof covers one evaluated element per blinded element
+    if blinded_elements.len() != evaluated_elements.len() {
+        return Err(deserialization_error());
+    }
+
+    let mut seed_transcript = Vec::new();
+    append_with_length(&mut seed_transcript, &serialize_element::<S>(public_key));
+    append_with_length(&mut seed_transcript, &dst::<S>("Seed-", mode));
+    let seed = S::hash(&seed_transcript);
+
+    let hash_to_scalar_dst = dst::<S>("HashToScalar-", mode);
+    let mut m = Element::<S>::identity();
+    let mut z = Element::<S>::identity();
+    for (i, (c, d)) in blinded_elements.iter().zip(evaluated_elements.iter()).enumerate() {
+        let mut composite_transcript = Vec::new();
+        append_with_length(&mut composite_transcript, &seed);
+        composite_transcript.extend_from_slice(&(i as u16).to_be_bytes());
+        append_with_length(&mut composite_transcript, &serialize_element::<S>(c));
+        append_with_length(&mut composite_transcript, &serialize_element::<S>(d));
+        composite_transcript.extend_from_slice(b"Composite");
+
+        let di = S::hash_to_scalar(&composite_transcript, &hash_to_scalar_dst)?;
+        m += *c * di;
+        z += *d * di;
+    }
+
+    Ok((m, z))
+}
+
+fn challenge<S: Suite>(
+    mode: Mode,
+    public_key: &Element<S>,
+    m: &Element<S>,
+    z: &Element<S>,
+    t2: &Element<S>,
+    t3: &Element<S>,
+) -> Result<Scalar<S>, JKKX16Error> {
+    let mut challenge_transcript = Vec::new();
+    for element in [public_key, m, z, t2, t3] {
+        append_with_length(&mut challenge_transcript, &serialize_element::<S>(element));
+    }
+    challenge_transcript.extend_from_slice(b"Challenge");
+
+    S::hash_to_scalar(&challenge_transcript, &dst::<S>("HashToScalar-", mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_std::test_rng;
+
+    // the key derivation inputs shared by all test vectors of RFC 9497, Appendix A
+    const SEED: [u8; 32] = [0xa3; 32];
+    const KEY_INFO: &[u8] = b"test key";
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_derive_key_pair_vectors() {
+        let (sk, _) = derive_key_pair::<Ristretto255Sha512>(Mode::Oprf, &SEED, KEY_INFO).unwrap();
+        assert_eq!(hex("5ebcea5ee37023ccb9fc2d2019f9d7737be85591ae8652ffa9ef0f4d37063b0e"), serialize_scalar::<Ristretto255Sha512>(&sk));
+
+        let (sk, pk) = derive_key_pair::<Ristretto255Sha512>(Mode::Voprf, &SEED, KEY_INFO).unwrap();
+        assert_eq!(hex("e6f73f344b79b379f1a0dd37e07ff62e38d9f71345ce62ae3a9bc60b04ccd909"), serialize_scalar::<Ristretto255Sha512>(&sk));
+        assert_eq!(hex("c803e2cc6b05fc15064549b5920659ca4a77b2cca6f04f6b357009335476ad4e"), serialize_element::<Ristretto255Sha512>(&pk));
+
+        let (sk, _) = derive_key_pair::<P256Sha256>(Mode::Oprf, &SEED, KEY_INFO).unwrap();
+        assert_eq!(hex("159749d750713afe245d2d39ccfaae8381c53ce92d098a9375ee70739c7ac0bf"), serialize_scalar::<P256Sha256>(&sk));
+
+        let (sk, pk) = derive_key_pair::<P256Sha256>(Mode::Voprf, &SEED, KEY_INFO).unwrap();
+        assert_eq!(hex("ca5d94c8807817669a51b196c34c1b7f8442fde4334a7121ae4736364312fca6"), serialize_scalar::<P256Sha256>(&sk));
+        assert_eq!(hex("03e17e70604bcabe198882c0a1f27a92441e774224ed9c702e51dd17038b102462"), serialize_element::<P256Sha256>(&pk));
+    }
+
+    #[test]
+    fn test_ristretto255_oprf_vectors() {
+        // RFC 9497, Appendix A.1.1, test vectors 1 and 2
+        let (sk, _) = derive_key_pair::<Ristretto255Sha512>(Mode::Oprf, &SEED, KEY_INFO).unwrap();
+        let blind_scalar = deserialize_scalar::<Ristretto255Sha512>(
+            &hex("64d37aed22a27f5191de1c1d69fadb899d8862b58eb4220029e036ec4c1f6706")
+        ).unwrap();
+
+        for (input, blinded, evaluated, output) in [
+            (
+                "00",
+                "609a0ae68c15a3cf6903766461307e5c8bb2f95e7e6550e1ffa2dc99e412803c",
+                "7ec6578ae5120958eb2db1745758ff379e77cb64fe77b0b2d8cc917ea0869c7e",
+                "527759c3d9366f277d8c6020418d96bb393ba2afb20ff90df23fb7708264e2f3\
+                 ab9135e3bd69955851de4b1f9fe8a0973396719b7912ba9ee8aa7d0b5e24bcf6",
+            ),
+            (
+                "5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a",
+                "da27ef466870f5f15296299850aa088629945a17d1f5b7f5ff043f76b3c06418",
+                "b4cbf5a4f1eeda5a63ce7b77c7d23f461db3fcab0dd28e4e17cecb5c90d02c25",
+                "f4a74c9c592497375e796aa837e907b1a045d34306a749db9f34221f7e750cb4\
+                 f2a6413a6bf6fa5e19ba6348eb673934a722a7ede2e7621306d18951e7cf2c73",
+            ),
+        ] {
+            let input = hex(input);
+            let blinded_element = blind::<Ristretto255Sha512>(Mode::Oprf, &input, &blind_scalar).unwrap();
+            assert_eq!(hex(blinded), serialize_element::<Ristretto255Sha512>(&blinded_element));
+
+            let evaluated_element = blind_evaluate::<Ristretto255Sha512>(&sk, &blinded_element);
+            assert_eq!(hex(evaluated), serialize_element::<Ristretto255Sha512>(&evaluated_element));
+
+            assert_eq!(hex(output), finalize::<Ristretto255Sha512>(&input, &blind_scalar, &evaluated_element));
+        }
+    }
+
+    #[test]
+    fn test_ristretto255_voprf_vector() {
+        // RFC 9497, Appendix A.1.2, test vector 1
+        let (sk, pk) = derive_key_pair::<Ristretto255Sha512>(Mode::Voprf, &SEED, KEY_INFO).unwrap();
+        let blind_scalar = deserialize_scalar::<Ristretto255Sha512>(
+            &hex("64d37aed22a27f5191de1c1d69fadb899d8862b58eb4220029e036ec4c1f6706")
+        ).unwrap();
+        let proof_random_scalar = deserialize_scalar::<Ristretto255Sha512>(
+            &hex("222a5e897cf59db8145db8d16e597e8facb80ae7d4e26d9881aa6f61d645fc0e")
+        ).unwrap();
+        let input = hex("00");
+
+        let blinded_element = blind::<Ristretto255Sha512>(Mode::Voprf, &input, &blind_scalar).unwrap();
+        assert_eq!(
+            hex("863f330cc1a1259ed5a5998a23acfd37fb4351a793a5b3c090b642ddc439b945"),
+            serialize_element::<Ristretto255Sha512>(&blinded_element)
+        );
+
+        let evaluated_element = blind_evaluate::<Ristretto255Sha512>(&sk, &blinded_element);
+        assert_eq!(
+            hex("aa8fa048764d5623868679402ff6108d2521884fa138cd7f9c7669a9a014267e"),
+            serialize_element::<Ristretto255Sha512>(&evaluated_element)
+        );
+
+        let proof = generate_proof::<Ristretto255Sha512>(
+            Mode::Voprf, &sk, &pk, &[blinded_element], &[evaluated_element], &proof_random_scalar
+        ).unwrap();
+        assert_eq!(
+            hex("ddef93772692e535d1a53903db24367355cc2cc78de93b3be5a8ffcc6985dd06\
+                 6d4346421d17bf5117a2a1ff0fcb2a759f58a539dfbe857a40bce4cf49ec600d"),
+            proof.to_bytes()
+        );
+        assert!(verify_proof::<Ristretto255Sha512>(Mode::Voprf, &pk, &[blinded_element], &[evaluated_element], &proof).unwrap());
+
+        assert_eq!(
+            hex("b58cfbe118e0cb94d79b5fd6a6dafb98764dff49c14e1770b566e42402da1a7d\
+                 a4d8527693914139caee5bd03903af43a491351d23b430948dd50cde10d32b3c"),
+            finalize::<Ristretto255Sha512>(&input, &blind_scalar, &evaluated_element)
+        );
+    }
+
+    #[test]
+    fn test_p256_oprf_vectors() {
+        // RFC 9497, Appendix A.3.1, test vectors 1 and 2
+        let (sk, _) = derive_key_pair::<P256Sha256>(Mode::Oprf, &SEED, KEY_INFO).unwrap();
+        let blind_scalar = deserialize_scalar::<P256Sha256>(
+            &hex("3338fa65ec36e0290022b48eb562889d89dbfa691d1cde91517fa222ed7ad364")
+        ).unwrap();
+
+        for (input, blinded, evaluated, output) in [
+            (
+                "00",
+                "03723a1e5c09b8b9c18d1dcbca29e8007e95f14f4732d9346d490ffc195110368d",
+                "030de02ffec47a1fd53efcdd1c6faf5bdc270912b8749e783c7ca75bb412958832",
+                "a0b34de5fa4c5b6da07e72af73cc507cceeb48981b97b7285fc375345fe495dd",
+            ),
+            (
+                "5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a",
+                "03cc1df781f1c2240a64d1c297b3f3d16262ef5d4cf102734882675c26231b0838",
+                "03a0395fe3828f2476ffcd1f4fe540e5a8489322d398be3c4e5a869db7fcb7c52c",
+                "c748ca6dd327f0ce85f4ae3a8cd6d4d5390bbb804c9e12dcf94f853fece3dcce",
+            ),
+        ] {
+            let input = hex(input);
+            let blinded_element = blind::<P256Sha256>(Mode::Oprf, &input, &blind_scalar).unwrap();
+            assert_eq!(hex(blinded), serialize_element::<P256Sha256>(&blinded_element));
+
+            let evaluated_element = blind_evaluate::<P256Sha256>(&sk, &blinded_element);
+            assert_eq!(hex(evaluated), serialize_element::<P256Sha256>(&evaluated_element));
+
+            assert_eq!(hex(output), finalize::<P256Sha256>(&input, &blind_scalar, &evaluated_element));
+        }
+    }
+
+    #[test]
+    fn test_p256_voprf_vector() {
+        // RFC 9497, Appendix A.3.2, test vector 1
+        let (sk, pk) = derive_key_pair::<P256Sha256>(Mode::Voprf, &SEED, KEY_INFO).unwrap();
+        let blind_scalar = deserialize_scalar::<P256Sha256>(
+            &hex("3338fa65ec36e0290022b48eb562889d89dbfa691d1cde91517fa222ed7ad364")
+        ).unwrap();
+        let proof_random_scalar = deserialize_scalar::<P256Sha256>(
+            &hex("f9db001266677f62c095021db018cd8cbb55941d4073698ce45c405d1348b7b1")
+        ).unwrap();
+        let input = hex("00");
+
+        let blinded_element = blind::<P256Sha256>(Mode::Voprf, &input, &blind_scalar).unwrap();
+        assert_eq!(
+            hex("02dd05901038bb31a6fae01828fd8d0e49e35a486b5c5d4b4994013648c01277da"),
+            serialize_element::<P256Sha256>(&blinded_element)
+        );
+
+        let evaluated_element = blind_evaluate::<P256Sha256>(&sk, &blinded_element);
+        assert_eq!(
+            hex("0209f33cab60cf8fe69239b0afbcfcd261af4c1c5632624f2e9ba29b90ae83e4a2"),
+            serialize_element::<P256Sha256>(&evaluated_element)
+        );
+
+        let proof = generate_proof::<P256Sha256>(
+            Mode::Voprf, &sk, &pk, &[blinded_element], &[evaluated_element], &proof_random_scalar
+        ).unwrap();
+        assert_eq!(
+            hex("e7c2b3c5c954c035949f1f74e6bce2ed539a3be267d1481e9ddb178533df4c26\
+                 64f69d065c604a4fd953e100b856ad83804eb3845189babfa5a702090d6fc5fa"),
+            proof.to_bytes()
+        );
+        assert!(verify_proof::<P256Sha256>(Mode::Voprf, &pk, &[blinded_element], &[evaluated_element], &proof).unwrap());
+
+        assert_eq!(
+            hex("0412e8f78b02c415ab3a288e228978376f99927767ff37c5718d420010a645a1"),
+            finalize::<P256Sha256>(&input, &blind_scalar, &evaluated_element)
+        );
+    }
+
+    fn check_voprf<S: Suite>() {
+        let rng = &mut test_rng();
+        let (sk, pk) = derive_key_pair::<S>(Mode::Voprf, &SEED, KEY_INFO).unwrap();
+        let (_, other_pk) = derive_key_pair::<S>(Mode::Voprf, &SEED, b"other key").unwrap();
+
+        let inputs = [b"alice".to_vec(), b"bob".to_vec()];
+        let blinds: Vec<Scalar<S>> = inputs.iter().map(|_| random_scalar::<S, _>(rng)).collect();
+        let blinded: Vec<Element<S>> = inputs.iter().zip(blinds.iter())
+            .map(|(input, b)| blind::<S>(Mode::Voprf, input, b).unwrap())
+            .collect();
+        let evaluated: Vec<Element<S>> = blinded.iter().map(|b| blind_evaluate::<S>(&sk, b)).collect();
+
+        // a single proof covers the whole batch, and survives serialization
+        let proof = generate_proof::<S>(Mode::Voprf, &sk, &pk, &blinded, &evaluated, &random_scalar::<S, _>(rng)).unwrap();
+        let proof = Proof::<S>::from_bytes(&proof.to_bytes()).unwrap();
+        assert!(verify_proof::<S>(Mode::Voprf, &pk, &blinded, &evaluated, &proof).unwrap());
+        assert!(!verify_proof::<S>(Mode::Voprf, &other_pk, &blinded, &evaluated, &proof).unwrap());
+        assert!(!verify_proof::<S>(Mode::Voprf, &pk, &blinded, &[evaluated[0], evaluated[0]], &proof).unwrap());
+        assert!(verify_proof::<S>(Mode::Voprf, &pk, &blinded, &evaluated[..1], &proof).is_err());
+
+        // the output does not depend on the blind
+        let evaluated_element = deserialize_element::<S>(&serialize_element::<S>(&evaluated[0])).unwrap();
+        let other_blind = random_scalar::<S, _>(rng);
+        let other_evaluated = blind_evaluate::<S>(&sk, &blind::<S>(Mode::Voprf, &inputs[0], &other_blind).unwrap());
+        assert_eq!(
+            finalize::<S>(&inputs[0], &blinds[0], &evaluated_element),
+            finalize::<S>(&inputs[0], &other_blind, &other_evaluated)
+        );
+
+        // the identity is not a valid element
+        assert!(deserialize_element::<S>(&serialize_element::<S>(&Element::<S>::identity())).is_err());
+    }
+
+    #[test]
+    fn test_voprf() {
+        check_voprf::<Ristretto255Sha512>();
+        check_voprf::<P256Sha256>();
+    }
+}
@@ -0,0 +1,315 @@
+//! The ristretto255 group of https://www.rfc-editor.org/rfc/rfc9496, over curve25519-dalek,
+//! as an arkworks `Group`, so that the PPSS scheme runs over it as over the arkworks curves.
+//! Unlike `CurveGroup`, `Group` asks for no coordinates, which ristretto255 elements do not
+//! have: they are classes of Edwards points, and are serialized in the 32-byte encoding of
+//! the RFC, whose decoding rejects anything but the canonical encoding of an element.
+//! Scalars live in the arkworks field of order ℓ = 2^252 + 27742317777372353535851937790883648493,
+//! and are carried over to curve25519-dalek by their little-endian encoding.
+
+use std::{fmt, hash::{Hash, Hasher}, iter::Sum};
+
+use ark_ec::Group;
+use ark_ff::{BigInteger, PrimeField, Zero};
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate, Write,
+};
+use ark_std::{ops::*, rand::{distributions::{Distribution, Standard}, Rng}};
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_POINT,
+    ristretto::{CompressedRistretto, RistrettoPoint},
+    traits::Identity,
+};
+use zeroize::Zeroize;
+
+pub use fr::Fr;
+
+mod fr {
+    // the derive predates these lints, which the arkworks curve crates do not see as dependencies
+    #![allow(unexpected_cfgs, non_local_definitions)]
+
+    use ark_ff::{Fp256, MontBackend, MontConfig};
+
+    #[derive(MontConfig)]
+    #[modulus = "7237005577332262213973186563042994240857116359379907606001950938285454250989"]
+    #[generator = "2"]
+    pub struct FrConfig;
+    pub type Fr = Fp256<MontBackend<FrConfig, 4>>;
+}
+
+/// An element of ristretto255.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Ristretto(RistrettoPoint);
+
+/// Length of the encoding of an element.
+const ENCODING_LENGTH: usize = 32;
+
+impl Ristretto {
+    /// Maps 64 uniformly random bytes to an element, as `hash_to_ristretto255` of RFC 9380 does
+    /// with the output of `expand_message`.
+    pub fn from_uniform_bytes(bytes: &[u8; 64]) -> Self {
+        Ristretto(RistrettoPoint::from_uniform_bytes(bytes))
+    }
+
+    fn encode(&self) -> [u8; ENCODING_LENGTH] {
+        self.0.compress().to_bytes()
+    }
+}
+
+/// Converts a scalar to the curve25519-dalek representation; both fields have order ℓ.
+fn to_dalek(scalar: &Fr) -> curve25519_dalek::Scalar {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&scalar.into_bigint().to_bytes_le());
+    curve25519_dalek::Scalar::from_bytes_mod_order(bytes)
+}
+
+impl Group for Ristretto {
+    type ScalarField = Fr;
+
+    fn generator() -> Self {
+        Ristretto(RISTRETTO_BASEPOINT_POINT)
+    }
+
+    fn double_in_place(&mut self) -> &mut Self {
+        self.0 += self.0;
+        self
+    }
+
+    fn mul_bigint(&self, other: impl AsRef<[u64]>) -> Self {
+        let bytes: Vec<u8> = other.as_ref().iter().flat_map(|limb| limb.to_le_bytes()).collect();
+        *self * Fr::from_le_bytes_mod_order(&bytes)
+    }
+}
+
+impl Default for Ristretto {
+    fn default() -> Self {
+        Ristretto(RistrettoPoint::identity())
+    }
+}
+
+impl Zero for Ristretto {
+    fn zero() -> Self {
+        Ristretto(RistrettoPoint::identity())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == RistrettoPoint::identity()
+    }
+}
+
+impl Zeroize for Ristretto {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Hash for Ristretto {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.encode().hash(state);
+    }
+}
+
+impl fmt::Debug for Ristretto {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Ristretto({})", self)
+    }
+}
+
+impl fmt::Display for Ristretto {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.encode().iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl Distribution<Ristretto> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Ristretto {
+        let mut bytes = [0u8; 64];
+        rng.fill_bytes(&mut bytes);
+        Ristretto::from_uniform_bytes(&bytes)
+    }
+}
+
+impl CanonicalSerialize for Ristretto {
+    fn serialize_with_mode<W: Write>(&self, mut writer: W, _compress: Compress) -> Result<(), SerializationError> {
+        writer.write_all(&self.encode())?;
+        Ok(())
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        ENCODING_LENGTH
+    }
+}
+
+impl Valid for Ristretto {
+    /// Every element is valid; decoding is what rejects invalid encodings.
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl CanonicalDeserialize for Ristretto {
+    fn deserialize_with_mode<R: Read>(mut reader: R, _compress: Compress, _validate: Validate) -> Result<Self, SerializationError> {
+        let mut bytes = [0u8; ENCODING_LENGTH];
+        reader.read_exact(&mut bytes)?;
+        CompressedRistretto(bytes).decompress()
+            .map(Ristretto)
+            .ok_or(SerializationError::InvalidData)
+    }
+}
+
+impl Neg for Ristretto {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Ristretto(-self.0)
+    }
+}
+
+impl<'a> Add<&'a Ristretto> for Ristretto {
+    type Output = Self;
+
+    fn add(self, other: &'a Self) -> Self {
+        self + *other
+    }
+}
+
+impl<'a> Sub<&'a Ristretto> for Ristretto {
+    type Output = Self;
+
+    fn sub(self, other: &'a Self) -> Self {
+        self - *other
+    }
+}
+
+impl<'a> Mul<&'a Fr> for Ristretto {
+    type Output = Self;
+
+    fn mul(self, scalar: &'a Fr) -> Self {
+        self * *scalar
+    }
+}
+
+impl Add for Ristretto {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Ristretto(self.0 + other.0)
+    }
+}
+
+impl Sub for Ristretto {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Ristretto(self.0 - other.0)
+    }
+}
+
+impl Mul<Fr> for Ristretto {
+    type Output = Self;
+
+    fn mul(self, scalar: Fr) -> Self {
+        Ristretto(self.0 * to_dalek(&scalar))
+    }
+}
+
+impl<'a> AddAssign<&'a Ristretto> for Ristretto {
+    fn add_assign(&mut self, other: &'a Self) {
+        *self = *self + other;
+    }
+}
+
+impl<'a> SubAssign<&'a Ristretto> for Ristretto {
+    fn sub_assign(&mut self, other: &'a Self) {
+        *self = *self - other;
+    }
+}
+
+impl<'a> MulAssign<&'a Fr> for Ristretto {
+    fn mul_assign(&mut self, scalar: &'a Fr) {
+        *self = *self * scalar;
+    }
+}
+
+impl AddAssign for Ristretto {
+    fn add_assign(&mut self, other: Self) {
+        *self += &other;
+    }
+}
+
+impl SubAssign for Ristretto {
+    fn sub_assign(&mut self, other: Self) {
+        *self -= &other;
+    }
+}
+
+impl MulAssign<Fr> for Ristretto {
+    fn mul_assign(&mut self, scalar: Fr) {
+        *self *= &scalar;
+    }
+}
+
+impl Sum for Ristretto {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc, x| acc + x)
+    }
+}
+
+impl<'a> Sum<&'a Ristretto> for Ristretto {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc, x| acc + x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::{Field, One};
+    use ark_std::{UniformRand, test_rng};
+
+    #[test]
+    fn test_scalar_field() {
+        // the fields agree on their order, hence on arithmetic
+        assert_eq!(to_dalek(&-Fr::one()), -curve25519_dalek::Scalar::ONE);
+
+        let rng = &mut test_rng();
+        let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+        assert_eq!(to_dalek(&(a * b)), to_dalek(&a) * to_dalek(&b));
+        assert_eq!(to_dalek(&a.inverse().unwrap()), to_dalek(&a).invert());
+    }
+
+    #[test]
+    fn test_group() {
+        let rng = &mut test_rng();
+        let g = Ristretto::generator();
+        let (a, b) = (Fr::rand(rng), Fr::rand(rng));
+
+        assert_eq!(g * a + g * b, g * (a + b));
+        assert_eq!((g * a) * b, g * (a * b));
+        assert_eq!(g * a - g * a, Ristretto::zero());
+        assert_eq!(g.mul_bigint((-Fr::one()).into_bigint()), -g);
+        assert_eq!(g.double(), g + g);
+        assert!(!g.is_zero());
+    }
+
+    #[test]
+    fn test_serialization() {
+        let rng = &mut test_rng();
+        let p = Ristretto::rand(rng);
+
+        let mut bytes = Vec::new();
+        p.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(ENCODING_LENGTH, bytes.len());
+        assert_eq!(p, Ristretto::deserialize_compressed(bytes.as_slice()).unwrap());
+
+        // RFC 9496, Appendix A.1: the encoding of the generator
+        assert_eq!(
+            "e2f2ae0a6abc4e71a884a961c500515f58e30b6aa582dd8db6a65945e08d2d76",
+            Ristretto::generator().to_string()
+        );
+
+        // encodings of field elements must be canonical, so the top bit is never set
+        let mut bad = [0u8; 32];
+        bad[31] = 0x80;
+        assert!(Ristretto::deserialize_compressed(bad.as_slice()).is_err());
+    }
+}
@@ -1,78 +1,147 @@
 use std::vec;
 
-use rand::SeedableRng;
+use ark_ec::Group;
 use ark_ff::Field;
 use ark_poly::{Polynomial, univariate::DensePolynomial};
+use ark_std::rand::Rng;
 
-use super::lagrange::*;
+use super::{lagrange::*, jkkx16::JKKX16Error};
+
+/// A share as an (x, y) coordinate pair.
+pub type Share<F> = (F, F);
+
+/// The shares of a verifiable sharing, along with the commitments to its polynomial.
+pub type VerifiableSharing<C> = (Vec<Share<<C as Group>::ScalarField>>, Vec<C>);
 
 /// outputs a (t,n) Shamir secret sharing of the input secret, 
 /// where t is the threshold and n is the number of shares.
 /// Note: the threshold t indicates that any t shares can be combined
 /// to recover the secret, and any subset of t-1 shares cannot.
 /// The output shares are (x,y) coordinate pairs.
-pub fn share<F: Field>(
+/// Fails unless 1 <= t <= n.
+pub fn share<F: Field, R: Rng>(
     secret: F,
     threshold: usize,
     num_shares: usize,
-) -> Vec<(F, F)> {
-    let share_ids = (1..=(num_shares as u64)).map(F::from).collect::<Vec<F>>();
+    rng: &mut R,
+) -> Result<Vec<Share<F>>, JKKX16Error> {
+    let p = sample_polynomial(secret, threshold, num_shares, rng)?;
+    Ok(evaluate_polynomial(&p, num_shares))
+}
+
+/// Feldman's verifiable variant of `share`: additionally outputs commitments
+/// b^{a_0}, ..., b^{a_{t-1}} to the coefficients of the sharing polynomial, in the
+/// given base b, against which anyone who knows b can check a share with `verify_share`.
+/// Note that b^{a_0} = b^secret. With the generator g as the base, anyone can check a
+/// share; a base derived from a secret keeps the check to those who know that secret.
+pub fn share_verifiable<C: Group, R: Rng>(
+    secret: C::ScalarField,
+    threshold: usize,
+    num_shares: usize,
+    base: &C,
+    rng: &mut R,
+) -> Result<VerifiableSharing<C>, JKKX16Error> {
+    let p = sample_polynomial(secret, threshold, num_shares, rng)?;
+
+    let commitments = p.coeffs.iter().map(|a| base.mul(a)).collect();
+
+    Ok((evaluate_polynomial(&p, num_shares), commitments))
+}
+
+/// Checks that the share (x, y) lies on the polynomial committed to in the base b,
+/// i.e., that b^y == prod_k C_k^{x^k}.
+pub fn verify_share<C: Group>(
+    commitments: &[C],
+    base: &C,
+    share: &Share<C::ScalarField>,
+) -> bool {
+    let (x, y) = share;
+    base.mul(y) == evaluate_commitments::<C>(commitments, x)
+}
+
+/// Computes b^{p(x)} from the commitments to the coefficients of p in the base b.
+pub fn evaluate_commitments<C: Group>(commitments: &[C], x: &C::ScalarField) -> C {
+    // Horner's rule in the exponent
+    commitments.iter().rev()
+        .fold(C::zero(), |acc, c| acc.mul(x) + c)
+}
 
-    // generate random x-coordinates
-    let mut rng = rand_chacha::ChaCha8Rng::from_seed([0u8; 32]);
+fn sample_polynomial<F: Field, R: Rng>(
+    secret: F,
+    threshold: usize,
+    num_shares: usize,
+    rng: &mut R,
+) -> Result<DensePolynomial<F>, JKKX16Error> {
+    if threshold == 0 || threshold > num_shares {
+        return Err(JKKX16Error::InvalidThresholdError(threshold, num_shares));
+    }
 
     // lets sample a random polynomial with a fixed point at x = 0
     // A t degree polynomial is defined by t + 1 coefficients: a_0, a_1, ..., a_t
     // such that p(x) = a_0 + a_1 * x + a_2 * x^2 + ... + a_t * x^t
     // here we want t = threshold - 1, so we have threshold number of coefficients
-    let p = DensePolynomial { coeffs: {
+    Ok(DensePolynomial { coeffs: {
         let mut coefficients = vec![secret]; // the secret is embedded at x = 0
-        (1..threshold).for_each(|_| coefficients.push(F::rand(&mut rng)));
+        (1..threshold).for_each(|_| coefficients.push(F::rand(rng)));
         coefficients
-    }};
+    }})
+}
+
+fn evaluate_polynomial<F: Field>(p: &DensePolynomial<F>, num_shares: usize) -> Vec<(F, F)> {
+    let share_ids = (1..=(num_shares as u64)).map(F::from).collect::<Vec<F>>();
 
     // we skip over 0 because that's where the secret is embedded
     let xs = share_ids.to_vec();
     let ys = xs.iter().map(|x| p.evaluate(x)).collect::<Vec<F>>();
 
     // output is a vector of (x,y) coordinate pairs
-    let shares = xs.iter().zip(ys.iter()).map(|(x, y)| (*x, *y)).collect();
-
-    shares
+    xs.iter().zip(ys.iter()).map(|(x, y)| (*x, *y)).collect()
 }
 
 /// recovers the secret given a subset of t shares,
 /// where each share is a (x,y) coordinate pair.
 /// Note that this will return a arbitrary value 
 /// if the reconstruction threshold is not met
+/// and an error if two shares have the same x-coordinate
 pub fn recover<F: Field>(
     shares: &[(F, F)],
-) -> F {
-    let xs = shares.iter().map(|(x, _)| *x).collect::<Vec<F>>();
-    let ys = shares.iter().map(|(_, y)| *y).collect::<Vec<F>>();
-
-    let λs: Vec<F> = (0..xs.len()).map(|i| {
-        // each lagrange coefficient is computed with respect to x = 0,
-        // since that's where we are embedding the secret in our entire
-        // construction. So the ith coefficient is w.r.t. xs[i].
-        let λ_i = lagrange_coefficient(&xs, i, &F::zero()); λ_i
-    }).collect();
-
-    // the reconstructed secret is a weighted sum of ys, 
-    // with the lagrange coefficients as weights
-    let secret = λs.iter().zip(ys.iter())
-        .fold(F::zero(), |acc, (&λ_i, &y_i)| { acc + λ_i * y_i });
-
-    secret
+) -> Result<F, DuplicateCoordinateError> {
+    // the secret is embedded at x = 0 in our entire construction
+    evaluate(shares, &F::zero())
+}
+
+/// evaluates the polynomial interpolated from the given shares at the point `x`;
+/// given t shares of a (t,n) sharing, this recomputes the share held by any party.
+pub fn evaluate<F: Field>(
+    shares: &[(F, F)],
+    x: &F,
+) -> Result<F, DuplicateCoordinateError> {
+    let (xs, ys): (Vec<F>, Vec<F>) = shares.iter().copied().unzip();
+    Ok(Interpolator::new(&xs)?.interpolate(&ys, x))
+}
+
+/// evaluates the polynomial interpolated from the given shares at each of the points `xs`,
+/// setting up the interpolation only once.
+pub fn evaluate_many<F: Field>(
+    shares: &[(F, F)],
+    xs: &[F],
+) -> Result<Vec<F>, DuplicateCoordinateError> {
+    let (share_xs, ys): (Vec<F>, Vec<F>) = shares.iter().copied().unzip();
+    let interpolator = Interpolator::new(&share_xs)?;
+    Ok(xs.iter().map(|x| interpolator.interpolate(&ys, x)).collect())
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
 
+    use ark_ec::Group;
+    use ark_std::test_rng;
+
     use super::*;
 
     type F = ark_bls12_381::Fr;
+    type G = ark_bls12_381::G1Projective;
 
     #[test]
     fn test_share_and_recover() {
@@ -80,13 +149,86 @@ mod tests {
         let threshold = 3;
         let num_shares = 5;
 
-        let shares = share(secret, threshold, num_shares);
+        let shares = share(secret, threshold, num_shares, &mut test_rng()).unwrap();
+
+        assert_eq!(secret, recover(&shares[..threshold]).unwrap());
+        assert_eq!(secret, recover(&shares[1..4]).unwrap());
+        assert_eq!(secret, recover(&shares[2..5]).unwrap());
+        assert_eq!(secret, recover(&shares[..]).unwrap());
+        assert_eq!(secret, recover(vec![shares[0], shares[2], shares[4]].as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_evaluate_missing_shares() {
+        let secret = F::from(42u64);
+        let shares = share(secret, 3, 5, &mut test_rng()).unwrap();
+
+        // any 3 shares determine the shares held by the other parties
+        let subset = vec![shares[4], shares[0], shares[2]];
+        for (x, y) in shares.iter() {
+            assert_eq!(*y, evaluate(&subset, x).unwrap());
+        }
+        let xs: Vec<F> = shares.iter().map(|(x, _)| *x).collect();
+        let ys: Vec<F> = shares.iter().map(|(_, y)| *y).collect();
+        assert_eq!(ys, evaluate_many(&subset, &xs).unwrap());
+    }
+
+    #[test]
+    fn test_verifiable_share() {
+        let secret = F::from(42u64);
+        let g = G::generator();
+        let (shares, commitments) = share_verifiable::<G, _>(secret, 3, 5, &g, &mut test_rng()).unwrap();
+
+        assert_eq!(3, commitments.len());
+        assert_eq!(secret, recover(&shares[1..4]).unwrap());
+        for share in shares.iter() {
+            assert!(verify_share::<G>(&commitments, &g, share));
+        }
+
+        // a share off the polynomial is pinpointed
+        let (x, y) = shares[2];
+        assert!(!verify_share::<G>(&commitments, &g, &(x, y + F::from(1u64))));
+        assert!(!verify_share::<G>(&commitments, &g, &(shares[3].0, y)));
+
+        // in another base, the shares only check against that base
+        let base = g * F::from(7u64);
+        let (shares, commitments) = share_verifiable::<G, _>(secret, 3, 5, &base, &mut test_rng()).unwrap();
+        assert_eq!(base * secret, commitments[0]);
+        assert!(shares.iter().all(|share| verify_share::<G>(&commitments, &base, share)));
+        assert!(!shares.iter().any(|share| verify_share::<G>(&commitments, &g, share)));
+    }
+
+    #[test]
+    fn test_sharings_differ() {
+        let secret = F::from(42u64);
+        let mut rng = test_rng();
+
+        // two sharings of the same secret use fresh coefficients
+        let first = share(secret, 3, 5, &mut rng).unwrap();
+        let second = share(secret, 3, 5, &mut rng).unwrap();
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert_eq!(a.0, b.0);
+            assert_ne!(a.1, b.1);
+        }
+        assert_eq!(recover(&first[..3]), recover(&second[2..]));
 
-        assert_eq!(secret, recover(&shares[..threshold]));
-        assert_eq!(secret, recover(&shares[1..4]));
-        assert_eq!(secret, recover(&shares[2..5]));
-        assert_eq!(secret, recover(&shares[..]));
-        assert_eq!(secret, recover(vec![shares[0], shares[2], shares[4]].as_slice()));
+        let g = G::generator();
+        let (_, first) = share_verifiable::<G, _>(secret, 3, 5, &g, &mut rng).unwrap();
+        let (_, second) = share_verifiable::<G, _>(secret, 3, 5, &g, &mut rng).unwrap();
+        assert_eq!(first[0], second[0]);
+        assert_ne!(first[1..], second[1..]);
+    }
+
+    #[test]
+    fn test_invalid_threshold() {
+        let secret = F::from(42u64);
+        let mut rng = test_rng();
+
+        assert!(matches!(share(secret, 0, 5, &mut rng), Err(JKKX16Error::InvalidThresholdError(0, 5))));
+        assert!(matches!(share(secret, 6, 5, &mut rng), Err(JKKX16Error::InvalidThresholdError(6, 5))));
+        assert!(matches!(share(secret, 1, 0, &mut rng), Err(JKKX16Error::InvalidThresholdError(1, 0))));
+        assert!(share_verifiable::<G, _>(secret, 6, 5, &G::generator(), &mut rng).is_err());
+        assert_eq!(1, share(secret, 1, 1, &mut rng).unwrap().len());
     }
 
     // let's check if shares of shares can be recovered
@@ -96,8 +238,10 @@ mod tests {
         let threshold = 3;
         let num_parties = 5;
 
+        let mut rng = test_rng();
+
         // this is the first layer of shares
-        let shares: Vec<(F,F)> = share(secret, threshold, num_parties);
+        let shares: Vec<(F,F)> = share(secret, threshold, num_parties, &mut rng).unwrap();
 
         // this contains the shares of shares, indexed by the receiver id
         let mut incoming_shares: HashMap<F, Vec<(F,F)>> = HashMap::new();
@@ -105,22 +249,22 @@ mod tests {
         // each shareholder becomes a dealer in the next layer of shares
         for (dealer_id, share_value) in shares {
 
-            let shares_of_shares = share(share_value, threshold, num_parties);
+            let shares_of_shares = share(share_value, threshold, num_parties, &mut rng).unwrap();
 
             for (receiver_id, share_of_share_value) in shares_of_shares {
-                incoming_shares.entry(receiver_id).or_insert(Vec::new()).push((dealer_id, share_of_share_value));
+                incoming_shares.entry(receiver_id).or_default().push((dealer_id, share_of_share_value));
             }
         }
 
         // now we have shares of shares, let's try to get a sharing of the original secret
         let mut reconstructed_shares: Vec<(F,F)> = Vec::new();
         for (receiver_id, shares_of_shares) in incoming_shares {
-            let reconstructed_share = recover(&shares_of_shares[..threshold]);
+            let reconstructed_share = recover(&shares_of_shares[..threshold]).unwrap();
             reconstructed_shares.push((receiver_id, reconstructed_share));
         }
 
         // we should be able to recover the original secret
-        assert_eq!(secret, recover(&reconstructed_shares[..threshold]));
+        assert_eq!(secret, recover(&reconstructed_shares[..threshold]).unwrap());
 
     }
 }
\ No newline at end of file
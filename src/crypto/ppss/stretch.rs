@@ -0,0 +1,128 @@
+//! Memory-hard stretching of the password before it is hashed to the curve. Without it,
+//! a coalition of a threshold number of keypers can test PIN guesses at the cost of a
+//! few curve operations each; with it, every guess costs a full Argon2id evaluation.
+
+use std::fmt;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use sha2::{Digest, Sha256};
+
+/// Cost parameters of Argon2id, which are recorded alongside the ciphertext,
+/// so that reconstruction stretches the password exactly as keygen did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StretchParameters {
+    /// memory size, in KiB
+    pub memory_kib: u32,
+    /// number of passes over the memory
+    pub iterations: u32,
+    /// degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Default for StretchParameters {
+    /// The OWASP recommendation for Argon2id: 19 MiB of memory, 2 passes, 1 lane.
+    fn default() -> Self {
+        StretchParameters { memory_kib: 19 * 1024, iterations: 2, parallelism: 1 }
+    }
+}
+
+/// Bounds on the cost parameters, for 1 GiB of memory at most.
+const MIN_MEMORY_KIB: u32 = 7 * 1024;
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MIN_ITERATIONS: u32 = 1;
+const MAX_ITERATIONS: u32 = 16;
+const MIN_PARALLELISM: u32 = 1;
+const MAX_PARALLELISM: u32 = 16;
+
+impl StretchParameters {
+    /// Checks the parameters against the bounds we accept, which vaults are untrusted input for:
+    /// below them a guess becomes cheap, and above them stretching exhausts the client's memory
+    /// or time. The lowest memory is that of the cheapest OWASP recommendation for Argon2id.
+    pub fn check(&self) -> Result<(), StretchError> {
+        let bounds = [
+            ("memory_kib", self.memory_kib, MIN_MEMORY_KIB, MAX_MEMORY_KIB),
+            ("iterations", self.iterations, MIN_ITERATIONS, MAX_ITERATIONS),
+            ("parallelism", self.parallelism, MIN_PARALLELISM, MAX_PARALLELISM),
+        ];
+        for (name, value, min, max) in bounds {
+            if value < min || value > max {
+                return Err(StretchError::OutOfBounds(name, value));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StretchError {
+    /// the named cost parameter has a value outside the accepted bounds
+    OutOfBounds(&'static str, u32),
+    /// Argon2 failed on the parameters or the inputs
+    Argon2(argon2::Error),
+}
+
+impl std::error::Error for StretchError {}
+
+impl fmt::Display for StretchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StretchError::OutOfBounds(name, value) =>
+                write!(f, "The password stretch parameter {} = {} is out of bounds.", name, value),
+            StretchError::Argon2(ref e) =>
+                write!(f, "Failed to stretch the password: {}", e),
+        }
+    }
+}
+
+const SALT_PREFIX: &[u8] = b"bedrock-password-stretch";
+
+/// Computes Argon2id(password, salt = SHA256(prefix || client_id)). Salting with the
+/// client id forces an attacker to stretch each PIN guess anew for every client.
+pub fn stretch_password(
+    params: &StretchParameters,
+    client_id: &[u8],
+    password: &[u8],
+) -> Result<Vec<u8>, StretchError> {
+    params.check()?;
+    let argon2_params = Params::new(params.memory_kib, params.iterations, params.parallelism, Some(32))
+        .map_err(StretchError::Argon2)?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params);
+
+    let mut hasher = Sha256::new();
+    hasher.update(SALT_PREFIX);
+    hasher.update(client_id);
+    let salt = hasher.finalize();
+
+    let mut output = vec![0u8; 32];
+    argon2.hash_password_into(password, &salt, &mut output).map_err(StretchError::Argon2)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stretch_password() {
+        let params = StretchParameters { memory_kib: MIN_MEMORY_KIB, iterations: 1, parallelism: 1 };
+
+        let stretched = stretch_password(&params, b"alice@gmail.com", b"198837").unwrap();
+        assert_eq!(stretched, stretch_password(&params, b"alice@gmail.com", b"198837").unwrap());
+
+        // the output depends on the client, the password and the cost parameters
+        assert_ne!(stretched, stretch_password(&params, b"bob@gmail.com", b"198837").unwrap());
+        assert_ne!(stretched, stretch_password(&params, b"alice@gmail.com", b"000000").unwrap());
+        let costlier = StretchParameters { iterations: 2, ..params };
+        assert_ne!(stretched, stretch_password(&costlier, b"alice@gmail.com", b"198837").unwrap());
+
+        // parameters out of bounds are rejected before Argon2 runs
+        let too_cheap = StretchParameters { memory_kib: 64, ..params };
+        assert_eq!(Err(StretchError::OutOfBounds("memory_kib", 64)), stretch_password(&too_cheap, b"alice@gmail.com", b"198837"));
+        let too_costly = StretchParameters { memory_kib: u32::MAX, ..params };
+        assert_eq!(Err(StretchError::OutOfBounds("memory_kib", u32::MAX)), stretch_password(&too_costly, b"alice@gmail.com", b"198837"));
+        for invalid in [StretchParameters { iterations: 0, ..params }, StretchParameters { parallelism: 1 << 24, ..params }] {
+            assert!(matches!(stretch_password(&invalid, b"alice@gmail.com", b"198837"), Err(StretchError::OutOfBounds(_, _))));
+        }
+        assert_eq!(Ok(()), StretchParameters::default().check());
+    }
+}
@@ -0,0 +1,242 @@
+//! Curves the PPSS scheme can be instantiated with. Each suite fixes the prime-order group
+//! and a hash-to-curve function onto it, following https://www.rfc-editor.org/rfc/rfc9380.
+//! For P-256 and secp256k1, the RustCrypto implementations of the standard suites are used,
+//! and the resulting points are carried over to the arkworks curves by their coordinates.
+//! The group of a suite is only required to be an arkworks `Group`, which asks for no
+//! coordinates, so that ristretto255 from `ristretto` serves as a suite as well.
+
+use ark_ec::{
+    Group, short_weierstrass::{Affine, Projective},
+    hashing::{HashToCurve, HashToCurveError, curve_maps::wb::{WBConfig, WBMap}, map_to_curve_hasher::MapToCurveBasedHasher}
+};
+use ark_ff::{field_hashers::DefaultFieldHasher, PrimeField};
+use sha2::Sha256;
+
+use super::jkkx16::JKKX16Error;
+use super::rfc9497::expand_message_xmd_sha512;
+use super::ristretto::Ristretto;
+
+use k256::elliptic_curve::{
+    hash2curve::{ExpandMsgXmd, GroupDigest},
+    sec1::ToEncodedPoint,
+};
+
+pub trait CurveSuite: Send + Sync + 'static {
+    type Group: Group;
+
+    /// short name of the curve, e.g. in keyper routes
+    const NAME: &'static str;
+
+    /// salt of the keyper's per-client key derivation, separating the keys of different curves
+    const SERVER_KEY_SALT: &'static [u8];
+
+    /// domain separation tag under which the scheme hashes to the curve
+    const DST: &'static str;
+
+    /// Hashes an arbitrary message to a point on the curve.
+    fn hash_to_curve(msg: &[u8]) -> Result<Self::Group, HashToCurveError> {
+        Self::hash_to_curve_with_dst(msg, Self::DST.as_bytes())
+    }
+
+    /// Hashes an arbitrary message to a point on the curve under the given domain separation
+    /// tag, by the hash-to-curve suite of RFC 9380 for the curve.
+    fn hash_to_curve_with_dst(msg: &[u8], dst: &[u8]) -> Result<Self::Group, HashToCurveError>;
+}
+
+/// The G1 group of BLS12-381, which vaults used before the curve became configurable.
+pub struct Bls12381G1;
+
+/// NIST P-256, also known as secp256r1.
+pub struct P256;
+
+/// secp256k1, the curve of Bitcoin.
+pub struct Secp256k1;
+
+/// ristretto255, the prime-order group built from Curve25519.
+pub struct Ristretto255;
+
+impl CurveSuite for Bls12381G1 {
+    type Group = ark_bls12_381::G1Projective;
+
+    const NAME: &'static str = "bls12_381";
+
+    const SERVER_KEY_SALT: &'static [u8] = b"bedrock-jkkx16-server-key";
+
+    const DST: &'static str = "BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_";
+
+    // Adapted from https://github.com/ArnaudBrousseau/bls_on_arkworks
+    /// ([spec link](https://www.ietf.org/archive/id/draft-irtf-cfrg-bls-signature-05.html#section-1.3))
+    /// A cryptographic hash function that takes as input an arbitrary octet string and returns a point on an
+    /// elliptic curve. Functions of this kind are defined in [hash-to-curve-spec](https://datatracker.ietf.org/doc/html/draft-irtf-cfrg-hash-to-curve-16).
+    fn hash_to_curve_with_dst(msg: &[u8], dst: &[u8]) -> Result<ark_bls12_381::G1Projective, HashToCurveError> {
+        hash_to_curve_xmd_sha256::<ark_bls12_381::g1::Config>(msg, dst).map(Into::into)
+    }
+}
+
+/// Hashes onto a curve with the SSWU map onto an isogenous curve, by the `*_XMD:SHA-256_SSWU_RO_`
+/// suites of RFC 9380; for BLS12-381, these are the suites the BLS signature ciphersuites use.
+pub(crate) fn hash_to_curve_xmd_sha256<P: WBConfig>(msg: &[u8], dst: &[u8]) -> Result<Affine<P>, HashToCurveError> {
+    let mapper = MapToCurveBasedHasher::<
+        Projective<P>,
+        DefaultFieldHasher<Sha256, 128>,
+        WBMap<P>,
+    >::new(dst)?;
+    mapper.hash(msg)
+}
+
+impl CurveSuite for P256 {
+    type Group = ark_secp256r1::Projective;
+
+    const NAME: &'static str = "p256";
+
+    const SERVER_KEY_SALT: &'static [u8] = b"bedrock-jkkx16-server-key-p256";
+
+    const DST: &'static str = "BEDROCK-V01-CS01-with-P256_XMD:SHA-256_SSWU_RO_";
+
+    /// P256_XMD:SHA-256_SSWU_RO_ from RFC 9380, Section 8.2.
+    fn hash_to_curve_with_dst(msg: &[u8], dst: &[u8]) -> Result<ark_secp256r1::Projective, HashToCurveError> {
+        let point = p256::NistP256::hash_from_bytes::<ExpandMsgXmd<Sha256>>(&[msg], &[dst])
+            .map_err(|_| HashToCurveError::MapToCurveError("P-256 hash to curve failed".to_string()))?
+            .to_affine()
+            .to_encoded_point(false);
+
+        from_coordinates(point.x(), point.y()).map(Into::into)
+    }
+}
+
+impl CurveSuite for Secp256k1 {
+    type Group = ark_secp256k1::Projective;
+
+    const NAME: &'static str = "secp256k1";
+
+    const SERVER_KEY_SALT: &'static [u8] = b"bedrock-jkkx16-server-key-secp256k1";
+
+    const DST: &'static str = "BEDROCK-V01-CS01-with-secp256k1_XMD:SHA-256_SSWU_RO_";
+
+    /// secp256k1_XMD:SHA-256_SSWU_RO_ from RFC 9380, Section 8.7.
+    fn hash_to_curve_with_dst(msg: &[u8], dst: &[u8]) -> Result<ark_secp256k1::Projective, HashToCurveError> {
+        let point = k256::Secp256k1::hash_from_bytes::<ExpandMsgXmd<Sha256>>(&[msg], &[dst])
+            .map_err(|_| HashToCurveError::MapToCurveError("secp256k1 hash to curve failed".to_string()))?
+            .to_affine()
+            .to_encoded_point(false);
+
+        from_coordinates(point.x(), point.y()).map(Into::into)
+    }
+}
+
+impl CurveSuite for Ristretto255 {
+    type Group = Ristretto;
+
+    const NAME: &'static str = "ristretto255";
+
+    const SERVER_KEY_SALT: &'static [u8] = b"bedrock-jkkx16-server-key-ristretto255";
+
+    const DST: &'static str = "BEDROCK-V01-CS01-with-ristretto255_XMD:SHA-512_R255MAP_RO_";
+
+    /// ristretto255_XMD:SHA-512_R255MAP_RO_ from RFC 9380, Appendix B, as in the RFC 9497 suite.
+    fn hash_to_curve_with_dst(msg: &[u8], dst: &[u8]) -> Result<Ristretto, HashToCurveError> {
+        let uniform_bytes = expand_message_xmd_sha512(msg, dst).map_err(|e| match e {
+            JKKX16Error::HashingError(e) => e,
+            e => HashToCurveError::MapToCurveError(e.to_string()),
+        })?;
+
+        Ok(Ristretto::from_uniform_bytes(&uniform_bytes))
+    }
+}
+
+/// Builds an arkworks point from the big-endian affine coordinates of a point
+/// that is known to be on the curve; the point at infinity has no coordinates.
+fn from_coordinates<P: ark_ec::short_weierstrass::SWCurveConfig>(
+    x: Option<&impl AsRef<[u8]>>,
+    y: Option<&impl AsRef<[u8]>>,
+) -> Result<Affine<P>, HashToCurveError>
+where
+    P::BaseField: PrimeField,
+{
+    match (x, y) {
+        (Some(x), Some(y)) => Ok(Affine::new_unchecked(
+            P::BaseField::from_be_bytes_mod_order(x.as_ref()),
+            P::BaseField::from_be_bytes_mod_order(y.as_ref()),
+        )),
+        _ => Err(HashToCurveError::MapToCurveError("hashed to the point at infinity".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ec::CurveGroup;
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use ark_std::Zero;
+
+    use crate::crypto::ppss::ristretto::Fr;
+
+    fn check_suite<S: CurveSuite>() {
+        let p = S::hash_to_curve(b"alice@gmail.com").unwrap();
+        assert!(!p.is_zero());
+
+        // deserialization checks that the point is a valid element of the group
+        let mut bytes = Vec::new();
+        p.serialize_uncompressed(&mut bytes).unwrap();
+        assert_eq!(p, S::Group::deserialize_uncompressed(bytes.as_slice()).unwrap());
+
+        assert_eq!(p, S::hash_to_curve(b"alice@gmail.com").unwrap());
+        assert_ne!(p, S::hash_to_curve(b"bob@gmail.com").unwrap());
+    }
+
+    #[test]
+    fn test_hash_to_curve() {
+        check_suite::<Bls12381G1>();
+        check_suite::<P256>();
+        check_suite::<Secp256k1>();
+        check_suite::<Ristretto255>();
+    }
+
+    #[test]
+    fn test_p256_rfc9380_vector() {
+        // RFC 9380, Appendix J.1.1, P256_XMD:SHA-256_SSWU_RO_ with msg = ""
+        let p = P256::hash_to_curve_with_dst(b"", b"QUUX-V01-CS02-with-P256_XMD:SHA-256_SSWU_RO_")
+            .unwrap()
+            .into_affine();
+
+        assert!(p.is_on_curve());
+        assert_eq!(
+            p.x,
+            ark_secp256r1::Fq::from_be_bytes_mod_order(&hex("2c15230b26dbc6fc9a37051158c95b79656e17a1a920b11394ca91c44247d3e4"))
+        );
+        assert_eq!(
+            p.y,
+            ark_secp256r1::Fq::from_be_bytes_mod_order(&hex("8a7a74985cc5c776cdfe4b1f19884970453912e9d31528c060be9ab5c43e8415"))
+        );
+    }
+
+    #[test]
+    fn test_ristretto255_rfc9497_vector() {
+        // RFC 9497, Appendix A.1.1, test vector 1: the blinded element is the hash of the input,
+        // under the HashToGroup DST of the OPRF mode, times the blind
+        let p = Ristretto255::hash_to_curve_with_dst(&[0x00], b"HashToGroup-OPRFV1-\x00-ristretto255-SHA512").unwrap();
+        let blind = Fr::from_le_bytes_mod_order(&hex("64d37aed22a27f5191de1c1d69fadb899d8862b58eb4220029e036ec4c1f6706"));
+
+        let mut bytes = Vec::new();
+        (p * blind).serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(hex("609a0ae68c15a3cf6903766461307e5c8bb2f95e7e6550e1ffa2dc99e412803c"), bytes);
+    }
+
+    #[test]
+    fn test_bls12_381_g2_rfc9380_vector() {
+        // RFC 9380, Appendix J.10.1, BLS12381G2_XMD:SHA-256_SSWU_RO_ with msg = ""
+        let p = hash_to_curve_xmd_sha256::<ark_bls12_381::g2::Config>(
+            b"", b"QUUX-V01-CS02-with-BLS12381G2_XMD:SHA-256_SSWU_RO_"
+        ).unwrap();
+
+        let fq = |s| ark_bls12_381::Fq::from_be_bytes_mod_order(&hex(s));
+        assert_eq!(p.x.c0, fq("0141ebfbdca40eb85b87142e130ab689c673cf60f1a3e98d69335266f30d9b8d4ac44c1038e9dcdd5393faf5c41fb78a"));
+        assert_eq!(p.x.c1, fq("05cb8437535e20ecffaef7752baddf98034139c38452458baeefab379ba13dff5bf5dd71b72418717047f5b0f37da03d"));
+        assert_eq!(p.y.c0, fq("0503921d7f6a12805e72940b963c0cf3471c7b2a524950ca195d11062ee75ec076daf2d4bc358c4b190c0c98064fdd92"));
+        assert_eq!(p.y.c1, fq("12424ac32561493f3fe3c260708a12b7c620e7be00099a974e259ddc7d1f6395c3c811cdd19f1e8dbf3e9ecfdcbab8d6"));
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+}
@@ -0,0 +1,149 @@
+//! Privacy Pass-style anonymous tokens, which let the storage service meter a client's
+//! guesses while the keypers keep no state per client. A token is a PRF evaluation, under
+//! a token key the storage service shares with the keyper, of the very reconstruct request
+//! that it pays for. The client obtains it blindly, exactly as it obtains the keyper's PRF
+//! evaluation in `jkkx16`, once the storage service has authenticated its credential; the
+//! storage service thus cannot link the token it issues to the request it is spent on.
+//!
+//! Since a token is bound to the blinded input, the client id and the key version of a single
+//! request, spending it twice only yields the same evaluation twice, so the keyper need not
+//! track spent tokens.
+
+use ark_crypto_primitives::Error;
+use ark_ec::Group;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::{ops::*, rand::Rng};
+
+use super::{PpssPcheme, ServerKey};
+use super::jkkx16::{
+    ClientState, JKKX16Error, JKKX16Scheme, Parameters, PrfInput, PrfOutput,
+    derive_client_key, unblind_prf_output,
+};
+use super::suite::CurveSuite;
+
+/// Stands in for the client id in the derivation of the token key from its seed,
+/// which is never used to evaluate the PRF for actual clients.
+const TOKEN_CLIENT_ID: &[u8] = b"bedrock-token";
+
+/// An unblinded token, attached to the `PrfInput` it pays for.
+#[derive(Clone, Default, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Token<C: Group> {
+    /// version of the token key, which the keyper needs to re-derive it
+    pub key_version: u32,
+    /// H(request)^k for the token key k
+    pub evaluation: C,
+}
+
+/// The client's state for unblinding, along with the blinded request for the issuer.
+pub type TokenRequest<S> = (ClientState<<S as CurveSuite>::Group>, PrfInput<<S as CurveSuite>::Group>);
+
+/// Returns the public key under which tokens are issued, which clients
+/// learn out of band, lest the issuer tag them with keys of its own.
+pub fn public_key<S: CurveSuite>(pp: &Parameters<S::Group>, key: &ServerKey) -> S::Group {
+    pp.generator.mul(derive_client_key::<S>(key, TOKEN_CLIENT_ID))
+}
+
+/// Blinds the request to be paid for, to be sent to the issuer.
+pub fn client_generate_token_request<S: CurveSuite, R: Rng>(
+    pp: &Parameters<S::Group>,
+    prf_input: &PrfInput<S::Group>,
+    rng: &mut R,
+) -> Result<TokenRequest<S>, Error> {
+    let binding = prf_input.binding().map_err(JKKX16Error::SerializationError)?;
+    JKKX16Scheme::<S>::client_generate_reconstruct_request(pp, TOKEN_CLIENT_ID, &binding, rng)
+}
+
+/// Evaluates the token key on the blinded request, along with a proof that it was used;
+/// the issuer calls this once it has authenticated the client and counted the guess.
+pub fn server_issue_token<S: CurveSuite>(
+    pp: &Parameters<S::Group>,
+    key: &ServerKey,
+    token_request: &PrfInput<S::Group>,
+) -> Result<PrfOutput<S::Group>, Error> {
+    JKKX16Scheme::<S>::server_process_keygen_request(pp, key, TOKEN_CLIENT_ID, token_request)
+}
+
+/// Checks that the issuer used its published key, and strips the client's blind.
+pub fn client_finalize_token<C: Group>(
+    pp: &Parameters<C>,
+    state: &ClientState<C>,
+    issuer_response: &PrfOutput<C>,
+    public_key: &C,
+) -> Result<Token<C>, JKKX16Error> {
+    if issuer_response.public_key != *public_key {
+        return Err(JKKX16Error::KeyMismatchError(0));
+    }
+
+    Ok(Token {
+        key_version: issuer_response.key_version,
+        evaluation: unblind_prf_output(pp, state, 0, issuer_response)?,
+    })
+}
+
+/// Checks that the request carries a token issued for it under the given key.
+pub fn server_verify_token<S: CurveSuite>(
+    key: &ServerKey,
+    prf_input: &PrfInput<S::Group>,
+) -> Result<bool, JKKX16Error> {
+    let Some(token) = &prf_input.token else {
+        return Ok(false);
+    };
+    if token.key_version != key.version {
+        return Err(JKKX16Error::UnknownKeyVersionError(token.key_version));
+    }
+
+    let binding = prf_input.binding().map_err(JKKX16Error::SerializationError)?;
+    let request_hash = S::hash_to_curve(&binding).map_err(JKKX16Error::HashingError)?;
+    Ok(request_hash.mul(derive_client_key::<S>(key, TOKEN_CLIENT_ID)) == token.evaluation)
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_std::test_rng;
+
+    use super::*;
+    use crate::crypto::ppss::suite::{Bls12381G1, P256, Ristretto255};
+
+    fn check_token<S: CurveSuite>() {
+        let rng = &mut test_rng();
+        let token_key = ServerKey::new(1, [5u8; 32]);
+        let pp = JKKX16Scheme::<S>::setup(rng).unwrap();
+        let (_, mut prf_input) = JKKX16Scheme::<S>::client_generate_reconstruct_request(&pp, b"alice@gmail.com", b"198837", rng).unwrap();
+
+        let (state, token_request) = client_generate_token_request::<S, _>(&pp, &prf_input, rng).unwrap();
+        let response = server_issue_token::<S>(&pp, &token_key, &token_request).unwrap();
+
+        // the issuer sees neither the request nor the token
+        let token = client_finalize_token(&pp, &state, &response, &public_key::<S>(&pp, &token_key)).unwrap();
+        assert_ne!(token.evaluation, token_request.blinded_prf_input);
+        assert!(!server_verify_token::<S>(&token_key, &prf_input).unwrap());
+        prf_input.token = Some(token.clone());
+        assert!(server_verify_token::<S>(&token_key, &prf_input).unwrap());
+
+        // the token only pays for the request it was issued for, and only under its key
+        let (_, mut other_input) = JKKX16Scheme::<S>::client_generate_reconstruct_request(&pp, b"alice@gmail.com", b"198837", rng).unwrap();
+        other_input.token = Some(token.clone());
+        assert!(!server_verify_token::<S>(&token_key, &other_input).unwrap());
+        let other_version = PrfInput { key_version: Some(2), ..prf_input.clone() };
+        assert!(!server_verify_token::<S>(&token_key, &other_version).unwrap());
+        assert!(!server_verify_token::<S>(&ServerKey::new(1, [6u8; 32]), &prf_input).unwrap());
+        assert!(matches!(
+            server_verify_token::<S>(&ServerKey::new(2, [5u8; 32]), &prf_input),
+            Err(JKKX16Error::UnknownKeyVersionError(1))
+        ));
+
+        // an issuer using a key other than its published one is caught
+        let response = server_issue_token::<S>(&pp, &ServerKey::new(1, [6u8; 32]), &token_request).unwrap();
+        assert!(matches!(
+            client_finalize_token(&pp, &state, &response, &public_key::<S>(&pp, &token_key)),
+            Err(JKKX16Error::KeyMismatchError(0))
+        ));
+    }
+
+    #[test]
+    fn test_token() {
+        check_token::<Bls12381G1>();
+        check_token::<P256>();
+        check_token::<Ristretto255>();
+    }
+}
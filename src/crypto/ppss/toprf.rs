@@ -0,0 +1,537 @@
+//! Threshold OPRF variant of the PPSS scheme, as adapted in https://eprint.iacr.org/2024/887.
+//! Rather than each keyper evaluating under an independent key, with the client secret-sharing
+//! its data key across the keypers, the keypers hold a (t, n) sharing of a single OPRF key k,
+//! produced by the distributed key generation in `dkg`. The client combines any t of the keypers'
+//! answers in the exponent, with Lagrange coefficients, into the evaluation under k, from which
+//! it derives its data key; the ciphertext holds no shares at all.
+//!
+//! Since k is the same for every client, the PRF takes the client id as a public input, as the
+//! partially oblivious PRF of Pythia (https://eprint.iacr.org/2015/644) does: it is
+//! F_k(id, pw) = e(H1(id), H2(pw))^k, with the client id hashed to G1 and the password to G2.
+//! Keyper i answers the blinded request H2(pw)^r with e(H1(id), H2(pw)^r)^(k_i), along with a
+//! proof against its verification key g^(k_i). An answer obtained under one client id is of no
+//! use under another, as relating the two takes the discrete log between their hashes, so the
+//! keypers' per-client guess limits bound the guesses against each vault.
+//!
+//! Besides the keypers' verification keys, the ciphertext pins the joint key g^k, so that
+//! vaults outlive the proactive refreshes in `dkg`: once the keypers have moved to a new epoch,
+//! the keys they advertise are checked to be shares of the pinned joint key instead.
+
+#![allow(dead_code)]
+
+use ark_crypto_primitives::Error;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::{ops::*, rand::Rng, UniformRand, Zero};
+use ark_bls12_381::{Bls12_381, G1Projective, G2Affine, Fr};
+use ark_ec::{pairing::{Pairing, PairingOutput}, Group};
+use ark_ff::Field;
+
+use super::{PpssPcheme, Reconstruction};
+use super::dkg::KeyShare;
+use super::dleq::{self, DleqProof};
+use super::lagrange::{DuplicateCoordinateError, Interpolator, interpolate_in_exponent};
+use super::jkkx16::{
+    CheckedResponses, JKKX16Error, Parameters, PinnedKey, SecretKey, JKKX16, check_responses, fr_to_32bytes,
+    hash_to_fr,
+};
+use super::suite::hash_to_curve_xmd_sha256;
+
+/// The target group of the pairing, in which the PRF takes its values.
+type Gt = PairingOutput<Bls12_381>;
+
+const DST_CLIENT_ID: &str = "BEDROCK-V01-TOPRF-with-BLS12381G1_XMD:SHA-256_SSWU_RO_";
+const DST_PASSWORD: &str = "BEDROCK-V01-TOPRF-with-BLS12381G2_XMD:SHA-256_SSWU_RO_";
+
+pub struct ThresholdOprf;
+
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PrfInput {
+    /// H2(pw)^r
+    pub blinded_prf_input: G2Affine,
+    pub client_id: Vec<u8>,
+    /// version of the key share to evaluate with; the keyper's current one if unset
+    pub key_version: Option<u32>,
+}
+
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PrfOutput {
+    pub key_version: u32,
+    /// the keyper's verification key g^(k_i)
+    pub public_key: G1Projective,
+    /// e(H1(id), H2(pw)^r)^(k_i)
+    pub blinded_prf_output: Gt,
+    pub proof: DleqProof<G1Projective>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ClientState {
+    blind_scalar: Fr,
+    /// e(H1(id), H2(pw)^r), against which the keypers' proofs are verified
+    tweaked_input: Gt,
+    password: Vec<u8>,
+}
+
+/// A keyper's share of the joint OPRF key, together with the version under which it is used;
+/// each version is the outcome of a separate run of the distributed key generation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerKeyShare {
+    pub version: u32,
+    pub key_share: KeyShare<G1Projective>,
+}
+
+impl ServerKeyShare {
+    pub fn new(version: u32, key_share: KeyShare<G1Projective>) -> Self {
+        ServerKeyShare { version, key_share }
+    }
+}
+
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Ciphertext<C: Group> {
+    /// verification keys of the keypers at keygen, tagged with the keyper index
+    server_keys: Vec<PinnedKey<C>>,
+    /// number of keyper responses needed to reconstruct
+    threshold: usize,
+    /// the joint key g^k, which the keypers' shares combine to in every epoch
+    joint_key: C,
+    hash: C::ScalarField,
+}
+
+impl<C: Group> Ciphertext<C> {
+    /// Returns the key pinned for the keyper with the given index.
+    pub fn server_key(&self, server: usize) -> Option<&PinnedKey<C>> {
+        self.server_keys
+            .iter()
+            .find(|pinned| pinned.server == server)
+    }
+
+    /// Returns the number of keypers holding shares of the OPRF key.
+    pub fn num_servers(&self) -> usize {
+        self.server_keys.len()
+    }
+
+    /// Returns the number of keyper responses needed to reconstruct.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Returns the joint key of the keypers.
+    pub fn joint_key(&self) -> &C {
+        &self.joint_key
+    }
+}
+
+/// Continues the numbering of the domain separators in `jkkx16`; 4 is retired, lest
+/// hashes of older versions be taken for the ones below.
+#[allow(clippy::enum_variant_names)]
+enum HashDomainSeparator {
+    DataKeyDerivation = 5,
+    ReconstructionCheckDerivation = 6,
+}
+
+impl PpssPcheme for ThresholdOprf
+{
+    type Parameters = Parameters<G1Projective>;
+    type ServerKey = ServerKeyShare;
+    type SecretKey = SecretKey;
+    type PrfInput = PrfInput;
+    type PrfOutput = PrfOutput;
+    type Ciphertext = Ciphertext<G1Projective>;
+    type ClientState = ClientState;
+
+    fn setup<R: Rng>(rng: &mut R) -> Result<Self::Parameters, Error> {
+        JKKX16::setup(rng)
+    }
+
+    fn client_generate_keygen_request<R: Rng>(
+        _pp: &Self::Parameters,
+        client_id: &[u8],
+        password: &[u8],
+        rng: &mut R,
+    ) -> Result<(Self::ClientState, Self::PrfInput), Error> {
+        Ok(blind_password(client_id, password, rng)?)
+    }
+
+    fn server_process_keygen_request(
+        pp: &Self::Parameters,
+        key: &ServerKeyShare,
+        client_id: &[u8],
+        input: &Self::PrfInput,
+    ) -> Result<Self::PrfOutput, Error> {
+        Ok(evaluate_prf(pp, key, client_id, input)?)
+    }
+
+    /// Expects the responses of all keypers, in the order of their indices, so that all
+    /// their keys are pinned; any threshold number of them determines the OPRF output.
+    fn client_keygen<R: Rng>(
+        pp: &Self::Parameters,
+        state: &Self::ClientState,
+        server_responses: &[Self::PrfOutput],
+        num_servers: usize,
+        threshold: usize,
+        _rng: &mut R,
+    ) -> Result<(Self::SecretKey, Self::Ciphertext), Error> {
+        if server_responses.len() != num_servers {
+            return Err(JKKX16Error::NotEnoughResponsesError(server_responses.len(), num_servers).into());
+        }
+        if threshold == 0 || threshold > num_servers {
+            return Err(JKKX16Error::InvalidThresholdError(threshold, num_servers).into());
+        }
+
+        let mut evaluations = Vec::new();
+        let mut server_keys = Vec::new();
+        for (i, server_output) in server_responses.iter().enumerate() {
+            evaluations.push((i, unblind_prf_output(pp, state, i, server_output)?));
+            server_keys.push(PinnedKey {
+                server: i,
+                key_version: server_output.key_version,
+                public_key: server_output.public_key,
+            });
+        }
+
+        // the keypers must hold shares of one key: the first t verification keys
+        // determine the others, which would not be the case for independent keys
+        let points: Vec<(Fr, G1Projective)> = server_keys[..threshold].iter()
+            .map(|pinned| (share_id(pinned.server), pinned.public_key))
+            .collect();
+        for pinned in server_keys[threshold..].iter() {
+            let expected = interpolate_in_exponent::<G1Projective>(&points, &share_id(pinned.server))
+                .map_err(|DuplicateCoordinateError(j)| JKKX16Error::DuplicateResponseError(server_keys[j].server))?;
+            if expected != pinned.public_key {
+                return Err(JKKX16Error::KeyMismatchError(pinned.server).into());
+            }
+        }
+
+        let joint_key = interpolate_in_exponent::<G1Projective>(&points, &Fr::zero())
+            .map_err(|DuplicateCoordinateError(j)| JKKX16Error::DuplicateResponseError(server_keys[j].server))?;
+        let mut ciphertext = Ciphertext { server_keys, threshold, joint_key, hash: Fr::zero() };
+        let (key, hash) = derive_key(state, &evaluations[..threshold], &ciphertext)?;
+        ciphertext.hash = hash;
+
+        Ok((key, ciphertext))
+    }
+
+    fn client_generate_reconstruct_request<R: Rng>(
+        _pp: &Self::Parameters,
+        client_id: &[u8],
+        password: &[u8],
+        rng: &mut R
+    ) -> Result<(Self::ClientState, Self::PrfInput), Error> {
+        Ok(blind_password(client_id, password, rng)?)
+    }
+
+    fn server_process_reconstruct_request(
+        pp: &Self::Parameters,
+        key: &ServerKeyShare,
+        client_id: &[u8],
+        input: &Self::PrfInput,
+    ) -> Result<Self::PrfOutput, Error> {
+        Ok(evaluate_prf(pp, key, client_id, input)?)
+    }
+
+    fn client_reconstruct(
+        pp: &Self::Parameters,
+        state: &Self::ClientState,
+        server_responses: &[(usize, Self::PrfOutput)],
+        ciphertext: &Self::Ciphertext,
+    ) -> Result<Reconstruction<Self::SecretKey>, Error> {
+        let CheckedResponses { passed: evaluations, responded, .. } = check_responses(server_responses, false, |i, server_output| {
+            check_response(pp, state, ciphertext, i, server_output)
+        })?;
+
+        if evaluations.len() < ciphertext.threshold {
+            return Err(JKKX16Error::NotEnoughResponsesError(evaluations.len(), ciphertext.threshold).into());
+        }
+        let first = (0..ciphertext.threshold).collect();
+        let keys: Vec<(usize, G1Projective)> = evaluations.iter().map(|(i, (key, _))| (*i, *key)).collect();
+        match inconsistent_keys(ciphertext, &keys, std::iter::once(first))? {
+            Some(inconsistent) if inconsistent.is_empty() => (),
+            Some(inconsistent) => return Err(JKKX16Error::KeyMismatchError(inconsistent[0]).into()),
+            // with only a threshold number of keys, the bad one cannot be told apart
+            None => return Err(JKKX16Error::KeyMismatchError(keys[0].0).into()),
+        }
+
+        let evaluations: Vec<(usize, Gt)> = evaluations.iter()
+            .map(|(i, (_, evaluation))| (*i, *evaluation))
+            .collect();
+        reconstruct_from_evaluations(state, &evaluations, &responded, vec![], ciphertext)
+    }
+
+    /// Every response is verified against the key the keyper advertises on its own, and
+    /// those keys against the ones pinned at keygen, or, after a refresh, against the
+    /// joint key; the bad responses are excluded, and any threshold number of good ones suffice.
+    fn client_reconstruct_robust(
+        pp: &Self::Parameters,
+        state: &Self::ClientState,
+        server_responses: &[(usize, Self::PrfOutput)],
+        ciphertext: &Self::Ciphertext,
+    ) -> Result<Reconstruction<Self::SecretKey>, Error> {
+        let CheckedResponses { passed: evaluations, responded, failed: mut inconsistent_servers } =
+            check_responses(server_responses, true, |i, server_output| {
+                check_response(pp, state, ciphertext, i, server_output)
+            })?;
+
+        // if no threshold number of keys are consistent, all of them are suspect
+        let keys: Vec<(usize, G1Projective)> = evaluations.iter().map(|(i, (key, _))| (*i, *key)).collect();
+        let candidates = Subsets::new(keys.len(), ciphertext.threshold);
+        let inconsistent_keys = inconsistent_keys(ciphertext, &keys, candidates)?
+            .unwrap_or_else(|| keys.iter().map(|(i, _)| *i).collect());
+        inconsistent_servers.extend(inconsistent_keys.iter());
+        inconsistent_servers.sort();
+
+        let evaluations: Vec<(usize, Gt)> = evaluations.iter()
+            .filter(|(i, _)| !inconsistent_keys.contains(i))
+            .map(|(i, (_, evaluation))| (*i, *evaluation))
+            .collect();
+
+        reconstruct_from_evaluations(state, &evaluations, &responded, inconsistent_servers, ciphertext)
+    }
+}
+
+/// Keyper i holds the evaluation of the shared polynomial at i + 1.
+fn share_id(server: usize) -> Fr {
+    Fr::from((server + 1) as u64)
+}
+
+/// Hashes the client id to G1 and blinds the hash of the password in G2.
+fn blind_password<R: Rng>(
+    client_id: &[u8],
+    password: &[u8],
+    rng: &mut R,
+) -> Result<(ClientState, PrfInput), JKKX16Error> {
+    let id_hash = hash_to_curve_xmd_sha256::<ark_bls12_381::g1::Config>(client_id, DST_CLIENT_ID.as_bytes())
+        .map_err(JKKX16Error::HashingError)?;
+    let password_hash = hash_to_curve_xmd_sha256::<ark_bls12_381::g2::Config>(password, DST_PASSWORD.as_bytes())
+        .map_err(JKKX16Error::HashingError)?;
+
+    // sample a non-zero random scalar
+    let mut blind = Fr::zero();
+    while blind.is_zero() {
+        blind = Fr::rand(rng);
+    }
+    let blinded_prf_input: G2Affine = password_hash.mul(blind).into();
+
+    let state = ClientState {
+        blind_scalar: blind,
+        tweaked_input: Bls12_381::pairing(id_hash, blinded_prf_input),
+        password: password.to_vec(),
+    };
+    let input = PrfInput { blinded_prf_input, client_id: client_id.to_vec(), key_version: None };
+
+    Ok((state, input))
+}
+
+/// Verifies the keyper's proof against the key it advertises, and strips the client's
+/// blind, yielding the keyper's share e(H1(id), H2(pw))^(k_i) of the PRF output.
+fn unblind_prf_output(
+    pp: &Parameters<G1Projective>,
+    state: &ClientState,
+    server: usize,
+    server_output: &PrfOutput,
+) -> Result<Gt, JKKX16Error> {
+    let valid = dleq::verify_in_target::<Bls12_381>(
+        &pp.generator,
+        &server_output.public_key,
+        &state.tweaked_input,
+        &server_output.blinded_prf_output,
+        &server_output.proof,
+    ).map_err(JKKX16Error::SerializationError)?;
+
+    if !valid {
+        return Err(JKKX16Error::InvalidProofError(server));
+    }
+
+    Ok(server_output.blinded_prf_output * state.blind_scalar.inverse().expect("blind should not be zero"))
+}
+
+/// Checks that the keyper with index `server` was pinned under the version it answers with,
+/// and strips the client's blind, after verifying the proof against the key the keyper
+/// advertises; yields that key along with the keyper's share of the PRF output.
+fn check_response(
+    pp: &Parameters<G1Projective>,
+    state: &ClientState,
+    ciphertext: &Ciphertext<G1Projective>,
+    server: usize,
+    server_output: &PrfOutput,
+) -> Result<(G1Projective, Gt), JKKX16Error> {
+    let pinned = ciphertext.server_key(server).ok_or(JKKX16Error::KeyMismatchError(server))?;
+    if pinned.key_version != server_output.key_version {
+        return Err(JKKX16Error::KeyMismatchError(server));
+    }
+
+    Ok((server_output.public_key, unblind_prf_output(pp, state, server, server_output)?))
+}
+
+/// Bounds the number of candidate subsets of keys that are tried against the joint key,
+/// which grows as n choose t with the number of keypers that respond.
+const MAX_CANDIDATES: usize = 256;
+
+/// Returns the indices of the keypers whose advertised keys are inconsistent with the joint key.
+/// As long as a threshold number of keys are the pinned ones, those determine the sharing;
+/// otherwise, the keypers have refreshed their shares, and the first candidate subset of keys
+/// (given by positions in `keys`) that combines to the joint key determines it instead.
+/// Returns None if no candidate does, and fails if more than `MAX_CANDIDATES` would be tried.
+fn inconsistent_keys(
+    ciphertext: &Ciphertext<G1Projective>,
+    keys: &[(usize, G1Projective)],
+    candidates: impl Iterator<Item = Vec<usize>>,
+) -> Result<Option<Vec<usize>>, JKKX16Error> {
+    let is_pinned = |(i, key): &(usize, G1Projective)| {
+        ciphertext.server_key(*i).is_some_and(|pinned| pinned.public_key == *key)
+    };
+    if keys.iter().filter(|key| is_pinned(key)).count() >= ciphertext.threshold {
+        return Ok(Some(keys.iter().filter(|key| !is_pinned(key)).map(|(i, _)| *i).collect()));
+    }
+
+    for (tried, subset) in candidates.enumerate() {
+        if tried == MAX_CANDIDATES {
+            return Err(JKKX16Error::SearchLimitError(tried));
+        }
+
+        let (xs, ys): (Vec<Fr>, Vec<G1Projective>) = subset.iter()
+            .map(|&j| (share_id(keys[j].0), keys[j].1))
+            .unzip();
+        let Ok(interpolator) = Interpolator::new(&xs) else {
+            continue;
+        };
+        let key_at = |x: &Fr| interpolator.interpolate_in_exponent::<G1Projective>(&ys, x);
+        if key_at(&Fr::zero()) != ciphertext.joint_key {
+            continue;
+        }
+
+        return Ok(Some(keys.iter()
+            .filter(|(i, key)| key_at(&share_id(*i)) != *key)
+            .map(|(i, _)| *i)
+            .collect()));
+    }
+
+    Ok(None)
+}
+
+/// Enumerates the subsets of size `k` of {0, ..., n-1} one at a time, in lexicographic order.
+struct Subsets {
+    n: usize,
+    next: Option<Vec<usize>>,
+}
+
+impl Subsets {
+    fn new(n: usize, k: usize) -> Self {
+        Subsets { n, next: (k <= n).then(|| (0..k).collect()) }
+    }
+}
+
+impl Iterator for Subsets {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        let subset = self.next.take()?;
+        let k = subset.len();
+
+        // advance the rightmost position that still can, and reset the ones after it
+        if let Some(pos) = (0..k).rev().find(|&pos| subset[pos] < self.n - k + pos) {
+            let mut next = subset.clone();
+            next[pos] += 1;
+            for i in (pos + 1)..k {
+                next[i] = next[i - 1] + 1;
+            }
+            self.next = Some(next);
+        }
+
+        Some(subset)
+    }
+}
+
+fn reconstruct_from_evaluations(
+    state: &ClientState,
+    evaluations: &[(usize, Gt)],
+    responded: &[usize],
+    inconsistent_servers: Vec<usize>,
+    ciphertext: &Ciphertext<G1Projective>,
+) -> Result<Reconstruction<SecretKey>, Error> {
+    if evaluations.len() < ciphertext.threshold {
+        return Err(JKKX16Error::NotEnoughResponsesError(evaluations.len(), ciphertext.threshold).into());
+    }
+
+    let (key, hash) = derive_key(state, &evaluations[..ciphertext.threshold], ciphertext)?;
+    if hash != ciphertext.hash {
+        return Err(JKKX16Error::InvalidPinError.into());
+    }
+
+    let missing_servers = ciphertext.server_keys.iter()
+        .map(|pinned| pinned.server)
+        .filter(|i| !responded.contains(i))
+        .collect();
+
+    Ok(Reconstruction { key, missing_servers, inconsistent_servers })
+}
+
+/// Combines a threshold number of evaluations, each given with the index of its keyper,
+/// in the exponent into e(H1(id), H2(pw))^k, and derives
+/// the data key along with the check value that lets the client detect a wrong password.
+fn derive_key(
+    state: &ClientState,
+    evaluations: &[(usize, Gt)],
+    ciphertext: &Ciphertext<G1Projective>,
+) -> Result<(SecretKey, Fr), JKKX16Error> {
+    let (xs, ys): (Vec<Fr>, Vec<Gt>) = evaluations.iter()
+        .map(|(i, evaluation)| (share_id(*i), *evaluation))
+        .unzip();
+    let interpolator = Interpolator::new(&xs)
+        .map_err(|DuplicateCoordinateError(j)| JKKX16Error::DuplicateResponseError(evaluations[j].0))?;
+    let prf_output = interpolator.interpolate_in_exponent::<Gt>(&ys, &Fr::zero());
+
+    let mut prf_output_bytes = Vec::new();
+    prf_output.serialize_compressed(&mut prf_output_bytes).map_err(JKKX16Error::SerializationError)?;
+
+    // H(pw, F_k(id, pw)) in the design doc
+    let hashed_output = fr_to_32bytes(
+        hash_to_fr::<G1Projective>(
+            HashDomainSeparator::DataKeyDerivation as u8,
+            &[],
+            &[],
+            &[state.password.clone(), prf_output_bytes]
+        ).map_err(JKKX16Error::SerializationError)?
+    );
+    let mut r = [0u8; 16]; r.copy_from_slice(&hashed_output[0..16]);
+    let mut key = [0u8; 16]; key.copy_from_slice(&hashed_output[16..32]);
+
+    let pks: Vec<G1Projective> = ciphertext.server_keys.iter().map(|pinned| pinned.public_key).collect();
+    let hash = hash_to_fr::<G1Projective>(
+        HashDomainSeparator::ReconstructionCheckDerivation as u8,
+        &pks,
+        &[],
+        &[state.password.to_vec(), r.to_vec()]
+    ).map_err(JKKX16Error::SerializationError)?;
+
+    Ok((key, hash))
+}
+
+/// Evaluates the keyper's share of the PRF on the blinded password, under the client id
+/// the request is limited under: e(H1(id), H2(pw)^r)^(k_i).
+fn evaluate_prf(
+    pp: &Parameters<G1Projective>,
+    key: &ServerKeyShare,
+    client_id: &[u8],
+    input: &PrfInput,
+) -> Result<PrfOutput, JKKX16Error> {
+    if let Some(version) = input.key_version {
+        if version != key.version {
+            return Err(JKKX16Error::UnknownKeyVersionError(version));
+        }
+    }
+
+    let share = &key.key_share.secret_share;
+    let id_hash = hash_to_curve_xmd_sha256::<ark_bls12_381::g1::Config>(client_id, DST_CLIENT_ID.as_bytes())
+        .map_err(JKKX16Error::HashingError)?;
+    let tweaked_input = Bls12_381::pairing(id_hash, input.blinded_prf_input);
+
+    // prove that the evaluation used the share behind the keyper's verification key
+    let proof = dleq::prove_in_target::<Bls12_381>(&pp.generator, &tweaked_input, share)
+        .map_err(JKKX16Error::SerializationError)?;
+
+    Ok(PrfOutput {
+        key_version: key.version,
+        public_key: pp.generator.mul(share),
+        blinded_prf_output: tweaked_input * share,
+        proof,
+    })
+}
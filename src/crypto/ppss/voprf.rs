@@ -0,0 +1,413 @@
+//! The PPSS construction of `jkkx16`, with the VOPRF of RFC 9497 as its OPRF layer in place
+//! of our own 2HashDH variant. Keypers derive their per-client key pair with `DeriveKeyPair`
+//! from their seed, and evaluate and prove as the RFC prescribes; the client masks each
+//! keyper's share with a hash of that keyper's VOPRF output, as in `jkkx16`.
+//!
+//! Only the group operations, proofs and finalization follow the RFC: the elements travel in
+//! their RFC encodings, but wrapped in our own `PrfInput` and `PrfOutput`, and the scheme is
+//! not served by the keyper nor selectable by vaults, which use `jkkx16` over any `CurveSuite`.
+//!
+//! Shares live in the scalar field of P-256 whatever the suite, since the secret sharing
+//! is independent of the group the OPRF is evaluated in.
+
+#![allow(dead_code)]
+
+use ark_crypto_primitives::Error;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::{marker::PhantomData, rand::Rng};
+use ark_ff::UniformRand;
+
+use super::{PpssPcheme, Reconstruction, ServerKey};
+use super::jkkx16::{
+    CheckedResponses, JKKX16Error, SecretKey, check_responses, fr_to_32bytes, hash_to_fr, missing_servers,
+    recover_shares,
+};
+use super::rfc9497::{self, Element, Mode, Proof, Scalar, Suite};
+use super::sss;
+
+/// Field of the shares, with the curve whose scalar field it is, for `hash_to_fr`.
+type Fr = ark_secp256r1::Fr;
+type ShareCurve = ark_secp256r1::Projective;
+
+/// The suite fixes all parameters of the scheme.
+#[derive(Clone, Debug)]
+pub struct Parameters;
+
+/// The scheme over the RFC 9497 suite `S`, in VOPRF mode.
+pub struct VoprfScheme<S: Suite> {
+    _suite: PhantomData<S>,
+}
+
+/// Carries the blinded element, encoded as in the RFC.
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PrfInput {
+    pub blinded_element: Vec<u8>,
+    pub client_id: Vec<u8>,
+    /// version of the server key to evaluate with; the server's current key if unset
+    pub key_version: Option<u32>,
+}
+
+/// Carries the keyper's public key, evaluated element and proof, encoded as in the RFC.
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PrfOutput {
+    pub key_version: u32,
+    pub public_key: Vec<u8>,
+    pub evaluated_element: Vec<u8>,
+    pub proof: Vec<u8>,
+}
+
+/// The key a keyper used at keygen, as pinned in the ciphertext.
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct PinnedKey {
+    /// index of the keyper, which is also the position of its share
+    pub server: usize,
+    /// version of the keyper's key, which the keyper needs to re-derive it
+    pub key_version: u32,
+    pub public_key: Vec<u8>,
+}
+
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Ciphertext {
+    encrypted_shares: Vec<(Fr, Fr)>,
+    /// keys of the keypers at keygen, tagged with the keyper index
+    server_keys: Vec<PinnedKey>,
+    /// number of keyper responses needed to reconstruct
+    threshold: usize,
+    hash: Fr,
+}
+
+impl Ciphertext {
+    /// Returns the key pinned for the keyper with the given index.
+    pub fn server_key(&self, server: usize) -> Option<&PinnedKey> {
+        self.server_keys
+            .iter()
+            .find(|pinned| pinned.server == server)
+    }
+
+    /// Returns the number of keypers the shares were split across.
+    pub fn num_servers(&self) -> usize {
+        self.encrypted_shares.len()
+    }
+
+    /// Returns the number of keyper responses needed to reconstruct.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ClientState<S: Suite> {
+    pub blind: Scalar<S>,
+    pub blinded_element: Element<S>,
+    pub client_id: Vec<u8>,
+    pub password: Vec<u8>,
+}
+
+/// Continues the numbering of the domain separators in `jkkx16` and `toprf`.
+#[allow(clippy::enum_variant_names)]
+enum HashDomainSeparator {
+    MaskDerivation = 7,
+    DataKeyDerivation = 8,
+    ReconstructionCheckDerivation = 9,
+}
+
+impl<S: Suite> PpssPcheme for VoprfScheme<S>
+{
+    type Parameters = Parameters;
+    type ServerKey = ServerKey;
+    type SecretKey = SecretKey;
+    type PrfInput = PrfInput;
+    type PrfOutput = PrfOutput;
+    type Ciphertext = Ciphertext;
+    type ClientState = ClientState<S>;
+
+    fn setup<R: Rng>(_rng: &mut R) -> Result<Self::Parameters, Error> {
+        Ok(Parameters)
+    }
+
+    fn client_generate_keygen_request<R: Rng>(
+        _pp: &Self::Parameters,
+        client_id: &[u8],
+        password: &[u8],
+        rng: &mut R,
+    ) -> Result<(Self::ClientState, Self::PrfInput), Error> {
+        Ok(blind_password::<S, R>(client_id, password, rng)?)
+    }
+
+    fn server_process_keygen_request(
+        _pp: &Self::Parameters,
+        key: &ServerKey,
+        client_id: &[u8],
+        input: &Self::PrfInput,
+    ) -> Result<Self::PrfOutput, Error> {
+        Ok(evaluate_prf::<S>(key, client_id, input)?)
+    }
+
+    fn client_keygen<R: Rng>(
+        _pp: &Self::Parameters,
+        state: &Self::ClientState,
+        server_responses: &[Self::PrfOutput],
+        num_servers: usize,
+        threshold: usize,
+        rng: &mut R,
+    ) -> Result<(Self::SecretKey, Self::Ciphertext), Error> {
+        if server_responses.len() != num_servers {
+            return Err(JKKX16Error::NotEnoughResponsesError(server_responses.len(), num_servers).into());
+        }
+        let secret = Fr::rand(rng);
+        let shares = sss::share(secret, threshold, num_servers, rng)?;
+
+        let mut encrypted_shares = Vec::new();
+        let mut server_keys = Vec::new();
+        for (i, server_output) in server_responses.iter().enumerate() {
+            let mask_i = share_mask::<S>(state, i, server_output)?;
+
+            encrypted_shares.push((shares[i].0, shares[i].1 + mask_i));
+            server_keys.push(PinnedKey {
+                server: i,
+                key_version: server_output.key_version,
+                public_key: server_output.public_key.clone(),
+            });
+        }
+
+        let (r, key) = derive_data_key(&secret)?;
+
+        let ys: Vec<Fr> = encrypted_shares.iter().map(|(_, y)| *y)
+            .chain(shares.iter().map(|(_, y)| *y))
+            .collect();
+        let hash = reconstruction_check(&server_keys, &ys, &state.password, &r)?;
+
+        Ok((key, Ciphertext { encrypted_shares, server_keys, threshold, hash }))
+    }
+
+    fn client_generate_reconstruct_request<R: Rng>(
+        _pp: &Self::Parameters,
+        client_id: &[u8],
+        password: &[u8],
+        rng: &mut R
+    ) -> Result<(Self::ClientState, Self::PrfInput), Error> {
+        Ok(blind_password::<S, R>(client_id, password, rng)?)
+    }
+
+    fn server_process_reconstruct_request(
+        _pp: &Self::Parameters,
+        key: &ServerKey,
+        client_id: &[u8],
+        input: &Self::PrfInput,
+    ) -> Result<Self::PrfOutput, Error> {
+        Ok(evaluate_prf::<S>(key, client_id, input)?)
+    }
+
+    fn client_reconstruct(
+        _pp: &Self::Parameters,
+        state: &Self::ClientState,
+        server_responses: &[(usize, Self::PrfOutput)],
+        ciphertext: &Self::Ciphertext,
+    ) -> Result<Reconstruction<Self::SecretKey>, Error> {
+        let CheckedResponses { passed: shares, responded, .. } = check_responses(server_responses, false, |i, server_output| {
+            decrypt_share::<S>(state, ciphertext, i, server_output)
+        })?;
+
+        if shares.len() < ciphertext.threshold {
+            return Err(JKKX16Error::NotEnoughResponsesError(shares.len(), ciphertext.threshold).into());
+        }
+
+        let key = reconstruct_from_shares(state, &shares[..ciphertext.threshold], ciphertext)?;
+
+        Ok(Reconstruction {
+            key,
+            missing_servers: missing_servers(ciphertext.num_servers(), &responded),
+            inconsistent_servers: vec![],
+        })
+    }
+
+    fn client_reconstruct_robust(
+        _pp: &Self::Parameters,
+        state: &Self::ClientState,
+        server_responses: &[(usize, Self::PrfOutput)],
+        ciphertext: &Self::Ciphertext,
+    ) -> Result<Reconstruction<Self::SecretKey>, Error> {
+
+        // responses that fail the key or proof checks are excluded up front
+        let CheckedResponses { passed: shares, responded, failed: mut inconsistent_servers } =
+            check_responses(server_responses, true, |i, server_output| {
+                decrypt_share::<S>(state, ciphertext, i, server_output)
+            })?;
+
+        if shares.len() < ciphertext.threshold {
+            return Err(JKKX16Error::NotEnoughResponsesError(shares.len(), ciphertext.threshold).into());
+        }
+
+        // the proofs tie each remaining share to its keyper's pinned key, so the shares
+        // are all right or, with a wrong pin, all wrong: the first threshold ones are
+        // interpolated once, and any beyond not on their polynomial is inconsistent
+        let (first, rest) = shares.split_at(ciphertext.threshold);
+        let key = reconstruct_from_shares(state, first, ciphertext)?;
+
+        let xs: Vec<Fr> = rest.iter().map(|(_, (x, _))| *x).collect();
+        let (_, expected) = recover_shares(first, &xs)?;
+        inconsistent_servers.extend(rest.iter()
+            .zip(expected)
+            .filter(|((_, (_, y)), expected)| expected != y)
+            .map(|((i, _), _)| *i));
+        inconsistent_servers.sort();
+
+        Ok(Reconstruction {
+            key,
+            missing_servers: missing_servers(ciphertext.num_servers(), &responded),
+            inconsistent_servers,
+        })
+    }
+}
+
+/// Blinds the password, which is the input of the VOPRF.
+fn blind_password<S: Suite, R: Rng>(
+    client_id: &[u8],
+    password: &[u8],
+    rng: &mut R,
+) -> Result<(ClientState<S>, PrfInput), JKKX16Error> {
+    let blind = rfc9497::random_scalar::<S, R>(rng);
+    let blinded_element = rfc9497::blind::<S>(Mode::Voprf, password, &blind)?;
+
+    let input = PrfInput {
+        blinded_element: rfc9497::serialize_element::<S>(&blinded_element),
+        client_id: client_id.to_vec(),
+        key_version: None,
+    };
+    let state = ClientState {
+        blind,
+        blinded_element,
+        client_id: client_id.to_vec(),
+        password: password.to_vec(),
+    };
+
+    Ok((state, input))
+}
+
+/// Evaluates the VOPRF under the per-client key pair, which is derived as
+/// DeriveKeyPair(seed, version || client_id), and proves the evaluation.
+fn evaluate_prf<S: Suite>(
+    key: &ServerKey,
+    client_id: &[u8],
+    input: &PrfInput,
+) -> Result<PrfOutput, JKKX16Error> {
+    if let Some(version) = input.key_version {
+        if version != key.version {
+            return Err(JKKX16Error::UnknownKeyVersionError(version));
+        }
+    }
+
+    // the version has fixed length, so the info string is unambiguous
+    let mut info = key.version.to_be_bytes().to_vec();
+    info.extend_from_slice(client_id);
+    let (secret_key, public_key) = rfc9497::derive_key_pair::<S>(Mode::Voprf, &key.seed, &info)?;
+
+    let blinded_element = rfc9497::deserialize_element::<S>(&input.blinded_element)?;
+    let evaluated_element = rfc9497::blind_evaluate::<S>(&secret_key, &blinded_element);
+
+    // the proof's nonce is derived deterministically, as in `dleq`, so that keypers
+    // do not need a source of randomness; verifiers cannot tell the difference
+    let mut nonce_input = rfc9497::serialize_scalar::<S>(&secret_key);
+    nonce_input.extend(&input.blinded_element);
+    let r = S::hash_to_scalar(&nonce_input, b"bedrock-voprf-proof-nonce")?;
+
+    let proof = rfc9497::generate_proof::<S>(
+        Mode::Voprf, &secret_key, &public_key, &[blinded_element], &[evaluated_element], &r
+    )?;
+
+    Ok(PrfOutput {
+        key_version: key.version,
+        public_key: rfc9497::serialize_element::<S>(&public_key),
+        evaluated_element: rfc9497::serialize_element::<S>(&evaluated_element),
+        proof: proof.to_bytes(),
+    })
+}
+
+/// Verifies the response of the keyper at position `server`, and derives the mask of its
+/// share from its VOPRF output, which the RFC already binds to the password.
+fn share_mask<S: Suite>(
+    state: &ClientState<S>,
+    server: usize,
+    server_output: &PrfOutput,
+) -> Result<Fr, JKKX16Error> {
+    let public_key = rfc9497::deserialize_element::<S>(&server_output.public_key)?;
+    let evaluated_element = rfc9497::deserialize_element::<S>(&server_output.evaluated_element)?;
+    let proof = Proof::<S>::from_bytes(&server_output.proof)?;
+
+    let valid = rfc9497::verify_proof::<S>(
+        Mode::Voprf, &public_key, &[state.blinded_element], &[evaluated_element], &proof
+    )?;
+    if !valid {
+        return Err(JKKX16Error::InvalidProofError(server));
+    }
+
+    let prf_output = rfc9497::finalize::<S>(&state.password, &state.blind, &evaluated_element);
+
+    hash_to_fr::<ShareCurve>(HashDomainSeparator::MaskDerivation as u8, &[], &[], &[prf_output])
+        .map_err(JKKX16Error::SerializationError)
+}
+
+/// Checks the response of the keyper with index `server` against its pinned key,
+/// and uses its VOPRF output to decrypt the keyper's share.
+fn decrypt_share<S: Suite>(
+    state: &ClientState<S>,
+    ciphertext: &Ciphertext,
+    server: usize,
+    server_output: &PrfOutput,
+) -> Result<(Fr, Fr), JKKX16Error> {
+    // the keyper must still hold the key it used at keygen
+    let pinned = ciphertext.server_key(server).ok_or(JKKX16Error::KeyMismatchError(server))?;
+    if pinned.public_key != server_output.public_key || pinned.key_version != server_output.key_version {
+        return Err(JKKX16Error::KeyMismatchError(server));
+    }
+
+    let mask = share_mask::<S>(state, server, server_output)?;
+
+    let (x, y) = *ciphertext.encrypted_shares.get(server).ok_or(JKKX16Error::InvalidShareError(server))?;
+    Ok((x, y - mask))
+}
+
+/// Splits H(secret) into the value r of the reconstruction check and the data key.
+fn derive_data_key(secret: &Fr) -> Result<([u8; 16], SecretKey), JKKX16Error> {
+    let hashed_secret = fr_to_32bytes(
+        hash_to_fr::<ShareCurve>(HashDomainSeparator::DataKeyDerivation as u8, &[], &[*secret], &[])
+            .map_err(JKKX16Error::SerializationError)?
+    );
+    let mut r = [0u8; 16]; r.copy_from_slice(&hashed_secret[0..16]);
+    let mut key = [0u8; 16]; key.copy_from_slice(&hashed_secret[16..32]);
+
+    Ok((r, key))
+}
+
+/// H(pks, ys, pw, r), binding the pinned keys, the shares and the password.
+fn reconstruction_check(server_keys: &[PinnedKey], ys: &[Fr], password: &[u8], r: &[u8]) -> Result<Fr, JKKX16Error> {
+    let mut bytearray_inputs: Vec<Vec<u8>> = server_keys.iter().map(|pinned| pinned.public_key.clone()).collect();
+    bytearray_inputs.push(password.to_vec());
+    bytearray_inputs.push(r.to_vec());
+
+    hash_to_fr::<ShareCurve>(HashDomainSeparator::ReconstructionCheckDerivation as u8, &[], ys, &bytearray_inputs)
+        .map_err(JKKX16Error::SerializationError)
+}
+
+/// Interpolates a threshold number of decrypted shares, each given with the index of
+/// its keyper, recomputes the shares of all keypers from the interpolated polynomial,
+/// and runs the reconstruction check.
+fn reconstruct_from_shares<S: Suite>(
+    state: &ClientState<S>,
+    shares: &[(usize, (Fr, Fr))],
+    ciphertext: &Ciphertext,
+) -> Result<SecretKey, JKKX16Error> {
+    let xs: Vec<Fr> = ciphertext.encrypted_shares.iter().map(|(x, _)| *x).collect();
+    let (secret, recomputed) = recover_shares(shares, &xs)?;
+    let (r, key) = derive_data_key(&secret)?;
+
+    let ys: Vec<Fr> = ciphertext.encrypted_shares.iter().map(|(_, y)| *y)
+        .chain(recomputed)
+        .collect();
+
+    if reconstruction_check(&ciphertext.server_keys, &ys, &state.password, &r)? == ciphertext.hash {
+        Ok(key)
+    } else {
+        Err(JKKX16Error::InvalidPinError)
+    }
+}
@@ -0,0 +1,509 @@
+//! Schnorr signatures on secp256k1 as specified in BIP-340
+//! (https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki), which Taproot uses.
+//! Unlike `schnorr::Schnorr<Secp256k1>`, whose transcript is our own, these interoperate
+//! with Bitcoin tooling: a public key is the 32-byte x-coordinate of the point with even y,
+//! a secret key the 32-byte big-endian scalar, and a signature the x-coordinate of R
+//! followed by the big-endian scalar s. Challenges and nonces are tagged SHA-256 hashes,
+//! and the nonce is derived from the key, the message and 32 bytes of auxiliary randomness.
+
+use std::fmt;
+
+use ark_crypto_primitives::Error;
+use ark_ec::{AffineRepr, CurveGroup, Group};
+use ark_ff::{BigInteger, PrimeField, UniformRand, Zero};
+use ark_secp256k1::{Affine, Fq, Fr, Projective};
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate, Write,
+};
+use ark_std::rand::Rng;
+use sha2::{Digest, Sha256};
+
+use super::{encoding::read_bytes, SignatureScheme};
+
+const AUX_TAG: &[u8] = b"BIP0340/aux";
+const NONCE_TAG: &[u8] = b"BIP0340/nonce";
+const CHALLENGE_TAG: &[u8] = b"BIP0340/challenge";
+
+pub struct Bip340;
+
+/// The x-coordinate of the public point, which stands for the point with even y.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub struct PublicKey(pub [u8; 32]);
+
+/// The big-endian encoding of the secret scalar.
+#[derive(Clone, Default)]
+pub struct SecretKey(pub [u8; 32]);
+
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct Signature {
+    /// x-coordinate of the commitment R
+    pub r: [u8; 32],
+    /// big-endian encoding of the response s
+    pub s: [u8; 32],
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Bip340Error {
+    /// the secret key is zero or not below the group order
+    InvalidSecretKey,
+    /// the derived nonce is zero, which happens with negligible probability
+    ZeroNonce,
+}
+
+impl std::error::Error for Bip340Error {}
+
+impl fmt::Display for Bip340Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Bip340Error::InvalidSecretKey =>
+                write!(f, "The secret key must be a non-zero scalar."),
+            Bip340Error::ZeroNonce =>
+                write!(f, "The derived nonce is zero."),
+        }
+    }
+}
+
+impl SecretKey {
+    fn scalar(&self) -> Result<Fr, Bip340Error> {
+        match from_canonical_bytes::<Fr>(&self.0) {
+            Some(d) if !d.is_zero() => Ok(d),
+            _ => Err(Bip340Error::InvalidSecretKey),
+        }
+    }
+
+    pub fn public_key(&self) -> Result<PublicKey, Bip340Error> {
+        let point = (Projective::generator() * self.scalar()?).into_affine();
+        Ok(PublicKey(to_bytes(&point.x)))
+    }
+}
+
+#[allow(dead_code)]
+impl Signature {
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.r);
+        bytes[32..].copy_from_slice(&self.s);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        super::encoding::from_bytes(bytes)
+    }
+}
+
+impl Bip340 {
+    /// Signs with the given auxiliary randomness, as in the signing algorithm of BIP-340;
+    /// the nonce is a function of the key, the message and `aux_rand`, so the signature is
+    /// deterministic, while fresh randomness protects against fault attacks.
+    pub fn sign_with_aux_rand(sk: &SecretKey, message: &[u8], aux_rand: &[u8; 32]) -> Result<Signature, Bip340Error> {
+        let secret = sk.scalar()?;
+        let public_point = (Projective::generator() * secret).into_affine();
+        let public_key = to_bytes(&public_point.x);
+        // sign with the secret of the point with even y
+        let secret = if is_even(&public_point.y) { secret } else { -secret };
+
+        // t := bytes(d) xor hash_aux(a), k := hash_nonce(t || bytes(P) || m)
+        let mut t = to_bytes(&secret);
+        for (t, mask) in t.iter_mut().zip(tagged_hash(AUX_TAG, &[aux_rand])) {
+            *t ^= mask;
+        }
+        let nonce = Fr::from_be_bytes_mod_order(&tagged_hash(NONCE_TAG, &[&t, &public_key, message]));
+        if nonce.is_zero() {
+            return Err(Bip340Error::ZeroNonce);
+        }
+
+        let commitment = (Projective::generator() * nonce).into_affine();
+        let nonce = if is_even(&commitment.y) { nonce } else { -nonce };
+        let r = to_bytes(&commitment.x);
+
+        // s := k + ed
+        let e = challenge(&r, &public_key, message);
+        Ok(Signature { r, s: to_bytes(&(nonce + e * secret)) })
+    }
+}
+
+impl SignatureScheme for Bip340 {
+    type Parameters = ();
+    type PublicKey = PublicKey;
+    type SecretKey = SecretKey;
+    type Signature = Signature;
+
+    fn setup<R: Rng>(_rng: &mut R) -> Result<Self::Parameters, Error> {
+        Ok(())
+    }
+
+    fn keygen<R: Rng>(
+        _parameters: &Self::Parameters,
+        rng: &mut R,
+    ) -> Result<(Self::PublicKey, Self::SecretKey), Error> {
+        let secret_key = loop {
+            let secret = Fr::rand(rng);
+            if !secret.is_zero() {
+                break SecretKey(to_bytes(&secret));
+            }
+        };
+        Ok((secret_key.public_key()?, secret_key))
+    }
+
+    /// Signs with auxiliary randomness drawn from the random number generator.
+    fn sign<R: Rng>(
+        _parameters: &Self::Parameters,
+        sk: &Self::SecretKey,
+        message: &[u8],
+        rng: &mut R,
+    ) -> Result<Self::Signature, Error> {
+        Ok(Self::sign_with_aux_rand(sk, message, &rng.gen())?)
+    }
+
+    /// Verifies as in the verification algorithm of BIP-340.
+    fn verify(
+        _parameters: &Self::Parameters,
+        pk: &Self::PublicKey,
+        message: &[u8],
+        signature: &Self::Signature,
+    ) -> Result<bool, Error> {
+        let Some(public_point) = lift_x(&pk.0) else {
+            return Ok(false);
+        };
+        // r must be a field element and s a scalar, lest signatures be malleable
+        if from_canonical_bytes::<Fq>(&signature.r).is_none() {
+            return Ok(false);
+        }
+        let Some(response) = from_canonical_bytes::<Fr>(&signature.s) else {
+            return Ok(false);
+        };
+
+        // R := sG - eP must have even y and x-coordinate r
+        let e = challenge(&signature.r, &pk.0, message);
+        let commitment = (Projective::generator() * response - public_point * e).into_affine();
+        Ok(match commitment.xy() {
+            Some((x, y)) => is_even(y) && to_bytes(x) == signature.r,
+            None => false,
+        })
+    }
+}
+
+/// hash_tag(x) := SHA256(SHA256(tag) || SHA256(tag) || x)
+fn tagged_hash(tag: &[u8], inputs: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag);
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    for input in inputs {
+        hasher.update(input);
+    }
+    hasher.finalize().into()
+}
+
+/// e := hash_challenge(bytes(R) || bytes(P) || m) mod n
+fn challenge(r: &[u8; 32], public_key: &[u8; 32], message: &[u8]) -> Fr {
+    Fr::from_be_bytes_mod_order(&tagged_hash(CHALLENGE_TAG, &[r, public_key, message]))
+}
+
+/// Returns the point with the given x-coordinate and even y, if there is one.
+fn lift_x(x: &[u8; 32]) -> Option<Affine> {
+    let x = from_canonical_bytes::<Fq>(x)?;
+    let point = Affine::get_point_from_x_unchecked(x, false)?;
+    Some(if is_even(&point.y) { point } else { -point })
+}
+
+fn is_even<F: PrimeField>(element: &F) -> bool {
+    element.into_bigint().is_even()
+}
+
+fn to_bytes<F: PrimeField>(element: &F) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&element.into_bigint().to_bytes_be());
+    bytes
+}
+
+/// Decodes a big-endian element, rejecting encodings of integers that are not reduced.
+fn from_canonical_bytes<F: PrimeField>(bytes: &[u8; 32]) -> Option<F> {
+    let element = F::from_be_bytes_mod_order(bytes);
+    (to_bytes(&element) == *bytes).then_some(element)
+}
+
+impl CanonicalSerialize for PublicKey {
+    fn serialize_with_mode<W: Write>(&self, mut writer: W, _compress: Compress) -> Result<(), SerializationError> {
+        Ok(writer.write_all(&self.0)?)
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        32
+    }
+}
+
+impl Valid for PublicKey {
+    fn check(&self) -> Result<(), SerializationError> {
+        lift_x(&self.0).map(|_| ()).ok_or(SerializationError::InvalidData)
+    }
+}
+
+impl CanonicalDeserialize for PublicKey {
+    fn deserialize_with_mode<R: Read>(reader: R, _compress: Compress, validate: Validate) -> Result<Self, SerializationError> {
+        let public_key = PublicKey(read_bytes(reader)?);
+        if let Validate::Yes = validate {
+            public_key.check()?;
+        }
+        Ok(public_key)
+    }
+}
+
+impl CanonicalSerialize for SecretKey {
+    fn serialize_with_mode<W: Write>(&self, mut writer: W, _compress: Compress) -> Result<(), SerializationError> {
+        Ok(writer.write_all(&self.0)?)
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        32
+    }
+}
+
+impl Valid for SecretKey {
+    fn check(&self) -> Result<(), SerializationError> {
+        self.scalar().map(|_| ()).map_err(|_| SerializationError::InvalidData)
+    }
+}
+
+impl CanonicalDeserialize for SecretKey {
+    fn deserialize_with_mode<R: Read>(reader: R, _compress: Compress, validate: Validate) -> Result<Self, SerializationError> {
+        let secret_key = SecretKey(read_bytes(reader)?);
+        if let Validate::Yes = validate {
+            secret_key.check()?;
+        }
+        Ok(secret_key)
+    }
+}
+
+impl CanonicalSerialize for Signature {
+    fn serialize_with_mode<W: Write>(&self, mut writer: W, _compress: Compress) -> Result<(), SerializationError> {
+        Ok(writer.write_all(&self.to_bytes())?)
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        64
+    }
+}
+
+impl Valid for Signature {
+    // r and s are checked by the verifier, which must reject rather than fail on them
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl CanonicalDeserialize for Signature {
+    fn deserialize_with_mode<R: Read>(mut reader: R, _compress: Compress, _validate: Validate) -> Result<Self, SerializationError> {
+        let r = read_bytes(&mut reader)?;
+        let s = read_bytes(&mut reader)?;
+        Ok(Signature { r, s })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn hex32(s: &str) -> [u8; 32] {
+        hex(s).try_into().unwrap()
+    }
+
+    // the signing vectors of BIP-340: index, secret key, public key, aux_rand, message, signature
+    const SIGN_VECTORS: [(u8, &str, &str, &str, &str, &str); 8] = [
+        (
+            0,
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "E907831F80848D1069A5371B402410364BDF1C5F8307B0084C55F1CE2DCA821525F66A4A85EA8B71E482A74F382D2CE5EBEEE8FDB2172F477DF4900D310536C0",
+        ),
+        (
+            1,
+            "B7E151628AED2A6ABF7158809CF4F3C762E7160F38B4DA56A784D9045190CFEF",
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6896BD60EEAE296DB48A229FF71DFE071BDE413E6D43F917DC8DCF8C78DE33418906D11AC976ABCCB20B091292BFF4EA897EFCB639EA871CFA95F6DE339E4B0A",
+        ),
+        (
+            2,
+            "C90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74020BBEA63B14E5C9",
+            "DD308AFEC5777E13121FA72B9CC1B7CC0139715309B086C960E18FD969774EB8",
+            "C87AA53824B4D7AE2EB035A2B5BBBCCC080E76CDC6D1692C4B0B62D798E6D906",
+            "7E2D58D8B3BCDF1ABADEC7829054F90DDA9805AAB56C77333024B9D0A508B75C",
+            "5831AAEED7B44BB74E5EAB94BA9D4294C49BCF2A60728D8B4C200F50DD313C1BAB745879A5AD954A72C45A91C3A51D3C7ADEA98D82F8481E0E1E03674A6F3FB7",
+        ),
+        (
+            3,
+            "0B432B2677937381AEF05BB02A66ECD012773062CF3FA2549E44F58ED2401710",
+            "25D1DFF95105F5253C4022F628A996AD3A0D95FBF21D468A1B33F8C160D8F517",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+            "7EB0509757E246F19449885651611CB965ECC1A187DD51B64FDA1EDC9637D5EC97582B9CB13DB3933705B32BA982AF5AF25FD78881EBB32771FC5922EFC66EA3",
+        ),
+        // messages of other lengths than 32 bytes
+        (
+            15,
+            "0340034003400340034003400340034003400340034003400340034003400340",
+            "778CAA53B4393AC467774D09497A87224BF9FAB6F6E68B23086497324D6FD117",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "",
+            "71535DB165ECD9FBBC046E5FFAEA61186BB6AD436732FCCC25291A55895464CF6069CE26BF03466228F19A3A62DB8A649F2D560FAC652827D1AF0574E427AB63",
+        ),
+        (
+            16,
+            "0340034003400340034003400340034003400340034003400340034003400340",
+            "778CAA53B4393AC467774D09497A87224BF9FAB6F6E68B23086497324D6FD117",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "11",
+            "08A20A0AFEF64124649232E0693C583AB1B9934AE63B4C3511F3AE1134C6A303EA3173BFEA6683BD101FA5AA5DBC1996FE7CACFC5A577D33EC14564CEC2BACBF",
+        ),
+        (
+            17,
+            "0340034003400340034003400340034003400340034003400340034003400340",
+            "778CAA53B4393AC467774D09497A87224BF9FAB6F6E68B23086497324D6FD117",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0102030405060708090A0B0C0D0E0F1011",
+            "5130F39A4059B43BC7CAC09A19ECE52B5D8699D1A71E3C52DA9AFDB6B50AC370C4A482B77BF960F8681540E25B6771ECE1E5A37FD80E5A51897C5566A97EA5A5",
+        ),
+        (
+            18,
+            "0340034003400340034003400340034003400340034003400340034003400340",
+            "778CAA53B4393AC467774D09497A87224BF9FAB6F6E68B23086497324D6FD117",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "99999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999999",
+            "403B12B0D8555A344175EA7EC746566303321E5DBFA8BE6F091635163ECA79A8585ED3E3170807E7C03B720FC54C7B23897FCBA0E9D0B4A06894CFD249F22367",
+        ),
+    ];
+
+    // the verification vectors of BIP-340: index, public key, message, signature, validity
+    const VERIFY_VECTORS: [(u8, &str, &str, &str, bool); 11] = [
+        (
+            4,
+            "D69C3509BB99E412E68B0FE8544E72837DFA30746D8BE2AA65975F29D22DC7B9",
+            "4DF3C3F68FCC83B27E9D42C90431A72499F17875C81A599B566C9889B9696703",
+            "00000000000000000000003B78CE563F89A0ED9414F5AA28AD0D96D6795F9C6376AFB1548AF603B3EB45C9F8207DEE1060CB71C04E80F593060B07D28308D7F4",
+            true,
+        ),
+        // public key not on the curve
+        (
+            5,
+            "EEFDEA4CDB677750A420FEE807EACF21EB9898AE79B9768766E4FAA04A2D4A34",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6CFF5C3BA86C69EA4B7376F31A9BCB4F74C1976089B2D9963DA2E5543E17776969E89B4C5564D00349106B8497785DD7D1D713A8AE82B32FA79D5F7FC407D39B",
+            false,
+        ),
+        // R has odd y
+        (
+            6,
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "FFF97BD5755EEEA420453A14355235D382F6472F8568A18B2F057A14602975563CC27944640AC607CD107AE10923D9EF7A73C643E166BE5EBEAFA34B1AC553E2",
+            false,
+        ),
+        // negated message
+        (
+            7,
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "1FA62E331EDBC21C394792D2AB1100A7B432B013DF3F6FF4F99FCB33E0E1515F28890B3EDB6E7189B630448B515CE4F8622A954CFE545735AAEA5134FCCDB2BD",
+            false,
+        ),
+        // negated s
+        (
+            8,
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6CFF5C3BA86C69EA4B7376F31A9BCB4F74C1976089B2D9963DA2E5543E177769961764B3AA9B2FFCB6EF947B6887A226E8D7C93E00C5ED0C1834FF0D0C2E6DA6",
+            false,
+        ),
+        // sG - eP is the point at infinity
+        (
+            9,
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "0000000000000000000000000000000000000000000000000000000000000000123DDA8328AF9C23A94C1FEECFD123BA4FB73476F0D594DCB65C6425BD186051",
+            false,
+        ),
+        (
+            10,
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "00000000000000000000000000000000000000000000000000000000000000017615FBAF5AE28864013C099742DEADB4DBA87F11AC6754F93780D5A1837CF197",
+            false,
+        ),
+        // r is not the x-coordinate of a point
+        (
+            11,
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "4A298DACAE57395A15D0795DDBFD1DCB564DA82B0F269BC70A74F8220429BA1D69E89B4C5564D00349106B8497785DD7D1D713A8AE82B32FA79D5F7FC407D39B",
+            false,
+        ),
+        // r is the field size
+        (
+            12,
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F69E89B4C5564D00349106B8497785DD7D1D713A8AE82B32FA79D5F7FC407D39B",
+            false,
+        ),
+        // s is the group order
+        (
+            13,
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6CFF5C3BA86C69EA4B7376F31A9BCB4F74C1976089B2D9963DA2E5543E177769FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141",
+            false,
+        ),
+        // public key exceeds the field size
+        (
+            14,
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC30",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6CFF5C3BA86C69EA4B7376F31A9BCB4F74C1976089B2D9963DA2E5543E17776969E89B4C5564D00349106B8497785DD7D1D713A8AE82B32FA79D5F7FC407D39B",
+            false,
+        ),
+    ];
+
+    #[test]
+    fn test_bip340_sign_vectors() {
+        for (index, secret_key, public_key, aux_rand, message, signature) in SIGN_VECTORS {
+            let sk = SecretKey(hex32(secret_key));
+            let pk = sk.public_key().unwrap();
+            assert_eq!(hex(public_key), pk.0, "public key of vector {}", index);
+
+            let sig = Bip340::sign_with_aux_rand(&sk, &hex(message), &hex32(aux_rand)).unwrap();
+            assert_eq!(hex(signature), sig.to_bytes(), "signature of vector {}", index);
+            assert!(Bip340::verify(&(), &pk, &hex(message), &sig).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_bip340_verify_vectors() {
+        for (index, public_key, message, signature, valid) in VERIFY_VECTORS {
+            let sig = Signature::from_bytes(&hex(signature)).unwrap();
+            let pk = PublicKey(hex32(public_key));
+            assert_eq!(valid, Bip340::verify(&(), &pk, &hex(message), &sig).unwrap(), "vector {}", index);
+        }
+
+        // keys that are no x-coordinate do not even decode
+        for public_key in [VERIFY_VECTORS[1].1, VERIFY_VECTORS[10].1] {
+            assert!(PublicKey::deserialize_compressed(&hex(public_key)[..]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_invalid_secret_key() {
+        let order = hex32("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141");
+        for sk in [SecretKey([0u8; 32]), SecretKey(order)] {
+            assert_eq!(Err(Bip340Error::InvalidSecretKey), sk.public_key());
+            assert!(sk.check().is_err());
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! BLS signatures on BLS12-381 as specified in
+//! https://www.ietf.org/archive/id/draft-irtf-cfrg-bls-signature-05.html, for the vault's
+//! admin signing key and for guardian approvals. We use the minimal-pubkey-size variant with
+//! the proof-of-possession scheme: public keys are 48-byte points of G1, and signatures are
+//! 96-byte points of G2 onto which messages are hashed as in `suite::Bls12381G1`, with the
+//! ciphersuite's own DSTs. Both are encoded compressed, as the draft prescribes.
+//!
+//! Signatures on the same message aggregate into one, which verifies against the sum of the
+//! signers' public keys. This is only sound for public keys whose proof of possession was
+//! checked when they were registered, lest a rogue key cancel out the others.
+
+#![allow(dead_code)]
+
+use std::fmt;
+
+use ark_bls12_381::{Bls12_381, Fr, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_crypto_primitives::Error;
+use ark_ec::{pairing::Pairing, AffineRepr, CurveGroup, Group};
+use ark_ff::{PrimeField, Zero};
+use ark_serialize::CanonicalSerialize;
+use ark_std::{ops::*, rand::Rng};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+
+use super::SignatureScheme;
+use crate::crypto::ppss::suite::hash_to_curve_xmd_sha256;
+
+const DST_SIG: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+const DST_POP: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+const KEYGEN_SALT: &[u8] = b"BLS-SIG-KEYGEN-SALT-";
+
+pub struct Bls;
+
+pub type PublicKey = G1Affine;
+
+pub type SecretKey = Fr;
+
+pub type Signature = G2Affine;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BlsError {
+    /// the input keying material is shorter than the 32 bytes the key derivation demands
+    ShortKeyMaterial(usize),
+}
+
+impl std::error::Error for BlsError {}
+
+impl fmt::Display for BlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlsError::ShortKeyMaterial(len) =>
+                write!(f, "The key material has {} bytes, but at least 32 are needed.", len),
+        }
+    }
+}
+
+impl Bls {
+    /// Derives a secret key from at least 32 bytes of keying material, as KeyGen does.
+    pub fn derive_secret_key(ikm: &[u8], key_info: &[u8]) -> Result<SecretKey, BlsError> {
+        if ikm.len() < 32 {
+            return Err(BlsError::ShortKeyMaterial(ikm.len()));
+        }
+
+        // IKM || I2OSP(0, 1) and key_info || I2OSP(L, 2), for L = 48
+        let ikm = [ikm, &[0u8]].concat();
+        let info = [key_info, &[0u8, 48]].concat();
+        let mut salt = KEYGEN_SALT.to_vec();
+        loop {
+            salt = Sha256::digest(&salt).to_vec();
+            let mut okm = [0u8; 48];
+            Hkdf::<Sha256>::new(Some(&salt), &ikm)
+                .expand(&info, &mut okm)
+                .expect("48 bytes is a valid HKDF-SHA256 output length");
+
+            let secret_key = Fr::from_be_bytes_mod_order(&okm);
+            if !secret_key.is_zero() {
+                return Ok(secret_key);
+            }
+        }
+    }
+
+    /// Proves knowledge of the secret key, by signing the public key under the PoP DST.
+    pub fn prove_possession(sk: &SecretKey) -> Result<Signature, Error> {
+        let public_key = G1Projective::generator().mul(sk).into_affine();
+        sign_with_dst(sk, &to_bytes(&public_key)?, DST_POP)
+    }
+
+    pub fn verify_possession(pk: &PublicKey, proof: &Signature) -> Result<bool, Error> {
+        core_verify(pk, &to_bytes(pk)?, proof, DST_POP)
+    }
+
+    /// Adds up signatures, or returns `None` for none.
+    pub fn aggregate(signatures: &[Signature]) -> Option<Signature> {
+        if signatures.is_empty() {
+            return None;
+        }
+        Some(signatures.iter().map(|signature| signature.into_group()).sum::<G2Projective>().into_affine())
+    }
+
+    /// Verifies an aggregate of signatures on the same message; the public keys must have
+    /// proven possession, or any one signer could forge the aggregate for all of them.
+    pub fn fast_aggregate_verify(pks: &[PublicKey], message: &[u8], signature: &Signature) -> Result<bool, Error> {
+        if pks.is_empty() || !pks.iter().all(key_validate) {
+            return Ok(false);
+        }
+        let aggregate_key = pks.iter().map(|pk| pk.into_group()).sum::<G1Projective>().into_affine();
+        core_verify(&aggregate_key, message, signature, DST_SIG)
+    }
+
+    /// Verifies an aggregate of signatures on any messages, with one pairing per signer.
+    pub fn aggregate_verify(batch: &[(&PublicKey, &[u8])], signature: &Signature) -> Result<bool, Error> {
+        if batch.is_empty() || !batch.iter().all(|(pk, _)| key_validate(pk)) || !signature_validate(signature) {
+            return Ok(false);
+        }
+
+        // e(-P1, sig) * prod_i e(pk_i, H(m_i)) = 1
+        let mut g1_points = vec![-G1Affine::generator()];
+        let mut g2_points = vec![*signature];
+        for (pk, message) in batch {
+            g1_points.push(**pk);
+            g2_points.push(hash_to_g2(message, DST_SIG)?);
+        }
+        Ok(Bls12_381::multi_pairing(g1_points, g2_points).is_zero())
+    }
+}
+
+impl SignatureScheme for Bls {
+    type Parameters = ();
+    type PublicKey = PublicKey;
+    type SecretKey = SecretKey;
+    type Signature = Signature;
+
+    fn setup<R: Rng>(_rng: &mut R) -> Result<Self::Parameters, Error> {
+        Ok(())
+    }
+
+    fn keygen<R: Rng>(
+        _parameters: &Self::Parameters,
+        rng: &mut R,
+    ) -> Result<(Self::PublicKey, Self::SecretKey), Error> {
+        let ikm: [u8; 32] = rng.gen();
+        let secret_key = Self::derive_secret_key(&ikm, &[])?;
+        Ok((G1Projective::generator().mul(secret_key).into_affine(), secret_key))
+    }
+
+    fn sign<R: Rng>(
+        _parameters: &Self::Parameters,
+        sk: &Self::SecretKey,
+        message: &[u8],
+        _rng: &mut R,
+    ) -> Result<Self::Signature, Error> {
+        sign_with_dst(sk, message, DST_SIG)
+    }
+
+    fn verify(
+        _parameters: &Self::Parameters,
+        pk: &Self::PublicKey,
+        message: &[u8],
+        signature: &Self::Signature,
+    ) -> Result<bool, Error> {
+        core_verify(pk, message, signature, DST_SIG)
+    }
+}
+
+fn hash_to_g2(message: &[u8], dst: &[u8]) -> Result<G2Affine, Error> {
+    Ok(hash_to_curve_xmd_sha256::<ark_bls12_381::g2::Config>(message, dst)?)
+}
+
+fn sign_with_dst(sk: &SecretKey, message: &[u8], dst: &[u8]) -> Result<Signature, Error> {
+    Ok(hash_to_g2(message, dst)?.mul(sk).into_affine())
+}
+
+/// e(pk, H(m)) = e(P1, sig)
+fn core_verify(pk: &PublicKey, message: &[u8], signature: &Signature, dst: &[u8]) -> Result<bool, Error> {
+    if !key_validate(pk) || !signature_validate(signature) {
+        return Ok(false);
+    }
+    let message_point = hash_to_g2(message, dst)?;
+    Ok(Bls12_381::multi_pairing([-G1Affine::generator(), *pk], [*signature, message_point]).is_zero())
+}
+
+/// A public key must be a point of the prime-order subgroup other than the identity.
+fn key_validate(pk: &PublicKey) -> bool {
+    !pk.is_zero() && pk.is_on_curve() && pk.is_in_correct_subgroup_assuming_on_curve()
+}
+
+fn signature_validate(signature: &Signature) -> bool {
+    signature.is_on_curve() && signature.is_in_correct_subgroup_assuming_on_curve()
+}
+
+fn to_bytes(pk: &PublicKey) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    pk.serialize_compressed(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_std::test_rng;
+
+    fn keys(n: usize) -> Vec<(PublicKey, SecretKey)> {
+        let rng = &mut test_rng();
+        (0..n).map(|_| Bls::keygen(&(), rng).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_encoding_lengths() {
+        let (pk, sk) = keys(1)[0];
+        let sig = Bls::sign(&(), &sk, b"message", &mut test_rng()).unwrap();
+        assert_eq!(48, super::super::encoding::to_bytes(&pk).len());
+        assert_eq!(96, super::super::encoding::to_bytes(&sig).len());
+    }
+
+    #[test]
+    fn test_derive_secret_key() {
+        let sk = Bls::derive_secret_key(&[7u8; 32], b"").unwrap();
+        assert_eq!(sk, Bls::derive_secret_key(&[7u8; 32], b"").unwrap());
+        assert_ne!(sk, Bls::derive_secret_key(&[7u8; 32], b"admin").unwrap());
+        assert_eq!(Err(BlsError::ShortKeyMaterial(31)), Bls::derive_secret_key(&[7u8; 31], b""));
+    }
+
+    #[test]
+    fn test_proof_of_possession() {
+        let keys = keys(2);
+        let proof = Bls::prove_possession(&keys[0].1).unwrap();
+        assert!(Bls::verify_possession(&keys[0].0, &proof).unwrap());
+        assert!(!Bls::verify_possession(&keys[1].0, &proof).unwrap());
+
+        // a proof of possession is no signature on the encoded key, nor vice versa
+        let message = to_bytes(&keys[0].0).unwrap();
+        assert!(!Bls::verify(&(), &keys[0].0, &message, &proof).unwrap());
+        let signature = Bls::sign(&(), &keys[0].1, &message, &mut test_rng()).unwrap();
+        assert!(!Bls::verify_possession(&keys[0].0, &signature).unwrap());
+    }
+
+    #[test]
+    fn test_aggregate_verify() {
+        let rng = &mut test_rng();
+        let keys = keys(3);
+        let pks: Vec<_> = keys.iter().map(|(pk, _)| *pk).collect();
+
+        // approvals of the same message
+        let approvals: Vec<_> = keys.iter().map(|(_, sk)| Bls::sign(&(), sk, b"vault update", rng).unwrap()).collect();
+        let aggregate = Bls::aggregate(&approvals).unwrap();
+        assert!(Bls::fast_aggregate_verify(&pks, b"vault update", &aggregate).unwrap());
+        assert!(!Bls::fast_aggregate_verify(&pks[..2], b"vault update", &aggregate).unwrap());
+        assert!(!Bls::fast_aggregate_verify(&pks, b"another update", &aggregate).unwrap());
+        assert!(!Bls::fast_aggregate_verify(&[], b"vault update", &aggregate).unwrap());
+        assert_eq!(None, Bls::aggregate(&[]));
+
+        // signatures on distinct messages
+        let messages: [&[u8]; 3] = [b"one", b"two", b"three"];
+        let signatures: Vec<_> = keys.iter().zip(messages)
+            .map(|((_, sk), message)| Bls::sign(&(), sk, message, rng).unwrap())
+            .collect();
+        let aggregate = Bls::aggregate(&signatures).unwrap();
+        let batch: Vec<_> = pks.iter().zip(messages).collect();
+        assert!(Bls::aggregate_verify(&batch, &aggregate).unwrap());
+        let swapped = [batch[1], batch[0], batch[2]].map(|(pk, _)| pk).into_iter().zip(messages).collect::<Vec<_>>();
+        assert!(!Bls::aggregate_verify(&swapped, &aggregate).unwrap());
+
+        // the identity is no public key
+        assert!(!Bls::verify(&(), &G1Affine::zero(), b"vault update", &G2Affine::zero()).unwrap());
+    }
+}
@@ -0,0 +1,305 @@
+//! Ed25519 signatures as specified in https://www.rfc-editor.org/rfc/rfc8032, which the
+//! design uses for ephemeral session keys. Keys and signatures are the byte strings of the
+//! RFC, so they interoperate with WebCrypto and passkey platforms: a public key is the
+//! 32-byte encoding of the point A, a secret key the 32-byte seed it is derived from, and a
+//! signature the 64-byte encoding of R followed by S. Signing is deterministic, and
+//! verification uses the cofactored equation [8][S]B = [8]R + [8][k]A.
+
+use ark_crypto_primitives::Error;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate, Write,
+};
+use ark_std::rand::Rng;
+use curve25519_dalek::{
+    edwards::{CompressedEdwardsY, EdwardsPoint},
+    scalar::{clamp_integer, Scalar},
+    traits::IsIdentity,
+};
+use sha2::{Digest, Sha512};
+
+use super::{encoding::read_bytes, SignatureScheme};
+
+pub struct Ed25519;
+
+/// The 32-byte encoding of the public point A.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub struct PublicKey(pub [u8; 32]);
+
+/// The 32-byte seed from which the signing scalar and the nonce prefix are derived.
+#[derive(Clone, Default)]
+pub struct SecretKey(pub [u8; 32]);
+
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct Signature {
+    /// encoding of the commitment R
+    pub r: [u8; 32],
+    /// little-endian encoding of the response S
+    pub s: [u8; 32],
+}
+
+impl PublicKey {
+    /// Decodes the point, rejecting non-canonical encodings as RFC 8032, Section 5.1.3 does.
+    fn decompress(&self) -> Option<EdwardsPoint> {
+        decompress(&self.0)
+    }
+}
+
+impl SecretKey {
+    pub fn public_key(&self) -> PublicKey {
+        let (scalar, _) = expand_secret_key(self);
+        PublicKey(EdwardsPoint::mul_base(&scalar).compress().to_bytes())
+    }
+}
+
+#[allow(dead_code)]
+impl Signature {
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.r);
+        bytes[32..].copy_from_slice(&self.s);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        super::encoding::from_bytes(bytes)
+    }
+}
+
+impl SignatureScheme for Ed25519 {
+    type Parameters = ();
+    type PublicKey = PublicKey;
+    type SecretKey = SecretKey;
+    type Signature = Signature;
+
+    fn setup<R: Rng>(_rng: &mut R) -> Result<Self::Parameters, Error> {
+        Ok(())
+    }
+
+    fn keygen<R: Rng>(
+        _parameters: &Self::Parameters,
+        rng: &mut R,
+    ) -> Result<(Self::PublicKey, Self::SecretKey), Error> {
+        let secret_key = SecretKey(rng.gen());
+        Ok((secret_key.public_key(), secret_key))
+    }
+
+    /// Signs as in RFC 8032, Section 5.1.6; the nonce is derived from the key and the
+    /// message, so the random number generator is not used.
+    fn sign<R: Rng>(
+        _parameters: &Self::Parameters,
+        sk: &Self::SecretKey,
+        message: &[u8],
+        _rng: &mut R,
+    ) -> Result<Self::Signature, Error> {
+        let (scalar, prefix) = expand_secret_key(sk);
+        let public_key = EdwardsPoint::mul_base(&scalar).compress().to_bytes();
+
+        // r := SHA512(prefix || M), R := rB
+        let r = hash_to_scalar(&[&prefix, message]);
+        let commitment = EdwardsPoint::mul_base(&r).compress().to_bytes();
+
+        // S := r + k s, for k := SHA512(R || A || M)
+        let k = hash_to_scalar(&[&commitment, &public_key, message]);
+        let response = r + k * scalar;
+
+        Ok(Signature { r: commitment, s: response.to_bytes() })
+    }
+
+    /// Verifies as in RFC 8032, Section 5.1.7.
+    fn verify(
+        _parameters: &Self::Parameters,
+        pk: &Self::PublicKey,
+        message: &[u8],
+        signature: &Self::Signature,
+    ) -> Result<bool, Error> {
+        let (Some(public_key), Some(commitment)) = (pk.decompress(), decompress(&signature.r)) else {
+            return Ok(false);
+        };
+        // S must be reduced, lest signatures be malleable
+        let Some(response) = Option::<Scalar>::from(Scalar::from_canonical_bytes(signature.s)) else {
+            return Ok(false);
+        };
+
+        let k = hash_to_scalar(&[&signature.r, &pk.0, message]);
+        // [8]([S]B - [k]A - R) = 0
+        let difference = EdwardsPoint::vartime_double_scalar_mul_basepoint(&k, &-public_key, &response) - commitment;
+        Ok(difference.mul_by_cofactor().is_identity())
+    }
+}
+
+/// Splits SHA512(seed) into the clamped signing scalar s and the nonce prefix.
+fn expand_secret_key(sk: &SecretKey) -> (Scalar, [u8; 32]) {
+    let digest = Sha512::digest(sk.0);
+    let mut scalar_bytes = [0u8; 32];
+    scalar_bytes.copy_from_slice(&digest[..32]);
+    let mut prefix = [0u8; 32];
+    prefix.copy_from_slice(&digest[32..]);
+
+    // the clamped integer exceeds the group order, but B has prime order, so it may be reduced
+    (Scalar::from_bytes_mod_order(clamp_integer(scalar_bytes)), prefix)
+}
+
+fn hash_to_scalar(inputs: &[&[u8]]) -> Scalar {
+    let mut hasher = Sha512::new();
+    for input in inputs {
+        hasher.update(input);
+    }
+    Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+}
+
+fn decompress(bytes: &[u8; 32]) -> Option<EdwardsPoint> {
+    let point = CompressedEdwardsY(*bytes).decompress()?;
+    // decompression reduces y and ignores the sign of x = 0, which the RFC rejects
+    (point.compress().to_bytes() == *bytes).then_some(point)
+}
+
+impl CanonicalSerialize for PublicKey {
+    fn serialize_with_mode<W: Write>(&self, mut writer: W, _compress: Compress) -> Result<(), SerializationError> {
+        Ok(writer.write_all(&self.0)?)
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        32
+    }
+}
+
+impl Valid for PublicKey {
+    fn check(&self) -> Result<(), SerializationError> {
+        self.decompress().map(|_| ()).ok_or(SerializationError::InvalidData)
+    }
+}
+
+impl CanonicalDeserialize for PublicKey {
+    fn deserialize_with_mode<R: Read>(reader: R, _compress: Compress, validate: Validate) -> Result<Self, SerializationError> {
+        let public_key = PublicKey(read_bytes(reader)?);
+        if let Validate::Yes = validate {
+            public_key.check()?;
+        }
+        Ok(public_key)
+    }
+}
+
+impl CanonicalSerialize for SecretKey {
+    fn serialize_with_mode<W: Write>(&self, mut writer: W, _compress: Compress) -> Result<(), SerializationError> {
+        Ok(writer.write_all(&self.0)?)
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        32
+    }
+}
+
+impl Valid for SecretKey {
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl CanonicalDeserialize for SecretKey {
+    fn deserialize_with_mode<R: Read>(reader: R, _compress: Compress, _validate: Validate) -> Result<Self, SerializationError> {
+        Ok(SecretKey(read_bytes(reader)?))
+    }
+}
+
+impl CanonicalSerialize for Signature {
+    fn serialize_with_mode<W: Write>(&self, mut writer: W, _compress: Compress) -> Result<(), SerializationError> {
+        Ok(writer.write_all(&self.to_bytes())?)
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        64
+    }
+}
+
+impl Valid for Signature {
+    // R and S are checked by the verifier, which must reject rather than fail on them
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl CanonicalDeserialize for Signature {
+    fn deserialize_with_mode<R: Read>(mut reader: R, _compress: Compress, _validate: Validate) -> Result<Self, SerializationError> {
+        let r = read_bytes(&mut reader)?;
+        let s = read_bytes(&mut reader)?;
+        Ok(Signature { r, s })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_std::test_rng;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    // RFC 8032, Section 7.1: secret key, public key, message and signature
+    const VECTORS: [(&str, &str, &str, &str); 4] = [
+        (
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ),
+        (
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        ),
+        (
+            "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            "af82",
+            "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+        ),
+        (
+            "833fe62409237b9d62ec77587520911e9a759cec1d19755b7da901b96dca3d42",
+            "ec172b93ad5e563bf4932c70e1245034c35467ef2efd4d64ebf819683467e2bf",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            "dc2a4459e7369633a52b1bf277839a00201009a3efbf3ecb69bea2186c26b58909351fc9ac90b3ecfdfbc7c66431e0303dca179c138ac17ad9bef1177331a704",
+        ),
+    ];
+
+    #[test]
+    fn test_rfc8032_vectors() {
+        let rng = &mut test_rng();
+        for (secret_key, public_key, message, signature) in VECTORS {
+            let sk: SecretKey = super::super::encoding::from_bytes(&hex(secret_key)).unwrap();
+            let pk = sk.public_key();
+            assert_eq!(hex(public_key), pk.0);
+
+            let message = hex(message);
+            let sig = Ed25519::sign(&(), &sk, &message, rng).unwrap();
+            assert_eq!(hex(signature), sig.to_bytes());
+            assert!(Ed25519::verify(&(), &pk, &message, &Signature::from_bytes(&hex(signature)).unwrap()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_reject_malleable_signatures() {
+        let rng = &mut test_rng();
+        let (pk, sk) = Ed25519::keygen(&(), rng).unwrap();
+        let sig = Ed25519::sign(&(), &sk, b"message", rng).unwrap();
+        assert!(Ed25519::verify(&(), &pk, b"message", &sig).unwrap());
+
+        // S + L is the same scalar, but not reduced
+        let order = hex("edd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010");
+        let mut s = sig.s;
+        let mut carry = 0u16;
+        for i in 0..32 {
+            let sum = s[i] as u16 + order[i] as u16 + carry;
+            s[i] = sum as u8;
+            carry = sum >> 8;
+        }
+        assert!(!Ed25519::verify(&(), &pk, b"message", &Signature { s, ..sig.clone() }).unwrap());
+
+        // neither are points that do not decode
+        let invalid = PublicKey([0xff; 32]);
+        assert!(invalid.check().is_err());
+        assert!(!Ed25519::verify(&(), &invalid, b"message", &sig).unwrap());
+        assert!(Signature::from_bytes(&sig.to_bytes()[..63]).is_err());
+    }
+}
@@ -0,0 +1,336 @@
+//! Wire encodings of Schnorr keys and signatures.
+//!
+//! Keys and signatures have a fixed-length byte encoding, namely arkworks' compressed
+//! serialization, in which scalars and coordinates are little-endian:
+//!
+//! | curve     | public key | secret key | signature |
+//! |-----------|------------|------------|-----------|
+//! | JubJub    | 32 bytes   | 32 bytes   | 64 bytes  |
+//! | secp256k1 | 33 bytes   | 32 bytes   | 64 bytes  |
+//!
+//! A signature is the response scalar followed by the 32-byte challenge. A signature that
+//! carries its commitment instead, for batch verification, is the commitment followed by
+//! the response scalar, thus 64 bytes on JubJub and 65 bytes on secp256k1.
+//!
+//! For other tools, keys are also exported as JWK and PEM. secp256k1 keys use the EC JWKs of
+//! RFC 8812 and the SPKI and PKCS#8 documents of RFC 5480 and RFC 5915, with big-endian SEC1
+//! encodings. JubJub has neither a registered JWK curve nor an OID, so its keys use an OKP JWK
+//! with `"crv": "JubJub"`, as RFC 8037 does for Ed25519, and PEM documents labeled
+//! `JUBJUB PUBLIC KEY` and `JUBJUB PRIVATE KEY`, both carrying the fixed-length encodings above.
+
+#![allow(dead_code)]
+
+use std::fmt;
+
+use ark_ec::{AffineRepr, CurveGroup, Group};
+use ark_ff::{BigInteger, PrimeField};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use k256::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyEncodingError {
+    /// the JWK is malformed, or describes a key of another type or curve
+    MalformedJwk,
+    /// the PEM document is malformed, or holds a key of another type or curve
+    MalformedPem,
+    /// the encoded key is not a valid key, or its public and secret parts do not match
+    InvalidKey,
+}
+
+impl std::error::Error for KeyEncodingError {}
+
+impl fmt::Display for KeyEncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyEncodingError::MalformedJwk =>
+                write!(f, "The JWK does not describe a key on this curve."),
+            KeyEncodingError::MalformedPem =>
+                write!(f, "The PEM document does not hold a key on this curve."),
+            KeyEncodingError::InvalidKey =>
+                write!(f, "The encoded key is invalid."),
+        }
+    }
+}
+
+/// Encodes a key or signature in its fixed-length encoding.
+pub fn to_bytes<T: CanonicalSerialize>(value: &T) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(value.compressed_size());
+    value.serialize_compressed(&mut bytes)
+        .expect("serializing into a vector does not fail");
+    bytes
+}
+
+/// Decodes a key or signature from its fixed-length encoding; points are checked
+/// to be in the prime-order subgroup, and scalars to be reduced.
+pub fn from_bytes<T: CanonicalDeserialize>(bytes: &[u8]) -> Result<T, SerializationError> {
+    let mut reader = bytes;
+    let value = T::deserialize_compressed(&mut reader)?;
+    if !reader.is_empty() {
+        return Err(SerializationError::InvalidData);
+    }
+    Ok(value)
+}
+
+/// Reads a fixed-length byte string, failing rather than panicking on short input
+/// as arkworks' deserialization of arrays does.
+pub(super) fn read_bytes<R: Read, const N: usize>(mut reader: R) -> Result<[u8; N], SerializationError> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Curves whose keys can be exchanged with other tools.
+pub trait KeyEncoding: CurveGroup {
+    fn public_key_to_jwk(pk: &Self::Affine) -> Result<String, KeyEncodingError>;
+
+    fn public_key_from_jwk(jwk: &str) -> Result<Self::Affine, KeyEncodingError>;
+
+    /// The JWK of a secret key includes its public key, as the JWK formats require.
+    fn secret_key_to_jwk(sk: &Self::ScalarField) -> Result<String, KeyEncodingError>;
+
+    fn secret_key_from_jwk(jwk: &str) -> Result<Self::ScalarField, KeyEncodingError>;
+
+    fn public_key_to_pem(pk: &Self::Affine) -> Result<String, KeyEncodingError>;
+
+    fn public_key_from_pem(pem: &str) -> Result<Self::Affine, KeyEncodingError>;
+
+    fn secret_key_to_pem(sk: &Self::ScalarField) -> Result<String, KeyEncodingError>;
+
+    fn secret_key_from_pem(pem: &str) -> Result<Self::ScalarField, KeyEncodingError>;
+}
+
+/// An octet key pair JWK, as RFC 8037 defines for Ed25519.
+#[derive(Serialize, Deserialize)]
+struct OkpJwk {
+    kty: String,
+    crv: String,
+    x: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    d: Option<String>,
+}
+
+const JUBJUB_JWK_CURVE: &str = "JubJub";
+const JUBJUB_PUBLIC_KEY_LABEL: &str = "JUBJUB PUBLIC KEY";
+const JUBJUB_PRIVATE_KEY_LABEL: &str = "JUBJUB PRIVATE KEY";
+
+type JubJub = ark_ed_on_bls12_381::EdwardsProjective;
+
+impl KeyEncoding for JubJub {
+    fn public_key_to_jwk(pk: &Self::Affine) -> Result<String, KeyEncodingError> {
+        okp_jwk(pk, None)
+    }
+
+    fn public_key_from_jwk(jwk: &str) -> Result<Self::Affine, KeyEncodingError> {
+        let jwk = parse_okp_jwk(jwk)?;
+        decode_jwk_field(&jwk.x)
+    }
+
+    fn secret_key_to_jwk(sk: &Self::ScalarField) -> Result<String, KeyEncodingError> {
+        okp_jwk(&(Self::generator() * sk).into_affine(), Some(sk))
+    }
+
+    fn secret_key_from_jwk(jwk: &str) -> Result<Self::ScalarField, KeyEncodingError> {
+        let jwk = parse_okp_jwk(jwk)?;
+        let sk: Self::ScalarField = decode_jwk_field(jwk.d.as_ref().ok_or(KeyEncodingError::MalformedJwk)?)?;
+        let pk: Self::Affine = decode_jwk_field(&jwk.x)?;
+        if (Self::generator() * sk).into_affine() != pk {
+            return Err(KeyEncodingError::InvalidKey);
+        }
+        Ok(sk)
+    }
+
+    fn public_key_to_pem(pk: &Self::Affine) -> Result<String, KeyEncodingError> {
+        pem_rfc7468::encode_string(JUBJUB_PUBLIC_KEY_LABEL, pem_rfc7468::LineEnding::LF, &to_bytes(pk))
+            .map_err(|_| KeyEncodingError::InvalidKey)
+    }
+
+    fn public_key_from_pem(pem: &str) -> Result<Self::Affine, KeyEncodingError> {
+        from_bytes(&decode_pem(JUBJUB_PUBLIC_KEY_LABEL, pem)?).map_err(|_| KeyEncodingError::InvalidKey)
+    }
+
+    fn secret_key_to_pem(sk: &Self::ScalarField) -> Result<String, KeyEncodingError> {
+        pem_rfc7468::encode_string(JUBJUB_PRIVATE_KEY_LABEL, pem_rfc7468::LineEnding::LF, &to_bytes(sk))
+            .map_err(|_| KeyEncodingError::InvalidKey)
+    }
+
+    fn secret_key_from_pem(pem: &str) -> Result<Self::ScalarField, KeyEncodingError> {
+        from_bytes(&decode_pem(JUBJUB_PRIVATE_KEY_LABEL, pem)?).map_err(|_| KeyEncodingError::InvalidKey)
+    }
+}
+
+fn okp_jwk(pk: &ark_ed_on_bls12_381::EdwardsAffine, sk: Option<&ark_ed_on_bls12_381::Fr>) -> Result<String, KeyEncodingError> {
+    let jwk = OkpJwk {
+        kty: "OKP".to_string(),
+        crv: JUBJUB_JWK_CURVE.to_string(),
+        x: URL_SAFE_NO_PAD.encode(to_bytes(pk)),
+        d: sk.map(|sk| URL_SAFE_NO_PAD.encode(to_bytes(sk))),
+    };
+    serde_json::to_string(&jwk).map_err(|_| KeyEncodingError::InvalidKey)
+}
+
+fn parse_okp_jwk(jwk: &str) -> Result<OkpJwk, KeyEncodingError> {
+    let jwk: OkpJwk = serde_json::from_str(jwk).map_err(|_| KeyEncodingError::MalformedJwk)?;
+    if jwk.kty != "OKP" || jwk.crv != JUBJUB_JWK_CURVE {
+        return Err(KeyEncodingError::MalformedJwk);
+    }
+    Ok(jwk)
+}
+
+fn decode_jwk_field<T: CanonicalDeserialize>(field: &str) -> Result<T, KeyEncodingError> {
+    let bytes = URL_SAFE_NO_PAD.decode(field).map_err(|_| KeyEncodingError::MalformedJwk)?;
+    from_bytes(&bytes).map_err(|_| KeyEncodingError::InvalidKey)
+}
+
+fn decode_pem(label: &str, pem: &str) -> Result<Vec<u8>, KeyEncodingError> {
+    match pem_rfc7468::decode_vec(pem.trim().as_bytes()) {
+        Ok((pem_label, bytes)) if pem_label == label => Ok(bytes),
+        _ => Err(KeyEncodingError::MalformedPem),
+    }
+}
+
+type Secp256k1 = ark_secp256k1::Projective;
+
+impl KeyEncoding for Secp256k1 {
+    fn public_key_to_jwk(pk: &Self::Affine) -> Result<String, KeyEncodingError> {
+        Ok(to_k256_public_key(pk)?.to_jwk_string())
+    }
+
+    fn public_key_from_jwk(jwk: &str) -> Result<Self::Affine, KeyEncodingError> {
+        let pk = k256::PublicKey::from_jwk_str(jwk).map_err(|_| KeyEncodingError::MalformedJwk)?;
+        Ok(from_k256_public_key(&pk))
+    }
+
+    fn secret_key_to_jwk(sk: &Self::ScalarField) -> Result<String, KeyEncodingError> {
+        Ok(to_k256_secret_key(sk)?.to_jwk_string().to_string())
+    }
+
+    fn secret_key_from_jwk(jwk: &str) -> Result<Self::ScalarField, KeyEncodingError> {
+        let sk = k256::SecretKey::from_jwk_str(jwk).map_err(|_| KeyEncodingError::MalformedJwk)?;
+        Ok(from_k256_secret_key(&sk))
+    }
+
+    fn public_key_to_pem(pk: &Self::Affine) -> Result<String, KeyEncodingError> {
+        to_k256_public_key(pk)?.to_public_key_pem(LineEnding::LF)
+            .map_err(|_| KeyEncodingError::InvalidKey)
+    }
+
+    fn public_key_from_pem(pem: &str) -> Result<Self::Affine, KeyEncodingError> {
+        let pk = k256::PublicKey::from_public_key_pem(pem.trim()).map_err(|_| KeyEncodingError::MalformedPem)?;
+        Ok(from_k256_public_key(&pk))
+    }
+
+    fn secret_key_to_pem(sk: &Self::ScalarField) -> Result<String, KeyEncodingError> {
+        let pem = to_k256_secret_key(sk)?.to_pkcs8_pem(LineEnding::LF)
+            .map_err(|_| KeyEncodingError::InvalidKey)?;
+        Ok(pem.to_string())
+    }
+
+    fn secret_key_from_pem(pem: &str) -> Result<Self::ScalarField, KeyEncodingError> {
+        let sk = k256::SecretKey::from_pkcs8_pem(pem.trim()).map_err(|_| KeyEncodingError::MalformedPem)?;
+        Ok(from_k256_secret_key(&sk))
+    }
+}
+
+/// Carries a point over to the RustCrypto implementation by its big-endian affine coordinates.
+fn to_k256_public_key(pk: &ark_secp256k1::Affine) -> Result<k256::PublicKey, KeyEncodingError> {
+    let (x, y) = pk.xy().ok_or(KeyEncodingError::InvalidKey)?;
+    let point = k256::EncodedPoint::from_affine_coordinates(
+        &k256::FieldBytes::clone_from_slice(&x.into_bigint().to_bytes_be()),
+        &k256::FieldBytes::clone_from_slice(&y.into_bigint().to_bytes_be()),
+        false,
+    );
+    k256::PublicKey::from_sec1_bytes(point.as_bytes()).map_err(|_| KeyEncodingError::InvalidKey)
+}
+
+fn from_k256_public_key(pk: &k256::PublicKey) -> ark_secp256k1::Affine {
+    use k256::elliptic_curve::sec1::ToEncodedPoint;
+
+    // RustCrypto public keys are never the identity, so both coordinates are present
+    let point = pk.to_encoded_point(false);
+    ark_secp256k1::Affine::new_unchecked(
+        ark_secp256k1::Fq::from_be_bytes_mod_order(point.x().expect("public key is not the identity")),
+        ark_secp256k1::Fq::from_be_bytes_mod_order(point.y().expect("public key is not the identity")),
+    )
+}
+
+fn to_k256_secret_key(sk: &ark_secp256k1::Fr) -> Result<k256::SecretKey, KeyEncodingError> {
+    k256::SecretKey::from_slice(&sk.into_bigint().to_bytes_be()).map_err(|_| KeyEncodingError::InvalidKey)
+}
+
+fn from_k256_secret_key(sk: &k256::SecretKey) -> ark_secp256k1::Fr {
+    ark_secp256k1::Fr::from_be_bytes_mod_order(&sk.to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ff::UniformRand;
+    use ark_std::test_rng;
+
+    use super::*;
+    use crate::crypto::sig::{schnorr::{Schnorr, Signature}, SignatureScheme};
+
+    fn check_encodings<C: KeyEncoding + std::hash::Hash>(public_key_len: usize)
+    where
+        C::ScalarField: PrimeField,
+    {
+        let rng = &mut test_rng();
+        let pp = Schnorr::<C>::setup(rng).unwrap();
+        let (pk, sk) = Schnorr::<C>::keygen(&pp, rng).unwrap();
+        let sig = Schnorr::<C>::sign(&pp, &sk, b"message", rng).unwrap();
+
+        // fixed-length encodings
+        assert_eq!(public_key_len, to_bytes(&pk).len());
+        assert_eq!(32, to_bytes(&sk).len());
+        assert_eq!(64, sig.to_bytes().len());
+        assert_eq!(pk, from_bytes::<C::Affine>(&to_bytes(&pk)).unwrap());
+        assert_eq!(sk, from_bytes::<C::ScalarField>(&to_bytes(&sk)).unwrap());
+        let decoded = Signature::<C>::from_bytes(&sig.to_bytes()).unwrap();
+        assert!(Schnorr::<C>::verify(&pp, &pk, b"message", &decoded).unwrap());
+        let mut trailing = sig.to_bytes();
+        trailing.push(0);
+        assert!(Signature::<C>::from_bytes(&trailing).is_err());
+        assert!(Signature::<C>::from_bytes(&sig.to_bytes()[..63]).is_err());
+
+        // JWK and PEM
+        assert_eq!(pk, C::public_key_from_jwk(&C::public_key_to_jwk(&pk).unwrap()).unwrap());
+        assert_eq!(sk, C::secret_key_from_jwk(&C::secret_key_to_jwk(&sk).unwrap()).unwrap());
+        assert_eq!(pk, C::public_key_from_pem(&C::public_key_to_pem(&pk).unwrap()).unwrap());
+        assert_eq!(sk, C::secret_key_from_pem(&C::secret_key_to_pem(&sk).unwrap()).unwrap());
+
+        // a public key is no secret key, and vice versa
+        assert_eq!(Err(KeyEncodingError::MalformedJwk), C::secret_key_from_jwk(&C::public_key_to_jwk(&pk).unwrap()));
+        assert_eq!(Err(KeyEncodingError::MalformedPem), C::public_key_from_pem(&C::secret_key_to_pem(&sk).unwrap()));
+    }
+
+    #[test]
+    fn test_jubjub_encodings() {
+        check_encodings::<JubJub>(32);
+
+        let jwk = JubJub::secret_key_to_jwk(&ark_ed_on_bls12_381::Fr::rand(&mut test_rng())).unwrap();
+        let tampered = jwk.replace(JUBJUB_JWK_CURVE, "Ed25519");
+        assert_eq!(Err(KeyEncodingError::MalformedJwk), JubJub::secret_key_from_jwk(&tampered));
+
+        // the public key must match the secret key
+        let other = JubJub::public_key_to_jwk(&JubJub::generator().into_affine()).unwrap();
+        let x = |jwk: &str| serde_json::from_str::<OkpJwk>(jwk).unwrap().x;
+        let mismatched = jwk.replace(&x(&jwk), &x(&other));
+        assert_eq!(Err(KeyEncodingError::InvalidKey), JubJub::secret_key_from_jwk(&mismatched));
+    }
+
+    #[test]
+    fn test_secp256k1_encodings() {
+        check_encodings::<Secp256k1>(33);
+
+        // RFC 8812, Section 3.1 and the secp256k1 OID in the SPKI document
+        let pk = Secp256k1::generator().into_affine();
+        let jwk: serde_json::Value = serde_json::from_str(&Secp256k1::public_key_to_jwk(&pk).unwrap()).unwrap();
+        assert_eq!("EC", jwk["kty"]);
+        assert_eq!("secp256k1", jwk["crv"]);
+        assert_eq!("eb5mfvncu6xVoGKVzocLBwKb_NstzijZWfKBWxb4F5g", jwk["x"]);
+
+        let pem = Secp256k1::public_key_to_pem(&pk).unwrap();
+        assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----\nMFYwEAYHKoZIzj0CAQYFK4EEAAoDQgAE"));
+    }
+}
@@ -1,16 +1,20 @@
 use ark_crypto_primitives::Error;
-use ark_serialize::CanonicalSerialize;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use ark_std::hash::Hash;
 use ark_std::rand::Rng;
 
+pub mod bip340;
+pub mod bls;
+pub mod ed25519;
+pub mod encoding;
 pub mod schnorr;
 
 #[allow(dead_code)]
 pub trait SignatureScheme {
     type Parameters: Clone + Send + Sync;
-    type PublicKey: CanonicalSerialize + Hash + Eq + Clone + Default + Send + Sync;
-    type SecretKey: CanonicalSerialize + Clone + Default;
-    type Signature: CanonicalSerialize + Clone + Default + Send + Sync;
+    type PublicKey: CanonicalSerialize + CanonicalDeserialize + Hash + Eq + Clone + Default + Send + Sync;
+    type SecretKey: CanonicalSerialize + CanonicalDeserialize + Clone + Default;
+    type Signature: CanonicalSerialize + CanonicalDeserialize + Clone + Default + Send + Sync;
 
     fn setup<R: Rng>(rng: &mut R) -> Result<Self::Parameters, Error>;
 
@@ -32,11 +36,26 @@ pub trait SignatureScheme {
         message: &[u8],
         signature: &Self::Signature,
     ) -> Result<bool, Error>;
+
+    /// Verifies a batch of signatures, and returns the index of the first invalid one,
+    /// if any. Schemes that can check a batch faster than one at a time override this.
+    fn batch_verify<R: Rng>(
+        pp: &Self::Parameters,
+        batch: &[(&Self::PublicKey, &[u8], &Self::Signature)],
+        _rng: &mut R,
+    ) -> Result<Option<usize>, Error> {
+        for (i, (pk, message, signature)) in batch.iter().enumerate() {
+            if !Self::verify(pp, pk, message, signature)? {
+                return Ok(Some(i));
+            }
+        }
+        Ok(None)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::crypto::sig::{schnorr, *};
+    use crate::crypto::sig::{bip340, bls, ed25519, schnorr, *};
     use ark_ed_on_bls12_381::EdwardsProjective as JubJub;
     use ark_secp256k1::Projective as Secp256k1;
     use ark_std::test_rng;
@@ -45,8 +64,8 @@ mod test {
         let rng = &mut test_rng();
         let parameters = S::setup::<_>(rng).unwrap();
         let (pk, sk) = S::keygen(&parameters, rng).unwrap();
-        let sig = S::sign(&parameters, &sk, &message, rng).unwrap();
-        assert!(S::verify(&parameters, &pk, &message, &sig).unwrap());
+        let sig = S::sign(&parameters, &sk, message, rng).unwrap();
+        assert!(S::verify(&parameters, &pk, message, &sig).unwrap());
     }
 
     fn failed_verification<S: SignatureScheme>(message: &[u8], bad_message: &[u8]) {
@@ -57,6 +76,31 @@ mod test {
         assert!(!S::verify(&parameters, &pk, bad_message, &sig).unwrap());
     }
 
+    fn batch_verification<S: SignatureScheme>() {
+        let rng = &mut test_rng();
+        let parameters = S::setup::<_>(rng).unwrap();
+        let messages: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 16]).collect();
+        let keys: Vec<_> = messages.iter().map(|_| S::keygen(&parameters, rng).unwrap()).collect();
+        let sigs: Vec<_> = messages.iter().zip(&keys)
+            .map(|(message, (_, sk))| S::sign(&parameters, sk, message, rng).unwrap())
+            .collect();
+
+        let batch: Vec<_> = keys.iter().zip(&messages).zip(&sigs)
+            .map(|(((pk, _), message), sig)| (pk, message.as_slice(), sig))
+            .collect();
+        assert_eq!(None, S::batch_verify(&parameters, &batch, rng).unwrap());
+        assert_eq!(None, S::batch_verify(&parameters, &[], rng).unwrap());
+
+        // the invalid signature is named
+        let mut bad_batch = batch.clone();
+        bad_batch[5].1 = b"Bad message";
+        assert_eq!(Some(5), S::batch_verify(&parameters, &bad_batch, rng).unwrap());
+        let mut bad_batch = batch.clone();
+        bad_batch.swap(2, 3);
+        bad_batch[3].0 = bad_batch[2].0;
+        assert_eq!(Some(3), S::batch_verify(&parameters, &bad_batch, rng).unwrap());
+    }
+
     #[test]
     fn schnorr_signature_test_jubjub() {
         let message = "Hi, I am a Schnorr signature!";
@@ -76,4 +120,50 @@ mod test {
             "Bad message".as_bytes(),
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn schnorr_batch_verification_test() {
+        let message = "Hi, I am a Schnorr signature!";
+        sign_and_verify::<schnorr::BatchSchnorr<JubJub>>(message.as_bytes());
+        failed_verification::<schnorr::BatchSchnorr<JubJub>>(
+            message.as_bytes(),
+            "Bad message".as_bytes(),
+        );
+        batch_verification::<schnorr::BatchSchnorr<JubJub>>();
+        batch_verification::<schnorr::BatchSchnorr<Secp256k1>>();
+        batch_verification::<schnorr::Schnorr<JubJub>>();
+    }
+
+    #[test]
+    fn ed25519_signature_test() {
+        let message = "Hi, I am an Ed25519 signature!";
+        sign_and_verify::<ed25519::Ed25519>(message.as_bytes());
+        failed_verification::<ed25519::Ed25519>(
+            message.as_bytes(),
+            "Bad message".as_bytes(),
+        );
+        batch_verification::<ed25519::Ed25519>();
+    }
+
+    #[test]
+    fn bip340_signature_test() {
+        let message = "Hi, I am a BIP-340 signature!";
+        sign_and_verify::<bip340::Bip340>(message.as_bytes());
+        failed_verification::<bip340::Bip340>(
+            message.as_bytes(),
+            "Bad message".as_bytes(),
+        );
+        batch_verification::<bip340::Bip340>();
+    }
+
+    #[test]
+    fn bls_signature_test() {
+        let message = "Hi, I am a BLS signature!";
+        sign_and_verify::<bls::Bls>(message.as_bytes());
+        failed_verification::<bls::Bls>(
+            message.as_bytes(),
+            "Bad message".as_bytes(),
+        );
+        batch_verification::<bls::Bls>();
+    }
+}
@@ -7,9 +7,9 @@ use ark_std::ops::*;
 use ark_ec::{CurveConfig, CurveGroup};
 use ark_ff::{
     fields::PrimeField,
-    UniformRand,
+    UniformRand, Zero,
 };
-use ark_serialize::CanonicalSerialize;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate};
 use ark_std::rand::Rng;
 use ark_std::{hash::Hash, marker::PhantomData, vec::Vec};
 use blake2::Blake2s;
@@ -29,12 +29,44 @@ pub type PublicKey<C> = <C as CurveGroup>::Affine;
 
 pub type SecretKey<C> = <<C as CurveGroup>::Config as CurveConfig>::ScalarField;
 
-#[derive(Clone, Default, Debug, CanonicalSerialize)]
+#[derive(Clone, Default, Debug, PartialEq, Eq, CanonicalSerialize)]
 pub struct Signature<C: CurveGroup> {
     pub prover_response: C::ScalarField,
     pub verifier_challenge: [u8; 32],
 }
 
+impl<C: CurveGroup> Valid for Signature<C> {
+    fn check(&self) -> Result<(), SerializationError> {
+        self.prover_response.check()
+    }
+}
+
+impl<C: CurveGroup> CanonicalDeserialize for Signature<C> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let prover_response = C::ScalarField::deserialize_with_mode(&mut reader, compress, validate)?;
+        let verifier_challenge = super::encoding::read_bytes(&mut reader)?;
+        Ok(Signature { prover_response, verifier_challenge })
+    }
+}
+
+#[allow(dead_code)]
+impl<C: CurveGroup> Signature<C> {
+    /// Encodes the signature as the response scalar followed by the challenge;
+    /// see `super::encoding` for the lengths.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        super::encoding::to_bytes(self)
+    }
+
+    /// Decodes a signature, rejecting encodings of the wrong length.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        super::encoding::from_bytes(bytes)
+    }
+}
+
 impl<C: CurveGroup + Hash> SignatureScheme for Schnorr<C>
 where
     C::ScalarField: PrimeField,
@@ -89,19 +121,7 @@ where
 
             let public_key = parameters.generator.mul(sk).into();
             // Hash everything to get verifier challenge.
-            // e := H(salt || pubkey || r || msg);
-            let mut hash_input = Vec::new();
-            if parameters.salt != None {
-               parameters.salt.serialize_compressed(&mut hash_input)?;
-            }
-            public_key.serialize_compressed(&mut hash_input)?;
-            prover_commitment.serialize_compressed(&mut hash_input)?;
-            message.serialize_compressed(&mut hash_input)?;
-
-            let hash_digest = Blake2s::digest(&hash_input);
-            assert!(hash_digest.len() >= 32);
-            let mut verifier_challenge = [0u8; 32];
-            verifier_challenge.copy_from_slice(&hash_digest.as_slice());
+            let verifier_challenge = challenge::<C>(parameters, &public_key, &prover_commitment, message)?;
 
             (random_scalar, verifier_challenge)
         };
@@ -140,20 +160,146 @@ where
         claimed_prover_commitment += &public_key_times_verifier_challenge;
         let claimed_prover_commitment = claimed_prover_commitment.into_affine();
 
-        // e := H(salt || pubkey || r || msg)
-        let mut hash_input = Vec::new();
-        if parameters.salt != None {
-            hash_input.extend_from_slice(&parameters.salt.unwrap());
-        }
-        pk.serialize_compressed(&mut hash_input)?;
-        claimed_prover_commitment.serialize_compressed(&mut hash_input)?;
-        message.serialize_compressed(&mut hash_input)?;
-
-        // cast the hash output to get e
-        let obtained_verifier_challenge = &Blake2s::digest(&hash_input)[..];
+        let obtained_verifier_challenge = challenge::<C>(parameters, pk, &claimed_prover_commitment, message)?;
         // end_timer!(verify_time);
         // The signature is valid iff the computed verifier challenge is the same as the one
         // provided in the signature
-        Ok(verifier_challenge == obtained_verifier_challenge)
+        Ok(*verifier_challenge == obtained_verifier_challenge)
+    }
+}
+
+/// e := H(salt || pubkey || r || msg)
+fn challenge<C: CurveGroup>(
+    parameters: &Parameters<C>,
+    pk: &C::Affine,
+    prover_commitment: &C::Affine,
+    message: &[u8],
+) -> Result<[u8; 32], Error> {
+    let mut hash_input = Vec::new();
+    if let Some(salt) = parameters.salt {
+        hash_input.extend_from_slice(&salt);
+    }
+    pk.serialize_compressed(&mut hash_input)?;
+    prover_commitment.serialize_compressed(&mut hash_input)?;
+    message.serialize_compressed(&mut hash_input)?;
+
+    let hash_digest = Blake2s::digest(&hash_input);
+    let mut verifier_challenge = [0u8; 32];
+    verifier_challenge.copy_from_slice(&hash_digest[..32]);
+    Ok(verifier_challenge)
+}
+
+/// The same Schnorr signatures, in the form that carries the prover's commitment `r`
+/// rather than the challenge, which the verifier recomputes. Checking `sG + eY = r`
+/// is linear in the points, so a batch of signatures is checked at once by a random
+/// linear combination of these equations, in a single multi-scalar multiplication.
+pub struct BatchSchnorr<C: CurveGroup> {
+    _group: PhantomData<C>,
+}
+
+#[derive(Clone, Default, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct CommittedSignature<C: CurveGroup> {
+    pub prover_commitment: C::Affine,
+    pub prover_response: C::ScalarField,
+}
+
+#[allow(dead_code)]
+impl<C: CurveGroup> CommittedSignature<C> {
+    /// Encodes the signature as the commitment followed by the response scalar;
+    /// see `super::encoding` for the lengths.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        super::encoding::to_bytes(self)
+    }
+
+    /// Decodes a signature, rejecting encodings of the wrong length.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SerializationError> {
+        super::encoding::from_bytes(bytes)
+    }
+}
+
+impl<C: CurveGroup + Hash> SignatureScheme for BatchSchnorr<C>
+where
+    C::ScalarField: PrimeField,
+{
+    type Parameters = Parameters<C>;
+    type PublicKey = PublicKey<C>;
+    type SecretKey = SecretKey<C>;
+    type Signature = CommittedSignature<C>;
+
+    fn setup<R: Rng>(rng: &mut R) -> Result<Self::Parameters, Error> {
+        Schnorr::<C>::setup(rng)
+    }
+
+    fn keygen<R: Rng>(
+        parameters: &Self::Parameters,
+        rng: &mut R,
+    ) -> Result<(Self::PublicKey, Self::SecretKey), Error> {
+        Schnorr::<C>::keygen(parameters, rng)
+    }
+
+    fn sign<R: Rng>(
+        parameters: &Self::Parameters,
+        sk: &Self::SecretKey,
+        message: &[u8],
+        rng: &mut R,
+    ) -> Result<Self::Signature, Error> {
+        let random_scalar = C::ScalarField::rand(rng);
+        let prover_commitment = parameters.generator.mul(random_scalar).into_affine();
+
+        let public_key = parameters.generator.mul(sk).into_affine();
+        let verifier_challenge = challenge::<C>(parameters, &public_key, &prover_commitment, message)?;
+        let verifier_challenge_fe = C::ScalarField::from_le_bytes_mod_order(&verifier_challenge);
+
+        // k - xe;
+        let prover_response = random_scalar - (verifier_challenge_fe * sk);
+        Ok(CommittedSignature { prover_commitment, prover_response })
+    }
+
+    fn verify(
+        parameters: &Self::Parameters,
+        pk: &Self::PublicKey,
+        message: &[u8],
+        signature: &Self::Signature,
+    ) -> Result<bool, Error> {
+        let verifier_challenge = challenge::<C>(parameters, pk, &signature.prover_commitment, message)?;
+        let verifier_challenge_fe = C::ScalarField::from_le_bytes_mod_order(&verifier_challenge);
+
+        // sG + eY = kG
+        let claimed_prover_commitment = parameters.generator.mul(signature.prover_response) + pk.mul(verifier_challenge_fe);
+        Ok(claimed_prover_commitment.into_affine() == signature.prover_commitment)
+    }
+
+    fn batch_verify<R: Rng>(
+        parameters: &Self::Parameters,
+        batch: &[(&Self::PublicKey, &[u8], &Self::Signature)],
+        rng: &mut R,
+    ) -> Result<Option<usize>, Error> {
+        // sum_i z_i (s_i G + e_i Y_i - r_i) = 0 for random z_i, which fails
+        // with negligible probability unless every equation holds
+        let mut bases = vec![parameters.generator];
+        let mut scalars = vec![C::ScalarField::zero()];
+        for (pk, message, signature) in batch {
+            let verifier_challenge = challenge::<C>(parameters, pk, &signature.prover_commitment, message)?;
+            let verifier_challenge_fe = C::ScalarField::from_le_bytes_mod_order(&verifier_challenge);
+            let z = C::ScalarField::rand(rng);
+
+            scalars[0] += z * signature.prover_response;
+            bases.push(**pk);
+            scalars.push(z * verifier_challenge_fe);
+            bases.push(signature.prover_commitment);
+            scalars.push(-z);
+        }
+
+        if C::msm_unchecked(&bases, &scalars).is_zero() {
+            return Ok(None);
+        }
+
+        // some signature is invalid, so look for it
+        for (i, (pk, message, signature)) in batch.iter().enumerate() {
+            if !Self::verify(parameters, pk, message, signature)? {
+                return Ok(Some(i));
+            }
+        }
+        Ok(None)
     }
 }
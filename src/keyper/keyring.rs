@@ -0,0 +1,65 @@
+//! Key epochs of a keyper. New vaults are always created under the current key, while
+//! retired keys keep answering reconstruct requests for a grace period, during which
+//! clients re-run keygen to move their vaults to the current key.
+
+use std::time::SystemTime;
+
+use super::ServerKey;
+
+#[derive(Clone, Debug)]
+pub struct KeyRing {
+    current: ServerKey,
+    /// earlier keys, each with the time after which it is no longer served
+    retired: Vec<(ServerKey, SystemTime)>,
+}
+
+impl KeyRing {
+    pub fn new(current: ServerKey) -> Self {
+        KeyRing { current, retired: vec![] }
+    }
+
+    /// Keeps serving `key` to reconstruct requests until `expires_at`.
+    pub fn retire(mut self, key: ServerKey, expires_at: SystemTime) -> Self {
+        self.retired.push((key, expires_at));
+        self
+    }
+
+    /// The key under which keygen requests are answered.
+    pub fn current(&self) -> &ServerKey {
+        &self.current
+    }
+
+    /// The key of the given version, unless it has been retired for longer than its grace period.
+    pub fn get(&self, version: u32, now: SystemTime) -> Option<&ServerKey> {
+        if version == self.current.version {
+            return Some(&self.current);
+        }
+
+        self.retired
+            .iter()
+            .find(|(key, expires_at)| key.version == version && now < *expires_at)
+            .map(|(key, _)| key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_grace_period() {
+        let now = SystemTime::now();
+        let keys = KeyRing::new(ServerKey::new(2, [2u8; 32]))
+            .retire(ServerKey::new(1, [1u8; 32]), now + Duration::from_secs(60));
+
+        assert_eq!(2, keys.current().version);
+        assert_eq!(Some(&ServerKey::new(2, [2u8; 32])), keys.get(2, now));
+        assert_eq!(Some(&ServerKey::new(1, [1u8; 32])), keys.get(1, now));
+
+        // once the grace period is over, only the current key is served
+        assert_eq!(None, keys.get(1, now + Duration::from_secs(60)));
+        assert!(keys.get(2, now + Duration::from_secs(60)).is_some());
+        assert_eq!(None, keys.get(3, now));
+    }
+}
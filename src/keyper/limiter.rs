@@ -0,0 +1,540 @@
+//! Per-client guess limiting for the keyper. The keyper cannot tell a wrong PIN from a
+//! right one, so every PRF evaluation for a client counts as a failed guess, until the
+//! client proves a successful reconstruction by presenting its reset token. Consecutive
+//! failures are throttled with exponential backoff, and lock the client out altogether
+//! once they reach the configured maximum, until an administrator unlocks it.
+//!
+//! Verifiers of reset tokens are only registered in the wake of a keygen, and replacing
+//! one takes the current token. Keypers persist the records of registered clients, lest a
+//! restart hand out fresh guesses, and let anyone register a verifier for a client whose
+//! vault exists.
+//!
+//! Anyone can make up client ids, so the records of clients that have not registered a
+//! verifier are only kept in memory, and only up to a bound, beyond which the ones whose
+//! backoff and registration window have passed are dropped first, then the oldest. Changes
+//! to the persisted records are appended to a journal, which is folded into the file of
+//! records once it outgrows it, rather than rewriting the file on every request.
+
+use std::{collections::HashMap, fmt, fs, io::Write, path::{Path, PathBuf}, time::{Duration, SystemTime}};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LimiterConfig {
+    /// number of consecutive failed guesses after which the client is locked out
+    pub max_failures: u32,
+    /// delay imposed after the first failure, doubling with each further failure
+    pub backoff_secs: u64,
+    /// upper bound on the delay between two guesses
+    pub max_backoff_secs: u64,
+    /// time after a keygen during which the client may register the verifier of its reset token
+    pub registration_window_secs: u64,
+    /// number of clients without a registered verifier whose records are kept in memory
+    pub max_unregistered_clients: usize,
+}
+
+impl Default for LimiterConfig {
+    fn default() -> Self {
+        LimiterConfig {
+            max_failures: 10,
+            backoff_secs: 1,
+            max_backoff_secs: 3600,
+            registration_window_secs: 300,
+            max_unregistered_clients: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LimiterError {
+    /// the client has exhausted its guesses and must be unlocked by an administrator
+    Locked,
+    /// the client must wait for the given duration before guessing again
+    Backoff(Duration),
+    /// the reset token does not match the verifier registered for the client
+    InvalidToken,
+    /// the client has not run keygen recently, so it cannot register a verifier
+    RegistrationClosed,
+    /// the records could not be read from or written to their file
+    Storage(String),
+}
+
+impl std::error::Error for LimiterError {}
+
+impl fmt::Display for LimiterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LimiterError::Locked =>
+                write!(f, "Too many failed guesses, the vault is locked."),
+            LimiterError::Backoff(ref delay) =>
+                write!(f, "Too many failed guesses, retry in {} seconds.", delay.as_secs().max(1)),
+            LimiterError::InvalidToken =>
+                write!(f, "The reset token does not match the registered verifier."),
+            LimiterError::RegistrationClosed =>
+                write!(f, "A verifier can only be registered right after a keygen."),
+            LimiterError::Storage(ref e) =>
+                write!(f, "Failed to access the guess limiter's records: {}", e),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct AttemptRecord {
+    failures: u32,
+    last_attempt: Option<SystemTime>,
+    /// hash of the token the client presents after a successful reconstruction
+    verifier: Option<[u8; 32]>,
+    /// end of the window in which the client may register a verifier, opened by a keygen
+    registration_open_until: Option<SystemTime>,
+}
+
+impl AttemptRecord {
+    /// Time after which the record of an unregistered client holds nothing worth keeping:
+    /// the client is no longer backing off, and can no longer register a verifier.
+    fn expires_at(&self, config: &LimiterConfig) -> SystemTime {
+        let ready_at = self.last_attempt.map(|last_attempt| last_attempt + backoff(config, self.failures));
+        ready_at.max(self.registration_open_until).unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+}
+
+/// The journal is folded into the file of records once it has at least this many entries,
+/// and at least as many as there are records.
+const MIN_JOURNAL_ENTRIES: usize = 1024;
+
+#[derive(Debug, Default)]
+pub struct GuessLimiter {
+    config: LimiterConfig,
+    /// records of the clients that have registered a verifier
+    records: HashMap<Vec<u8>, AttemptRecord>,
+    /// records of the clients that have not, which are never persisted
+    unregistered: HashMap<Vec<u8>, AttemptRecord>,
+    /// file the records are written to, if they are persisted
+    path: Option<PathBuf>,
+    /// number of entries in the journal next to the file
+    journal_entries: usize,
+}
+
+impl GuessLimiter {
+    /// A limiter whose records are only kept in memory, and lost when the keyper stops.
+    pub fn new(config: LimiterConfig) -> Self {
+        GuessLimiter { config, ..GuessLimiter::default() }
+    }
+
+    /// A limiter whose records are kept in the given file, starting from its contents
+    /// and those of its journal if they exist.
+    pub fn open(config: LimiterConfig, path: impl AsRef<Path>) -> Result<Self, LimiterError> {
+        let path = path.as_ref().to_path_buf();
+        let mut records: HashMap<Vec<u8>, AttemptRecord> = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice::<Vec<(Vec<u8>, AttemptRecord)>>(&contents)
+                .map_err(|e| LimiterError::Storage(e.to_string()))?
+                .into_iter()
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(LimiterError::Storage(e.to_string())),
+        };
+
+        // replay the changes made since the file was written; a crash may have cut the last one short
+        let journal = match fs::read_to_string(journal_path(&path)) {
+            Ok(journal) => journal,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(LimiterError::Storage(e.to_string())),
+        };
+        let lines: Vec<&str> = journal.lines().collect();
+        for (i, line) in lines.iter().enumerate() {
+            match serde_json::from_str::<(Vec<u8>, AttemptRecord)>(line) {
+                Ok((client_id, record)) => { records.insert(client_id, record); }
+                Err(_) if i + 1 == lines.len() => (),
+                Err(e) => return Err(LimiterError::Storage(e.to_string())),
+            }
+        }
+
+        // files written before unregistered clients were kept apart may still hold some
+        let (records, unregistered) = records.into_iter().partition(|(_, record)| record.verifier.is_some());
+
+        // start from an empty journal, rather than append to a cut short entry
+        let mut limiter = GuessLimiter { config, records, unregistered, path: Some(path), journal_entries: 0 };
+        if !lines.is_empty() {
+            limiter.compact()?;
+        }
+        Ok(limiter)
+    }
+
+    pub fn config(&self) -> &LimiterConfig {
+        &self.config
+    }
+
+    /// Admits a guess by the client if it is neither locked out nor backing off,
+    /// and records the guess as a failure until the client reports otherwise.
+    pub fn record_attempt(&mut self, client_id: &[u8], now: SystemTime) -> Result<(), LimiterError> {
+        if let Some(record) = self.records.get_mut(client_id) {
+            admit(&self.config, record, now)?;
+            let record = record.clone();
+            return self.save(client_id, &record);
+        }
+
+        let config = self.config.clone();
+        admit(&config, self.unregistered_record(client_id, now), now)
+    }
+
+    /// Lets the client register a verifier for a while; called after an admitted keygen,
+    /// which is the only way to open the registration.
+    pub fn open_registration(&mut self, client_id: &[u8], now: SystemTime) -> Result<(), LimiterError> {
+        let open_until = Some(now + Duration::from_secs(self.config.registration_window_secs));
+
+        if let Some(record) = self.records.get_mut(client_id) {
+            record.registration_open_until = open_until;
+            let record = record.clone();
+            return self.save(client_id, &record);
+        }
+
+        self.unregistered_record(client_id, now).registration_open_until = open_until;
+        Ok(())
+    }
+
+    /// Registers the verifier against which the client's reset tokens are checked, closing
+    /// the registration opened by its keygen. Replacing an existing verifier requires the
+    /// token matching it. The failure count is left alone, for the client to reset with
+    /// its new token.
+    pub fn register(
+        &mut self,
+        client_id: &[u8],
+        verifier: [u8; 32],
+        current_token: Option<&[u8]>,
+        now: SystemTime,
+    ) -> Result<(), LimiterError> {
+        let record = match self.records.get_mut(client_id) {
+            Some(record) => record,
+            None => self.unregistered.get_mut(client_id).ok_or(LimiterError::RegistrationClosed)?,
+        };
+
+        match record.registration_open_until {
+            Some(open_until) if now < open_until => (),
+            _ => return Err(LimiterError::RegistrationClosed),
+        }
+
+        if let Some(registered) = record.verifier {
+            if current_token.map(token_verifier) != Some(registered) {
+                return Err(LimiterError::InvalidToken);
+            }
+        }
+
+        record.verifier = Some(verifier);
+        record.registration_open_until = None;
+
+        // the client's record is persisted from now on
+        let record = record.clone();
+        self.unregistered.remove(client_id);
+        self.records.insert(client_id.to_vec(), record.clone());
+        self.save(client_id, &record)
+    }
+
+    /// Resets the failure count of a client that proves a successful reconstruction.
+    pub fn report_success(&mut self, client_id: &[u8], token: &[u8]) -> Result<(), LimiterError> {
+        let record = self.records.get_mut(client_id).ok_or(LimiterError::InvalidToken)?;
+
+        if record.verifier != Some(token_verifier(token)) {
+            return Err(LimiterError::InvalidToken);
+        }
+
+        record.failures = 0;
+        record.last_attempt = None;
+        let record = record.clone();
+        self.save(client_id, &record)
+    }
+
+    /// Administrative override that lifts a lockout or backoff.
+    pub fn unlock(&mut self, client_id: &[u8]) -> Result<(), LimiterError> {
+        if let Some(record) = self.unregistered.get_mut(client_id) {
+            record.failures = 0;
+            record.last_attempt = None;
+        }
+
+        if let Some(record) = self.records.get_mut(client_id) {
+            record.failures = 0;
+            record.last_attempt = None;
+            let record = record.clone();
+            return self.save(client_id, &record);
+        }
+        Ok(())
+    }
+
+    /// Returns the record of a client without a registered verifier, making room for it
+    /// if it is new and the bound is reached.
+    fn unregistered_record(&mut self, client_id: &[u8], now: SystemTime) -> &mut AttemptRecord {
+        if !self.unregistered.contains_key(client_id) && self.unregistered.len() >= self.config.max_unregistered_clients {
+            let config = &self.config;
+            self.unregistered.retain(|_, record| record.expires_at(config) > now);
+
+            if self.unregistered.len() >= config.max_unregistered_clients {
+                let oldest = self.unregistered.iter()
+                    .min_by_key(|(_, record)| record.expires_at(config))
+                    .map(|(client_id, _)| client_id.clone());
+                if let Some(oldest) = oldest {
+                    self.unregistered.remove(&oldest);
+                }
+            }
+        }
+
+        self.unregistered.entry(client_id.to_vec()).or_default()
+    }
+
+    /// Appends the client's new record to the journal, if the records are persisted,
+    /// or folds the journal into the file of records once it has grown too long.
+    fn save(&mut self, client_id: &[u8], record: &AttemptRecord) -> Result<(), LimiterError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if self.journal_entries >= self.records.len().max(MIN_JOURNAL_ENTRIES) {
+            return self.compact();
+        }
+
+        let mut entry = serde_json::to_vec(&(client_id, record)).map_err(|e| LimiterError::Storage(e.to_string()))?;
+        entry.push(b'\n');
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(journal_path(path))
+            .and_then(|mut journal| journal.write_all(&entry))
+            .map_err(|e| LimiterError::Storage(e.to_string()))?;
+
+        self.journal_entries += 1;
+        Ok(())
+    }
+
+    /// Writes the records to their file, replacing it only once they are written out,
+    /// and empties the journal, whose changes the file then holds.
+    fn compact(&mut self) -> Result<(), LimiterError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let records: Vec<_> = self.records.iter().collect();
+        let contents = serde_json::to_vec(&records).map_err(|e| LimiterError::Storage(e.to_string()))?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, contents)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .and_then(|_| fs::write(journal_path(path), []))
+            .map_err(|e| LimiterError::Storage(e.to_string()))?;
+
+        self.journal_entries = 0;
+        Ok(())
+    }
+}
+
+/// Admits a guess if the client is neither locked out nor backing off, and counts it as a failure.
+fn admit(config: &LimiterConfig, record: &mut AttemptRecord, now: SystemTime) -> Result<(), LimiterError> {
+    if record.failures >= config.max_failures {
+        return Err(LimiterError::Locked);
+    }
+
+    if let Some(last_attempt) = record.last_attempt {
+        let ready_at = last_attempt + backoff(config, record.failures);
+        if now < ready_at {
+            return Err(LimiterError::Backoff(ready_at.duration_since(now).unwrap_or_default()));
+        }
+    }
+
+    record.failures += 1;
+    record.last_attempt = Some(now);
+    Ok(())
+}
+
+/// The journal of changes is kept next to the file of records.
+fn journal_path(path: &Path) -> PathBuf {
+    path.with_extension("journal")
+}
+
+/// The keyper only stores the hash of a client's reset token.
+pub fn token_verifier(token: &[u8]) -> [u8; 32] {
+    Sha256::digest(token).into()
+}
+
+fn backoff(config: &LimiterConfig, failures: u32) -> Duration {
+    if failures == 0 {
+        return Duration::ZERO;
+    }
+
+    let delay = config.backoff_secs
+        .saturating_mul(1u64.checked_shl(failures - 1).unwrap_or(u64::MAX))
+        .min(config.max_backoff_secs);
+    Duration::from_secs(delay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LimiterConfig {
+        LimiterConfig {
+            max_failures: 3,
+            backoff_secs: 10,
+            max_backoff_secs: 3600,
+            registration_window_secs: 60,
+            max_unregistered_clients: 2,
+        }
+    }
+
+    fn limiter() -> GuessLimiter {
+        GuessLimiter::new(config())
+    }
+
+    #[test]
+    fn test_backoff_and_lockout() {
+        let mut limiter = limiter();
+        let client = b"alice@gmail.com";
+        let start = SystemTime::now();
+
+        assert_eq!(Ok(()), limiter.record_attempt(client, start));
+
+        // the second guess must wait for 10 seconds, the third for 20 seconds
+        assert_eq!(Err(LimiterError::Backoff(Duration::from_secs(10))), limiter.record_attempt(client, start));
+        assert_eq!(Ok(()), limiter.record_attempt(client, start + Duration::from_secs(10)));
+        assert!(limiter.record_attempt(client, start + Duration::from_secs(20)).is_err());
+        assert_eq!(Ok(()), limiter.record_attempt(client, start + Duration::from_secs(30)));
+
+        // the client is now locked out, regardless of how long it waits
+        assert_eq!(Err(LimiterError::Locked), limiter.record_attempt(client, start + Duration::from_secs(1 << 20)));
+
+        // other clients are unaffected
+        assert_eq!(Ok(()), limiter.record_attempt(b"bob@gmail.com", start));
+
+        limiter.unlock(client).unwrap();
+        assert_eq!(Ok(()), limiter.record_attempt(client, start + Duration::from_secs(1 << 20)));
+    }
+
+    #[test]
+    fn test_report_success() {
+        let mut limiter = limiter();
+        let client = b"alice@gmail.com";
+        let token = b"token";
+        let start = SystemTime::now();
+
+        limiter.record_attempt(client, start).unwrap();
+        limiter.open_registration(client, start).unwrap();
+        limiter.register(client, token_verifier(token), None, start).unwrap();
+
+        // only the registered token resets the failure count
+        assert_eq!(Err(LimiterError::InvalidToken), limiter.report_success(client, b"guess"));
+        assert!(limiter.record_attempt(client, start).is_err());
+        limiter.report_success(client, token).unwrap();
+        assert_eq!(Ok(()), limiter.record_attempt(client, start));
+
+        // the verifier can only be replaced by presenting the current token
+        limiter.open_registration(client, start).unwrap();
+        assert_eq!(Err(LimiterError::InvalidToken), limiter.register(client, token_verifier(b"new"), None, start));
+        limiter.register(client, token_verifier(b"new"), Some(token), start).unwrap();
+        limiter.report_success(client, b"new").unwrap();
+    }
+
+    #[test]
+    fn test_registration_window() {
+        let mut limiter = limiter();
+        let client = b"alice@gmail.com";
+        let start = SystemTime::now();
+
+        // no verifier can be registered without a keygen, not even the first one
+        assert_eq!(Err(LimiterError::RegistrationClosed), limiter.register(client, [0u8; 32], None, start));
+        limiter.record_attempt(client, start).unwrap();
+        assert_eq!(Err(LimiterError::RegistrationClosed), limiter.register(client, [0u8; 32], None, start));
+
+        // a keygen opens the registration for a minute, and registering closes it
+        limiter.open_registration(client, start).unwrap();
+        let late = start + Duration::from_secs(60);
+        assert_eq!(Err(LimiterError::RegistrationClosed), limiter.register(client, [0u8; 32], None, late));
+        limiter.register(client, [0u8; 32], None, start).unwrap();
+        assert_eq!(Err(LimiterError::RegistrationClosed), limiter.register(client, [1u8; 32], None, start));
+
+        // registering does not reset the failure count
+        assert!(matches!(limiter.record_attempt(client, start), Err(LimiterError::Backoff(_))));
+    }
+
+    #[test]
+    fn test_persistence() {
+        let path = std::env::temp_dir().join(format!("bedrock-limiter-{}.json", std::process::id()));
+        let client = b"alice@gmail.com";
+        let token = b"token";
+        let start = SystemTime::now();
+
+        let mut limiter = GuessLimiter::open(config(), &path).unwrap();
+        limiter.record_attempt(client, start).unwrap();
+        limiter.open_registration(client, start).unwrap();
+        limiter.register(client, token_verifier(token), None, start).unwrap();
+
+        // the guesses of clients that have not registered are not written out
+        limiter.record_attempt(b"bob@gmail.com", start).unwrap();
+
+        // a restarted keyper still knows the failure count and the verifier
+        let mut limiter = GuessLimiter::open(config(), &path).unwrap();
+        assert!(limiter.record_attempt(client, start).is_err());
+        assert_eq!(Ok(()), limiter.record_attempt(b"bob@gmail.com", start));
+        limiter.open_registration(client, start).unwrap();
+        assert_eq!(Err(LimiterError::InvalidToken), limiter.register(client, [0u8; 32], None, start));
+        limiter.report_success(client, token).unwrap();
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(journal_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn test_journal() {
+        let path = std::env::temp_dir().join(format!("bedrock-limiter-journal-{}.json", std::process::id()));
+        let client = b"alice@gmail.com";
+        let config = LimiterConfig { max_failures: u32::MAX, backoff_secs: 0, ..config() };
+        let start = SystemTime::now();
+
+        let mut limiter = GuessLimiter::open(config.clone(), &path).unwrap();
+        limiter.record_attempt(client, start).unwrap();
+        limiter.open_registration(client, start).unwrap();
+        limiter.register(client, token_verifier(b"token"), None, start).unwrap();
+
+        // changes are appended to the journal, until it is folded into the file of records
+        for _ in 0..MIN_JOURNAL_ENTRIES {
+            limiter.record_attempt(client, start).unwrap();
+        }
+        assert_eq!(0, limiter.journal_entries);
+        assert!(path.exists());
+        limiter.record_attempt(client, start).unwrap();
+
+        // a crash may leave a partly written entry behind, which is dropped on restart
+        fs::OpenOptions::new().append(true).open(journal_path(&path)).unwrap().write_all(b"[[97],{\"fail").unwrap();
+        let mut limiter = GuessLimiter::open(config.clone(), &path).unwrap();
+        let failures = MIN_JOURNAL_ENTRIES as u32 + 2;
+        assert_eq!(failures, limiter.records[client.as_slice()].failures);
+
+        limiter.record_attempt(client, start).unwrap();
+        let limiter = GuessLimiter::open(config, &path).unwrap();
+        assert_eq!(failures + 1, limiter.records[client.as_slice()].failures);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(journal_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn test_unregistered_clients() {
+        let mut limiter = limiter();
+        let start = SystemTime::now();
+
+        // registered clients are kept whatever their number, unregistered ones up to the bound
+        limiter.record_attempt(b"alice@gmail.com", start).unwrap();
+        limiter.open_registration(b"alice@gmail.com", start).unwrap();
+        limiter.register(b"alice@gmail.com", token_verifier(b"token"), None, start).unwrap();
+        limiter.record_attempt(b"bob@gmail.com", start).unwrap();
+        limiter.record_attempt(b"carol@gmail.com", start + Duration::from_secs(1)).unwrap();
+        assert_eq!(2, limiter.unregistered.len());
+
+        // the oldest record makes room for a new client while the others are still backing off
+        limiter.record_attempt(b"dave@gmail.com", start + Duration::from_secs(2)).unwrap();
+        assert!(!limiter.unregistered.contains_key(b"bob@gmail.com".as_slice()));
+        assert!(limiter.record_attempt(b"carol@gmail.com", start + Duration::from_secs(2)).is_err());
+
+        // records whose backoff has passed go first
+        limiter.record_attempt(b"carol@gmail.com", start + Duration::from_secs(11)).unwrap();
+        limiter.record_attempt(b"erin@gmail.com", start + Duration::from_secs(12)).unwrap();
+        assert!(!limiter.unregistered.contains_key(b"dave@gmail.com".as_slice()));
+        assert!(limiter.unregistered.contains_key(b"carol@gmail.com".as_slice()));
+        assert!(limiter.records.contains_key(b"alice@gmail.com".as_slice()));
+    }
+}
@@ -0,0 +1,563 @@
+//! Server side of the PPSS protocol: a keyper holds a versioned 256-bit seed, from which
+//! it derives a per-client PRF key, and answers the clients' blinded keygen and reconstruct requests.
+//! Every evaluation counts as a guess against the client's limit (see `limiter`). Seeds are
+//! rotated by keeping the previous ones around for a grace period (see `keyring`).
+//! Keypers may also demand a proof of work with every evaluation (see `puzzle`), and may
+//! leave guess limiting to the storage service, which pays for reconstructions with tokens
+//! (see `tokens`).
+
+use std::{error::Error, fs, net::SocketAddr, path::{Path, PathBuf}, sync::{Arc, Mutex}, time::{Duration, SystemTime}};
+
+use ark_serialize::*;
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE}, Engine as _};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use warp::{http::StatusCode, reply::Reply, Filter};
+
+use crate::crypto::ppss::{*, jkkx16::*, suite::*};
+use crate::vault::Curve;
+
+pub mod keyring;
+pub mod limiter;
+pub mod puzzle;
+pub mod tokens;
+
+pub use crate::crypto::ppss::ServerKey;
+use keyring::KeyRing;
+use limiter::{GuessLimiter, LimiterConfig, LimiterError};
+use puzzle::{PuzzleConfig, PuzzleError, PuzzleIssuer};
+use tokens::{TokenConfig, TokenError};
+
+/// Contents of the keyper's configuration file, which is written in TOML:
+/// ```toml
+/// listen = "127.0.0.1:3030"
+/// seed = "<base64 encoding of 32 random bytes>"
+/// key_version = 1
+/// admin_token = "<secret required to unlock clients>"
+/// records = "/var/lib/bedrock-keyper/records.json"
+///
+/// [[retired_keys]]
+/// version = 0
+/// seed = "<base64 encoding of the previous seed>"
+/// expires_at = 1767225600
+///
+/// [limits]
+/// max_failures = 10
+/// backoff_secs = 1
+/// max_backoff_secs = 3600
+/// registration_window_secs = 300
+///
+/// [puzzle]
+/// difficulty = 16
+/// challenge_ttl_secs = 300
+///
+/// [tokens]
+/// seed = "<base64 encoding of the token seed shared with the storage service>"
+/// key_version = 1
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct KeyperConfig {
+    /// address and port the keyper serves requests on
+    pub listen: SocketAddr,
+    /// base64 encoding of the keyper's 32-byte secret seed
+    pub seed: String,
+    /// version of the seed, recorded in vaults so the seed can later be rotated
+    #[serde(default = "default_key_version")]
+    pub key_version: u32,
+    /// secret that authorizes unlock requests; unlocking is disabled without it
+    pub admin_token: Option<String>,
+    /// file in which the keyper keeps its clients' guess counts and verifiers across restarts
+    pub records: PathBuf,
+    /// previous seeds, still served to reconstruct requests until they expire
+    #[serde(default)]
+    pub retired_keys: Vec<RetiredKeyConfig>,
+    /// guess limiting policy
+    #[serde(default)]
+    pub limits: LimiterConfig,
+    /// proof-of-work policy; disabled unless a difficulty is set
+    #[serde(default)]
+    pub puzzle: PuzzleConfig,
+    /// token key; if set, reconstruct requests are paid for with tokens instead of being limited
+    pub tokens: Option<TokenConfig>,
+}
+
+/// A seed that has been rotated out, but is kept until its vaults are re-encrypted.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RetiredKeyConfig {
+    pub version: u32,
+    /// base64 encoding of the 32-byte seed
+    pub seed: String,
+    /// end of the grace period, in seconds since the Unix epoch
+    pub expires_at: u64,
+}
+
+fn default_key_version() -> u32 {
+    1
+}
+
+impl KeyperConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+}
+
+fn decode_seed(seed: &str) -> Result<[u8; 32], Box<dyn Error>> {
+    let seed = STANDARD.decode(seed.as_bytes())?
+        .try_into()
+        .map_err(|_| "keyper seed must be exactly 32 bytes")?;
+    Ok(seed)
+}
+
+/// The PRF routes carry the name of the curve as a suffix, e.g. `keygen-p256`,
+/// except for BLS12-381, which uses the plain names that predate the choice of curves.
+pub const KEYGEN_ROUTE: &str = "keygen";
+pub const RECONSTRUCT_ROUTE: &str = "reconstruct";
+pub const REGISTER_ROUTE: &str = "register";
+pub const SUCCESS_ROUTE: &str = "success";
+pub const UNLOCK_ROUTE: &str = "unlock";
+pub const CHALLENGE_ROUTE: &str = "challenge";
+pub const TOKEN_KEY_ROUTE: &str = "token_key";
+
+/// Registers the verifier of the client's reset token; `current_token` must hold
+/// the token matching the previously registered verifier, if there is one.
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct RegisterRequest {
+    pub client_id: Vec<u8>,
+    pub verifier: Vec<u8>,
+    pub current_token: Vec<u8>,
+}
+
+/// Sent by a client after a successful reconstruction to reset its failure count.
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct SuccessReport {
+    pub client_id: Vec<u8>,
+    pub token: Vec<u8>,
+}
+
+/// Sent by an administrator to lift a client's lockout.
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct UnlockRequest {
+    pub client_id: Vec<u8>,
+    pub admin_token: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct Keyper {
+    keys: KeyRing,
+    admin_token: Option<String>,
+    limiter: Arc<Mutex<GuessLimiter>>,
+    puzzles: PuzzleIssuer,
+    /// the token key, if reconstruct requests must carry tokens
+    token_key: Option<ServerKey>,
+}
+
+impl Keyper {
+    pub fn new(key: ServerKey) -> Self {
+        Keyper::with_limits(KeyRing::new(key), LimiterConfig::default(), None)
+    }
+
+    pub fn with_limits(keys: KeyRing, limits: LimiterConfig, admin_token: Option<String>) -> Self {
+        Keyper {
+            puzzles: PuzzleIssuer::new(PuzzleConfig::default(), keys.current()),
+            keys,
+            admin_token,
+            limiter: Arc::new(Mutex::new(GuessLimiter::new(limits))),
+            token_key: None,
+        }
+    }
+
+    /// Keeps the clients' guess counts and verifiers in the given file, starting from its
+    /// contents; without it, a restart forgets them.
+    pub fn with_records(self, path: impl AsRef<Path>) -> Result<Self, LimiterError> {
+        let limits = self.limiter().config().clone();
+        *self.limiter() = GuessLimiter::open(limits, path)?;
+        Ok(self)
+    }
+
+    /// Demands a proof of work with every evaluation, as configured.
+    pub fn with_puzzles(mut self, config: PuzzleConfig) -> Self {
+        self.puzzles = PuzzleIssuer::new(config, self.keys.current());
+        self
+    }
+
+    /// Demands a token issued under `token_key` with every reconstruct request,
+    /// which is then no longer counted against the client's guess limit.
+    pub fn with_tokens(mut self, token_key: ServerKey) -> Self {
+        self.token_key = Some(token_key);
+        self
+    }
+
+    pub fn from_config(config: &KeyperConfig) -> Result<Self, Box<dyn Error>> {
+        let mut keys = KeyRing::new(ServerKey::new(config.key_version, decode_seed(&config.seed)?));
+        for retired in &config.retired_keys {
+            if retired.version == config.key_version {
+                return Err(format!("retired key version {} is still the current version", retired.version).into());
+            }
+            let expires_at = SystemTime::UNIX_EPOCH + Duration::from_secs(retired.expires_at);
+            keys = keys.retire(ServerKey::new(retired.version, decode_seed(&retired.seed)?), expires_at);
+        }
+
+        let keyper = Keyper::with_limits(keys, config.limits.clone(), config.admin_token.clone())
+            .with_records(&config.records)?
+            .with_puzzles(config.puzzle.clone());
+        match &config.tokens {
+            Some(tokens) => Ok(keyper.with_tokens(tokens.key()?)),
+            None => Ok(keyper),
+        }
+    }
+
+    /// Dispatches a serialized request to the handler of the given route.
+    pub fn process_request(&self, route: &str, api_request: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let (route, curve) = split_route(route)?;
+
+        match route {
+            KEYGEN_ROUTE => self.process_keygen_request(curve, api_request),
+            RECONSTRUCT_ROUTE => self.process_reconstruct_request(curve, api_request),
+            REGISTER_ROUTE => self.process_register_request(api_request),
+            SUCCESS_ROUTE => self.process_success_report(api_request),
+            UNLOCK_ROUTE => self.process_unlock_request(api_request),
+            CHALLENGE_ROUTE => self.process_challenge_request(api_request),
+            TOKEN_KEY_ROUTE => self.process_token_key_request(curve),
+            _ => Err(format!("unknown route {}", route).into()),
+        }
+    }
+
+    /// Answers a serialized keygen request with a serialized PRF evaluation
+    /// under the current key; retired keys are never used for new vaults.
+    pub fn process_keygen_request(&self, curve: Curve, api_request: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        match curve {
+            Curve::BLS12_381_G1 => self.evaluate::<Bls12381G1>(api_request, true),
+            Curve::P256 => self.evaluate::<P256>(api_request, true),
+            Curve::SECP256K1 => self.evaluate::<Secp256k1>(api_request, true),
+            Curve::RISTRETTO255 => self.evaluate::<Ristretto255>(api_request, true),
+        }
+    }
+
+    /// Answers a serialized reconstruct request with a serialized PRF evaluation
+    /// under the requested key version, if it is still served.
+    pub fn process_reconstruct_request(&self, curve: Curve, api_request: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        match curve {
+            Curve::BLS12_381_G1 => self.evaluate::<Bls12381G1>(api_request, false),
+            Curve::P256 => self.evaluate::<P256>(api_request, false),
+            Curve::SECP256K1 => self.evaluate::<Secp256k1>(api_request, false),
+            Curve::RISTRETTO255 => self.evaluate::<Ristretto255>(api_request, false),
+        }
+    }
+
+    fn evaluate<S: CurveSuite>(&self, api_request: &[u8], keygen: bool) -> Result<Vec<u8>, Box<dyn Error>> {
+        let pp = JKKX16Scheme::<S>::setup::<_>(&mut rand::thread_rng())?;
+
+        let prf_input = PrfInput::<S::Group>::deserialize_compressed(api_request)?;
+
+        // the work is checked first, so that floods of requests cost us one hash each
+        self.puzzles.check(prf_input.proof_of_work.as_ref(), &prf_input.binding()?, SystemTime::now())?;
+
+        let key = match prf_input.key_version {
+            _ if keygen => self.keys.current(),
+            Some(version) => self.keys.get(version, SystemTime::now())
+                .ok_or(JKKX16Error::UnknownKeyVersionError(version))?,
+            None => self.keys.current(),
+        };
+
+        // keygen evaluates the same PRF as reconstruct, so it is limited alike,
+        // unless the storage service counted the guess when it issued the token
+        match &self.token_key {
+            Some(token_key) if !keygen => tokens::check::<S>(token_key, &prf_input)?,
+            _ => self.limiter().record_attempt(&prf_input.client_id, SystemTime::now())?,
+        }
+
+        let prf_output = if keygen {
+            JKKX16Scheme::<S>::server_process_keygen_request(&pp, key, prf_input.client_id.as_slice(), &prf_input)?
+        } else {
+            JKKX16Scheme::<S>::server_process_reconstruct_request(&pp, key, prf_input.client_id.as_slice(), &prf_input)?
+        };
+
+        // the client may now register the verifier of its reset token for the new key
+        if keygen {
+            self.limiter().open_registration(&prf_input.client_id, SystemTime::now())?;
+        }
+
+        let mut api_response = Vec::new();
+        prf_output.serialize_compressed(&mut api_response)?;
+        Ok(api_response)
+    }
+
+    /// Registers the verifier of a client's reset token, right after its keygen.
+    pub fn process_register_request(&self, api_request: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let request = RegisterRequest::deserialize_compressed(api_request)?;
+
+        let verifier: [u8; 32] = request.verifier.as_slice()
+            .try_into()
+            .map_err(|_| "verifier must be exactly 32 bytes")?;
+        let current_token = (!request.current_token.is_empty())
+            .then_some(request.current_token.as_slice());
+
+        self.limiter().register(&request.client_id, verifier, current_token, SystemTime::now())?;
+        Ok(vec![])
+    }
+
+    /// Resets the failure count of a client that presents its reset token.
+    pub fn process_success_report(&self, api_request: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let report = SuccessReport::deserialize_compressed(api_request)?;
+
+        self.limiter().report_success(&report.client_id, &report.token)?;
+        Ok(vec![])
+    }
+
+    /// Lifts a client's lockout, if the request carries the configured admin token.
+    pub fn process_unlock_request(&self, api_request: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let request = UnlockRequest::deserialize_compressed(api_request)?;
+
+        match &self.admin_token {
+            Some(admin_token) if admin_token.as_bytes() == request.admin_token.as_slice() => {
+                self.limiter().unlock(&request.client_id)?;
+                Ok(vec![])
+            },
+            _ => Err(LimiterError::InvalidToken.into()),
+        }
+    }
+
+    /// Issues a challenge for the client to solve before its next evaluation;
+    /// the request carries nothing the keyper needs.
+    pub fn process_challenge_request(&self, _api_request: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut api_response = Vec::new();
+        self.puzzles.issue(SystemTime::now()).serialize_compressed(&mut api_response)?;
+        Ok(api_response)
+    }
+
+    /// Publishes the public key of the keyper's token key over the given curve, against which
+    /// clients check the tokens the storage service issues them, lest it tag them with keys of its own.
+    pub fn process_token_key_request(&self, curve: Curve) -> Result<Vec<u8>, Box<dyn Error>> {
+        match curve {
+            Curve::BLS12_381_G1 => self.token_public_key::<Bls12381G1>(),
+            Curve::P256 => self.token_public_key::<P256>(),
+            Curve::SECP256K1 => self.token_public_key::<Secp256k1>(),
+            Curve::RISTRETTO255 => self.token_public_key::<Ristretto255>(),
+        }
+    }
+
+    fn token_public_key<S: CurveSuite>(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let token_key = self.token_key.as_ref().ok_or("the keyper does not accept tokens")?;
+        let pp = JKKX16Scheme::<S>::setup::<_>(&mut rand::thread_rng())?;
+
+        let mut api_response = Vec::new();
+        token::public_key::<S>(&pp, token_key).serialize_compressed(&mut api_response)?;
+        Ok(api_response)
+    }
+
+    /// HTTP routes of the keyper: `GET /<route>/<request>` for each of the routes above,
+    /// where the request is encoded in URL-safe base64, as sent by `remote::Remote`.
+    pub fn routes(self) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+        warp::get()
+            .and(warp::path!(String / String))
+            .map(move |route: String, api_request: String| {
+                respond(&api_request, |request| self.process_request(&route, request))
+            })
+    }
+
+    fn limiter(&self) -> std::sync::MutexGuard<'_, GuessLimiter> {
+        self.limiter.lock().expect("guess limiter lock poisoned")
+    }
+}
+
+/// Token by which a client proves to a keyper that it reconstructed the key;
+/// bound to the keyper's public key so that one keyper cannot replay it to another.
+pub(crate) fn reset_token(key: &SecretKey, public_key: &impl CanonicalSerialize) -> Result<Vec<u8>, SerializationError> {
+    let mut hash_input = b"bedrock-keyper-reset-token".to_vec();
+    key.serialize_compressed(&mut hash_input)?;
+    public_key.serialize_compressed(&mut hash_input)?;
+
+    Ok(Sha256::digest(&hash_input).to_vec())
+}
+
+/// Name of the route on which keypers evaluate the PRF over the given curve.
+pub fn prf_route(route: &str, curve: Curve) -> String {
+    match curve {
+        Curve::BLS12_381_G1 => route.to_string(),
+        Curve::P256 => format!("{}-{}", route, P256::NAME),
+        Curve::SECP256K1 => format!("{}-{}", route, Secp256k1::NAME),
+        Curve::RISTRETTO255 => format!("{}-{}", route, Ristretto255::NAME),
+    }
+}
+
+/// Splits a route into its name and the curve named by its suffix, if any.
+pub(crate) fn split_route(route: &str) -> Result<(&str, Curve), Box<dyn Error>> {
+    match route.split_once('-') {
+        Some((route, curve_name)) => Ok((route, curve_from_name(curve_name)?)),
+        None => Ok((route, Curve::BLS12_381_G1)),
+    }
+}
+
+fn curve_from_name(name: &str) -> Result<Curve, Box<dyn Error>> {
+    match name {
+        P256::NAME => Ok(Curve::P256),
+        Secp256k1::NAME => Ok(Curve::SECP256K1),
+        Ristretto255::NAME => Ok(Curve::RISTRETTO255),
+        _ => Err(format!("unknown curve {}", name).into()),
+    }
+}
+
+fn respond(
+    api_request: &str,
+    process: impl Fn(&[u8]) -> Result<Vec<u8>, Box<dyn Error>>,
+) -> warp::reply::Response {
+    let response = URL_SAFE.decode(api_request.as_bytes())
+        .map_err(|e| e.into())
+        .and_then(|request| process(request.as_slice()));
+
+    match response {
+        Ok(api_response) => api_response.into_response(),
+        Err(e) => {
+            eprintln!("Failed to process request: {}", e);
+            let status = match (e.downcast_ref::<LimiterError>(), e.downcast_ref::<PuzzleError>()) {
+                (Some(LimiterError::Locked), _) | (Some(LimiterError::Backoff(_)), _) => StatusCode::TOO_MANY_REQUESTS,
+                (Some(LimiterError::InvalidToken), _) | (Some(LimiterError::RegistrationClosed), _) => StatusCode::FORBIDDEN,
+                (Some(LimiterError::Storage(_)), _) => StatusCode::INTERNAL_SERVER_ERROR,
+                // the client is to fetch a fresh challenge and try again
+                (_, Some(PuzzleError::Missing)) | (_, Some(PuzzleError::Expired)) => StatusCode::PRECONDITION_REQUIRED,
+                (_, Some(_)) => StatusCode::FORBIDDEN,
+                (None, None) => match e.downcast_ref::<TokenError>() {
+                    Some(TokenError::Missing) => StatusCode::PAYMENT_REQUIRED,
+                    Some(TokenError::Invalid) => StatusCode::FORBIDDEN,
+                    None => StatusCode::BAD_REQUEST,
+                },
+            };
+            warp::reply::with_status(e.to_string(), status).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file for the keyper's records that no test writes to.
+    fn records_path() -> String {
+        std::env::temp_dir().join("bedrock-keyper-unused-records.json").display().to_string()
+    }
+
+    #[test]
+    fn test_keyper_from_config() {
+        let config: KeyperConfig = toml::from_str(&format!(
+            "listen = \"127.0.0.1:3030\"\nseed = \"{}\"\nrecords = \"{}\"", STANDARD.encode([7u8; 32]), records_path()
+        )).unwrap();
+        let keyper = Keyper::from_config(&config).unwrap();
+        assert_eq!(&ServerKey::new(1, [7u8; 32]), keyper.keys.current());
+
+        // seeds of any other length are rejected
+        let config = KeyperConfig { seed: STANDARD.encode([7u8; 16]), ..config };
+        assert!(Keyper::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_keyper_retired_keys() {
+        let config: KeyperConfig = toml::from_str(&format!(
+            "listen = \"127.0.0.1:3030\"\nseed = \"{}\"\nrecords = \"{}\"\nkey_version = 2\n\n\
+             [[retired_keys]]\nversion = 1\nseed = \"{}\"\nexpires_at = 4102444800\n\n\
+             [[retired_keys]]\nversion = 0\nseed = \"{}\"\nexpires_at = 0",
+            STANDARD.encode([2u8; 32]), records_path(), STANDARD.encode([1u8; 32]), STANDARD.encode([0u8; 32])
+        )).unwrap();
+        let keyper = Keyper::from_config(&config).unwrap();
+        let now = SystemTime::now();
+
+        assert_eq!(2, keyper.keys.current().version);
+        assert_eq!(Some(&ServerKey::new(1, [1u8; 32])), keyper.keys.get(1, now));
+        // the grace period of version 0 is long over
+        assert_eq!(None, keyper.keys.get(0, now));
+    }
+
+    #[test]
+    fn test_keyper_tokens() {
+        let token_key = ServerKey::new(1, [9u8; 32]);
+        let limits = LimiterConfig { max_failures: 1, ..LimiterConfig::default() };
+        let keyper = Keyper::with_limits(KeyRing::new(ServerKey::new(1, [7u8; 32])), limits, None)
+            .with_tokens(token_key.clone());
+
+        let rng = &mut rand::thread_rng();
+        let pp = JKKX16::setup(rng).unwrap();
+        let (_, mut prf_input) = JKKX16::client_generate_reconstruct_request(&pp, b"alice@gmail.com", b"198837", rng).unwrap();
+        let reconstruct = |prf_input: &PrfInput<_>| {
+            let mut api_request = Vec::new();
+            prf_input.serialize_compressed(&mut api_request).unwrap();
+            keyper.process_request(RECONSTRUCT_ROUTE, &api_request)
+        };
+
+        let err = reconstruct(&prf_input).unwrap_err();
+        assert_eq!(Some(&TokenError::Missing), err.downcast_ref());
+
+        // the storage service issues a token for the request
+        let (state, token_request) = token::client_generate_token_request::<Bls12381G1, _>(&pp, &prf_input, rng).unwrap();
+        let response = token::server_issue_token::<Bls12381G1>(&pp, &token_key, &token_request).unwrap();
+        let public_key = token::public_key::<Bls12381G1>(&pp, &token_key);
+        prf_input.token = Some(token::client_finalize_token(&pp, &state, &response, &public_key).unwrap());
+
+        // the keyper no longer limits the client's guesses itself, and replaying
+        // the token only yields the same evaluation again
+        assert_eq!(reconstruct(&prf_input).unwrap(), reconstruct(&prf_input).unwrap());
+
+        // the token does not pay for the request under another key version
+        let other_version = PrfInput { key_version: Some(1), ..prf_input.clone() };
+        let err = reconstruct(&other_version).unwrap_err();
+        assert_eq!(Some(&TokenError::Invalid), err.downcast_ref());
+
+        // beyond the guess limit, another request with a token of its own is admitted
+        let (_, mut next_input) = JKKX16::client_generate_reconstruct_request(&pp, b"alice@gmail.com", b"198837", rng).unwrap();
+        let (state, token_request) = token::client_generate_token_request::<Bls12381G1, _>(&pp, &next_input, rng).unwrap();
+        let response = token::server_issue_token::<Bls12381G1>(&pp, &token_key, &token_request).unwrap();
+        next_input.token = Some(token::client_finalize_token(&pp, &state, &response, &public_key).unwrap());
+        reconstruct(&next_input).unwrap();
+
+        // a token for another request is rejected
+        let (_, mut other_input) = JKKX16::client_generate_reconstruct_request(&pp, b"alice@gmail.com", b"000000", rng).unwrap();
+        other_input.token = prf_input.token.clone();
+        let err = reconstruct(&other_input).unwrap_err();
+        assert_eq!(Some(&TokenError::Invalid), err.downcast_ref());
+    }
+
+    #[test]
+    fn test_keyper_register() {
+        let keyper = Keyper::new(ServerKey::new(1, [7u8; 32]));
+        let rng = &mut rand::thread_rng();
+        let pp = JKKX16::setup(rng).unwrap();
+        let (_, prf_input) = JKKX16::client_generate_keygen_request(&pp, b"alice@gmail.com", b"198837", rng).unwrap();
+        let mut prf_request = Vec::new();
+        prf_input.serialize_compressed(&mut prf_request).unwrap();
+
+        let register = || {
+            let mut api_request = Vec::new();
+            RegisterRequest { client_id: b"alice@gmail.com".to_vec(), verifier: vec![0u8; 32], current_token: vec![] }
+                .serialize_compressed(&mut api_request).unwrap();
+            keyper.process_request(REGISTER_ROUTE, &api_request)
+        };
+
+        // a reconstruct request does not let anyone register a verifier, but a keygen does, once
+        keyper.process_request(RECONSTRUCT_ROUTE, &prf_request).unwrap();
+        assert_eq!(Some(&LimiterError::RegistrationClosed), register().unwrap_err().downcast_ref());
+        keyper.limiter().unlock(b"alice@gmail.com").unwrap();
+        keyper.process_request(KEYGEN_ROUTE, &prf_request).unwrap();
+        register().unwrap();
+        assert_eq!(Some(&LimiterError::RegistrationClosed), register().unwrap_err().downcast_ref());
+    }
+
+    #[test]
+    fn test_keyper_unlock() {
+        let limits = LimiterConfig { max_failures: 1, ..LimiterConfig::default() };
+        let keyper = Keyper::with_limits(KeyRing::new(ServerKey::new(1, [7u8; 32])), limits, Some("admin".to_string()));
+        let client_id = b"alice@gmail.com".to_vec();
+
+        keyper.limiter().record_attempt(&client_id, SystemTime::now()).unwrap();
+        assert!(keyper.limiter().record_attempt(&client_id, SystemTime::now()).is_err());
+
+        let unlock = |admin_token: &[u8]| {
+            let mut api_request = Vec::new();
+            UnlockRequest { client_id: client_id.clone(), admin_token: admin_token.to_vec() }
+                .serialize_compressed(&mut api_request).unwrap();
+            keyper.process_request(UNLOCK_ROUTE, &api_request)
+        };
+
+        assert!(unlock(b"guess").is_err());
+        unlock(b"admin").unwrap();
+        keyper.limiter().record_attempt(&client_id, SystemTime::now()).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+//! Proof-of-work admission for the keyper's PRF evaluations. Before evaluating, the keyper
+//! checks that the request carries a solution to one of its own challenges, which it
+//! recognizes by their tag rather than by remembering them; this throttles floods of
+//! requests without keeping any state per client. A challenge may be reused for several
+//! requests until it expires, but each request needs work of its own.
+
+use std::{fmt, time::{Duration, SystemTime}};
+
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::crypto::ppss::pow::{self, Challenge, ProofOfWork};
+use super::ServerKey;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PuzzleConfig {
+    /// number of leading zero bits demanded of a solution; zero disables the puzzles
+    pub difficulty: u8,
+    /// time for which a challenge can be solved and used
+    pub challenge_ttl_secs: u64,
+}
+
+impl Default for PuzzleConfig {
+    fn default() -> Self {
+        PuzzleConfig {
+            difficulty: 0,
+            challenge_ttl_secs: 300,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PuzzleError {
+    /// the request carries no proof of work, but the keyper demands one
+    Missing,
+    /// the challenge was not issued by this keyper, or was issued at a lower difficulty
+    InvalidChallenge,
+    /// the challenge is past its time to live
+    Expired,
+    /// the nonce does not solve the challenge for this request
+    InsufficientWork,
+}
+
+impl std::error::Error for PuzzleError {}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PuzzleError::Missing =>
+                write!(f, "The request must carry a proof of work."),
+            PuzzleError::InvalidChallenge =>
+                write!(f, "The proof of work answers a challenge this keyper did not issue."),
+            PuzzleError::Expired =>
+                write!(f, "The challenge of the proof of work has expired."),
+            PuzzleError::InsufficientWork =>
+                write!(f, "The proof of work does not solve its challenge."),
+        }
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+const TAG_KEY_SALT: &[u8] = b"bedrock-keyper-puzzle-tag";
+
+#[derive(Clone)]
+pub struct PuzzleIssuer {
+    config: PuzzleConfig,
+    tag_key: [u8; 32],
+}
+
+impl PuzzleIssuer {
+    /// Derives the key that authenticates challenges from the keyper's seed; challenges
+    /// issued under an earlier seed therefore expire with its rotation, at the latest.
+    pub fn new(config: PuzzleConfig, key: &ServerKey) -> Self {
+        let hkdf = Hkdf::<Sha256>::new(Some(TAG_KEY_SALT), &key.seed);
+        let mut tag_key = [0u8; 32];
+        hkdf.expand(&key.version.to_be_bytes(), &mut tag_key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+
+        PuzzleIssuer { config, tag_key }
+    }
+
+    /// Whether the keyper demands a proof of work with every evaluation.
+    pub fn enabled(&self) -> bool {
+        self.config.difficulty > 0
+    }
+
+    /// Issues a challenge at the configured difficulty.
+    pub fn issue(&self, now: SystemTime) -> Challenge {
+        let issued_at = now.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+        let difficulty = self.config.difficulty;
+
+        Challenge { issued_at, difficulty, tag: self.tag(issued_at, difficulty) }
+    }
+
+    /// Admits a request whose proof of work answers an unexpired challenge of this keyper,
+    /// issued at no less than the configured difficulty, for the given request input.
+    pub fn check(&self, proof: Option<&ProofOfWork>, input: &[u8], now: SystemTime) -> Result<(), PuzzleError> {
+        if !self.enabled() {
+            return Ok(());
+        }
+        let proof = proof.ok_or(PuzzleError::Missing)?;
+        let challenge = &proof.challenge;
+
+        let mac = self.mac(challenge.issued_at, challenge.difficulty);
+        if mac.verify_slice(&challenge.tag).is_err() || challenge.difficulty < self.config.difficulty {
+            return Err(PuzzleError::InvalidChallenge);
+        }
+
+        let expires_at = SystemTime::UNIX_EPOCH
+            + Duration::from_secs(challenge.issued_at.saturating_add(self.config.challenge_ttl_secs));
+        if now >= expires_at {
+            return Err(PuzzleError::Expired);
+        }
+
+        match pow::verify(proof, input) {
+            Ok(true) => Ok(()),
+            _ => Err(PuzzleError::InsufficientWork),
+        }
+    }
+
+    fn tag(&self, issued_at: u64, difficulty: u8) -> Vec<u8> {
+        self.mac(issued_at, difficulty).finalize().into_bytes().to_vec()
+    }
+
+    fn mac(&self, issued_at: u64, difficulty: u8) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.tag_key)
+            .expect("HMAC accepts keys of any length");
+        mac.update(&issued_at.to_be_bytes());
+        mac.update(&[difficulty]);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn puzzle_issuer(difficulty: u8) -> PuzzleIssuer {
+        PuzzleIssuer::new(PuzzleConfig { difficulty, challenge_ttl_secs: 60 }, &ServerKey::new(1, [7u8; 32]))
+    }
+
+    #[test]
+    fn test_check_proof_of_work() {
+        let issuer = puzzle_issuer(8);
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1767225600);
+        let challenge = issuer.issue(now);
+
+        assert_eq!(Err(PuzzleError::Missing), issuer.check(None, b"request", now));
+
+        let proof = pow::solve(&challenge, b"request").unwrap();
+        assert_eq!(Ok(()), issuer.check(Some(&proof), b"request", now));
+        assert_eq!(Err(PuzzleError::InsufficientWork), issuer.check(Some(&proof), b"another request", now));
+        assert_eq!(Err(PuzzleError::Expired), issuer.check(Some(&proof), b"request", now + Duration::from_secs(60)));
+
+        // the difficulty cannot be lowered, nor can another keyper's challenge be used
+        let easy = Challenge { difficulty: 0, ..challenge.clone() };
+        let proof = pow::solve(&easy, b"request").unwrap();
+        assert_eq!(Err(PuzzleError::InvalidChallenge), issuer.check(Some(&proof), b"request", now));
+
+        let foreign = PuzzleIssuer::new(PuzzleConfig { difficulty: 8, challenge_ttl_secs: 60 }, &ServerKey::new(1, [8u8; 32]));
+        let proof = pow::solve(&foreign.issue(now), b"request").unwrap();
+        assert_eq!(Err(PuzzleError::InvalidChallenge), issuer.check(Some(&proof), b"request", now));
+
+        // without a difficulty, no work is demanded
+        assert_eq!(Ok(()), puzzle_issuer(0).check(None, b"request", now));
+    }
+}
@@ -0,0 +1,124 @@
+//! Token-based admission of reconstruct requests. When configured with the token seed
+//! it shares with the storage service, the keyper answers a reconstruct request only if
+//! it carries an anonymous token issued for it (see `crypto::ppss::token`), and leaves
+//! the counting of guesses to the storage service rather than keeping it per client.
+//! A token is bound to the blinded input, the client id and the key version of its request,
+//! so replaying it only yields the same evaluation again, and the keyper keeps no record of it.
+//!
+//! The issuing side, which the storage service runs, is `TokenIssuer`.
+
+use std::{collections::HashMap, error::Error, fmt};
+
+use ark_ec::Group;
+use ark_serialize::*;
+use serde::Deserialize;
+
+use crate::crypto::ppss::{*, jkkx16::{JKKX16Scheme, PrfInput}, suite::*, token};
+use crate::vault::Curve;
+use super::{decode_seed, split_route, ServerKey};
+
+/// Route of the storage service on which clients obtain tokens, suffixed with the curve
+/// like the keyper's PRF routes.
+pub const ISSUE_TOKEN_ROUTE: &str = "issue_token";
+
+/// The token key the keyper shares with the storage service.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TokenConfig {
+    /// base64 encoding of the 32-byte token seed
+    pub seed: String,
+    /// version of the token seed, which tokens name
+    pub key_version: u32,
+}
+
+impl TokenConfig {
+    pub fn key(&self) -> Result<ServerKey, Box<dyn Error>> {
+        Ok(ServerKey::new(self.key_version, decode_seed(&self.seed)?))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenError {
+    /// the request carries no token, but the keyper demands one
+    Missing,
+    /// the token was not issued for this request, or not under the keyper's token key
+    Invalid,
+}
+
+impl std::error::Error for TokenError {}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TokenError::Missing =>
+                write!(f, "The request must carry a token from the storage service."),
+            TokenError::Invalid =>
+                write!(f, "The token was not issued for this request."),
+        }
+    }
+}
+
+/// Admits a request that carries a valid token under the given key.
+pub fn check<S: CurveSuite>(key: &ServerKey, prf_input: &PrfInput<S::Group>) -> Result<(), TokenError> {
+    if prf_input.token.is_none() {
+        return Err(TokenError::Missing);
+    }
+
+    match token::server_verify_token::<S>(key, prf_input) {
+        Ok(true) => Ok(()),
+        _ => Err(TokenError::Invalid),
+    }
+}
+
+/// Asks the storage service for a token that pays for a reconstruct request to the keyper at
+/// `keyper_url`; `token_request` blinds the request, as made by `token::client_generate_token_request`.
+#[derive(Clone, Default, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct IssueTokenRequest<C: Group> {
+    pub client_id: Vec<u8>,
+    pub keyper_url: Vec<u8>,
+    pub token_request: PrfInput<C>,
+}
+
+/// Issues tokens under the token key shared with each keyper. The storage service authenticates
+/// the client and counts the guess against its vault before handing the request to `process_request`;
+/// clients in debug mode simulate the issuer with this alone.
+#[derive(Clone)]
+pub struct TokenIssuer {
+    /// the token key shared with each keyper, by the keyper's url
+    keys: HashMap<String, ServerKey>,
+}
+
+impl TokenIssuer {
+    pub fn new(keys: HashMap<String, ServerKey>) -> Self {
+        TokenIssuer { keys }
+    }
+
+    /// Dispatches a serialized request to the handler of the given route.
+    pub fn process_request(&self, route: &str, api_request: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let (route, curve) = split_route(route)?;
+        if route != ISSUE_TOKEN_ROUTE {
+            return Err(format!("unknown route {}", route).into());
+        }
+
+        match curve {
+            Curve::BLS12_381_G1 => self.issue::<Bls12381G1>(api_request),
+            Curve::P256 => self.issue::<P256>(api_request),
+            Curve::SECP256K1 => self.issue::<Secp256k1>(api_request),
+            Curve::RISTRETTO255 => self.issue::<Ristretto255>(api_request),
+        }
+    }
+
+    fn issue<S: CurveSuite>(&self, api_request: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let pp = JKKX16Scheme::<S>::setup::<_>(&mut rand::thread_rng())?;
+
+        let request = IssueTokenRequest::<S::Group>::deserialize_compressed(api_request)?;
+        let keyper_url = String::from_utf8_lossy(&request.keyper_url);
+        let key = self.keys.get(keyper_url.as_ref())
+            .ok_or_else(|| format!("no token key is shared with the keyper at {}", keyper_url))?;
+
+        let issuer_response = token::server_issue_token::<S>(&pp, key, &request.token_request)?;
+
+        let mut api_response = Vec::new();
+        issuer_response.serialize_compressed(&mut api_response)?;
+        Ok(api_response)
+    }
+}
@@ -1,69 +1,114 @@
 use aes_gcm::{aead::Aead, Aes128Gcm, Nonce, Key};
 use aes::cipher::{typenum::*, KeyInit};
 use protobuf::Message;
-use std::error::Error;
+use std::{collections::HashMap, error::Error};
 use ark_serialize::*;
-use crate::crypto::ppss::{*, jkkx16::*};
+use rand::RngCore;
+use crate::crypto::ppss::{*, jkkx16::*, pow::Challenge, suite::{Bls12381G1, CurveSuite, P256, Ristretto255, Secp256k1}, token::Token};
 
 include!(concat!(env!("OUT_DIR"), "/protos/mod.rs"));
-use vault::Vault;
+use vault::{Curve, PasswordStretch, Vault};
 
 mod crypto;
 mod remote;
+pub mod keyper;
+
+use keyper::{Keyper, RegisterRequest, ServerKey, SuccessReport, tokens::{IssueTokenRequest, TokenError, TokenIssuer}};
+pub use crypto::ppss::stretch::StretchParameters;
 
-type PrfInput = crypto::ppss::jkkx16::PrfInput<ark_bls12_381::G1Projective>;
-type PrfOutput = crypto::ppss::jkkx16::PrfOutput<ark_bls12_381::G1Projective>;
-type PPSSCiphertext = crypto::ppss::jkkx16::Ciphertext<ark_bls12_381::G1Projective>;
 type SecretKey = crypto::ppss::jkkx16::SecretKey;
 
 pub struct BedrockClient {
     owner_id: String,
-    server_url: String,
-    debug_mode: bool,
+    /// keypers that new vaults are shared across, in the order of their indices
+    keyper_urls: Vec<String>,
+    /// number of keypers needed to recover new vaults
+    threshold: usize,
+    /// cost of stretching the password of new vaults, if at all
+    password_stretch: Option<StretchParameters>,
+    /// curve over which the keypers evaluate the PRF for new vaults
+    curve: Curve,
+    /// storage service that issues the tokens keypers may demand with reconstruct requests
+    token_issuer: Option<String>,
+    /// in debug mode, requests are answered by keypers simulated in-process, by url
+    local_keypers: Option<HashMap<String, Keyper>>,
+    /// in debug mode, tokens are issued in-process
+    local_token_issuer: Option<TokenIssuer>,
 }
 
 impl BedrockClient {
     pub fn new(url: &str, owner: &str) -> Self {
+        BedrockClient::with_keypers(&[url], 1, owner)
+    }
+
+    /// Creates vaults that any `threshold` of the given keypers can recover.
+    pub fn with_keypers(urls: &[&str], threshold: usize, owner: &str) -> Self {
         BedrockClient {
             owner_id: owner.to_string(),
-            server_url: url.to_string(),
-            debug_mode: false,
+            keyper_urls: urls.iter().map(|url| url.to_string()).collect(),
+            threshold,
+            password_stretch: Some(StretchParameters::default()),
+            curve: Curve::BLS12_381_G1,
+            token_issuer: None,
+            local_keypers: None,
+            local_token_issuer: None,
         }
     }
 
+    /// Sets the cost of stretching the password of new vaults, or disables stretching.
+    /// Existing vaults are recovered with the cost recorded in them.
+    pub fn with_password_stretch(self, password_stretch: Option<StretchParameters>) -> Self {
+        BedrockClient { password_stretch, ..self }
+    }
+
+    /// Sets the curve of new vaults. Existing vaults are recovered over the curve
+    /// recorded in them, and move to this one when their key is generated anew.
+    pub fn with_curve(self, curve: Curve) -> Self {
+        BedrockClient { curve, ..self }
+    }
+
+    /// Obtains tokens from the storage service at the given url, for the keypers
+    /// that demand one with every reconstruct request.
+    pub fn with_token_issuer(self, url: &str) -> Self {
+        BedrockClient { token_issuer: Some(url.to_string()), ..self }
+    }
+
     pub fn new_debug(url: &str, owner: &str) -> Self {
+        BedrockClient::with_keypers_debug(&[url], 1, owner)
+    }
+
+    /// Like `with_keypers`, but simulating each keyper in-process, under a seed of its own.
+    pub fn with_keypers_debug(urls: &[&str], threshold: usize, owner: &str) -> Self {
+        let local_keypers = urls.iter().enumerate()
+            .map(|(i, url)| (url.to_string(), Keyper::new(ServerKey::new(1, [i as u8; 32]))))
+            .collect();
+
         BedrockClient {
-            owner_id: owner.to_string(),
-            server_url: url.to_string(),
-            debug_mode: true,
+            local_keypers: Some(local_keypers),
+            ..BedrockClient::with_keypers(urls, threshold, owner)
         }
     }
 
     pub async fn initialize(&self, password: &[u8], secret: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        let mut rng = rand::thread_rng();
-        let pp = JKKX16::setup::<_>(&mut rng).unwrap();
+        let (key, kem_ciphertext) =
+            self.generate_key(password, &self.keyper_urls, self.threshold, &HashMap::new()).await?;
 
-        let (client_state, prf_input) = 
-            JKKX16::client_generate_keygen_request(&pp, &self.owner_id.as_bytes(), password, &mut rng)?;
+        // the secret is encrypted under a data key of its own, so that changing
+        // the PPSS key only requires wrapping the data key anew
+        let mut data_key = SecretKey::default();
+        rand::thread_rng().fill_bytes(&mut data_key);
 
-        let prf_output = if self.debug_mode {
-            simulate_prf_locally(&prf_input)?
-        } else {
-            invoke_prf_service(&self.server_url, &prf_input).await?
-        };
-
-        let (key, kem_ciphertext) =
-            JKKX16::client_keygen(&pp, &client_state, &[prf_output], 1, 1, &mut rng)?;
-        
-        let mut kem_ciphertext_serialized = Vec::new();
-        kem_ciphertext.serialize_compressed(&mut kem_ciphertext_serialized).unwrap();
-        let dem_ciphertext_serialized = encrypt_message(secret, &key);
+        let dem_ciphertext_serialized = encrypt_message(secret, &data_key);
 
         // create the vault
         let mut vault = Vault::new();
         vault.owner = self.owner_id.clone();
         vault.dem_ciphertext = dem_ciphertext_serialized;
-        vault.kem_ciphertext = kem_ciphertext_serialized;
+        vault.kem_ciphertext = kem_ciphertext;
+        vault.wrapped_key = encrypt_message(&data_key, &key);
+        vault.keyper_urls = self.keyper_urls.clone();
+        vault.password_stretch = self.password_stretch_record();
+        vault.curve = self.curve.into();
 
         Ok(vault.write_to_bytes().expect("failed to serialize vault"))
     }
@@ -71,110 +116,389 @@ impl BedrockClient {
     pub async fn recover(&self, vault: impl AsRef<[u8]>, password: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
         let vault = Vault::parse_from_bytes(vault.as_ref()).expect("failed to parse vault");
 
-        let ctxt: PPSSCiphertext = PPSSCiphertext::deserialize_compressed(vault.kem_ciphertext.as_slice()).unwrap();
-        
-        let mut rng = rand::thread_rng();
-        let pp = JKKX16::setup::<_>(&mut rng).unwrap();
+        let (key, _) = self.reconstruct_key(&vault, password).await?;
+        let data_key = unwrap_data_key(&vault, &key)?;
 
-        let (client_state, prf_input) =
-            JKKX16::client_generate_reconstruct_request(&pp, vault.owner.as_bytes(), password, &mut rng)?;
+        let secret = decrypt_message(vault.dem_ciphertext.as_slice(), &data_key).unwrap();
+
+        Ok(secret)
+    }
+
+    /// Re-encrypts the vault under the keypers' current keys, after they have rotated
+    /// their seeds. The old key versions must still be within their grace period; the
+    /// protected secret and the password are unchanged.
+    pub async fn rotate(&self, vault: impl AsRef<[u8]>, password: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let parsed = Vault::parse_from_bytes(vault.as_ref()).expect("failed to parse vault");
+        let (keyper_urls, threshold) = self.vault_keypers(&parsed)?;
 
-        let prf_output = if self.debug_mode {
-            simulate_prf_locally(&prf_input)?
-        } else {
-            invoke_prf_service(&self.server_url, &prf_input).await?
+        self.rekey(vault.as_ref(), password, password, &keyper_urls, threshold).await
+    }
+
+    /// Replaces the vault's PIN. Only the wrapping of the data key changes,
+    /// so the encrypted secret is carried over as is.
+    pub async fn change_pin(&self, vault: impl AsRef<[u8]>, old_pin: &[u8], new_pin: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let parsed = Vault::parse_from_bytes(vault.as_ref()).expect("failed to parse vault");
+        let (keyper_urls, threshold) = self.vault_keypers(&parsed)?;
+
+        self.rekey(vault.as_ref(), old_pin, new_pin, &keyper_urls, threshold).await
+    }
+
+    /// Moves the vault to a new list of keypers, any `threshold` of which can recover it.
+    /// The protected secret is unchanged, and the vault no longer depends on keypers
+    /// that are not in the new list, which can therefore be decommissioned.
+    pub async fn reshare(
+        &self,
+        vault: impl AsRef<[u8]>,
+        password: &[u8],
+        keyper_urls: &[&str],
+        threshold: usize,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        if threshold == 0 || threshold > keyper_urls.len() {
+            return Err(format!("threshold {} is not between 1 and {}", threshold, keyper_urls.len()).into());
+        }
+        let keyper_urls: Vec<String> = keyper_urls.iter().map(|url| url.to_string()).collect();
+
+        self.rekey(vault.as_ref(), password, password, &keyper_urls, threshold).await
+    }
+
+    /// Recovers the vault's data key with the old password, and wraps it under a fresh
+    /// PPSS key, generated with the new password and the current keys of the given keypers.
+    async fn rekey(
+        &self,
+        vault: &[u8],
+        old_password: &[u8],
+        new_password: &[u8],
+        keyper_urls: &[String],
+        threshold: usize,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut vault = Vault::parse_from_bytes(vault).expect("failed to parse vault");
+
+        let (old_key, reset_tokens) = self.reconstruct_key(&vault, old_password).await?;
+        let data_key = unwrap_data_key(&vault, &old_key)?;
+
+        // keypers only accept a new reset token from the holder of the old one; as both
+        // the reconstruction and the keygen are reported as successes, neither holds up the other
+        let (key, kem_ciphertext) = self.generate_key(new_password, keyper_urls, threshold, &reset_tokens).await?;
+
+        vault.kem_ciphertext = kem_ciphertext;
+        vault.wrapped_key = encrypt_message(&data_key, &key);
+        vault.keyper_urls = keyper_urls.to_vec();
+        vault.password_stretch = self.password_stretch_record();
+        vault.curve = self.curve.into();
+
+        Ok(vault.write_to_bytes().expect("failed to serialize vault"))
+    }
+
+    /// Returns the keypers of the vault along with its threshold.
+    fn vault_keypers(&self, vault: &Vault) -> Result<(Vec<String>, usize), Box<dyn Error>> {
+        let kem_ciphertext = vault.kem_ciphertext.as_slice();
+        let threshold = match vault_curve(vault)? {
+            Curve::BLS12_381_G1 => Ciphertext::<<Bls12381G1 as CurveSuite>::Group>::deserialize_compressed(kem_ciphertext)?.threshold(),
+            Curve::P256 => Ciphertext::<<P256 as CurveSuite>::Group>::deserialize_compressed(kem_ciphertext)?.threshold(),
+            Curve::SECP256K1 => Ciphertext::<<Secp256k1 as CurveSuite>::Group>::deserialize_compressed(kem_ciphertext)?.threshold(),
+            Curve::RISTRETTO255 => Ciphertext::<<Ristretto255 as CurveSuite>::Group>::deserialize_compressed(kem_ciphertext)?.threshold(),
         };
 
-        let key = JKKX16::client_reconstruct(&pp, &client_state, &[prf_output], &ctxt)?;
+        if vault.keyper_urls.is_empty() {
+            return Err("the vault does not record its keypers".into());
+        }
 
-        let secret = decrypt_message(vault.dem_ciphertext.as_slice(), &key).unwrap();
+        Ok((vault.keyper_urls.clone(), threshold))
+    }
 
-        Ok(secret)
+    fn password_stretch_record(&self) -> protobuf::MessageField<PasswordStretch> {
+        protobuf::MessageField::from_option(self.password_stretch.map(|params| PasswordStretch {
+            memory_kib: params.memory_kib,
+            iterations: params.iterations,
+            parallelism: params.parallelism,
+            ..Default::default()
+        }))
     }
-}
 
-fn encrypt_message(msg: &[u8], key: &SecretKey) -> Vec<u8> {
-    let key: &Key<Aes128Gcm> = key.into();
-    let cipher = Aes128Gcm::new(&key);
-    let nonce = Nonce::<U12>::default();
+    /// Runs keygen with the current keys of the given keypers, and registers the resulting
+    /// reset tokens, replacing the ones held by the keypers in `current_tokens`, if any.
+    /// Returns the key along with the serialized PPSS ciphertext, over the client's curve.
+    async fn generate_key(
+        &self,
+        password: &[u8],
+        keyper_urls: &[String],
+        threshold: usize,
+        current_tokens: &HashMap<String, Vec<u8>>,
+    ) -> Result<(SecretKey, Vec<u8>), Box<dyn Error>> {
+        let password = stretch_password(self.password_stretch.as_ref(), self.owner_id.as_bytes(), password)?;
+
+        match self.curve {
+            Curve::BLS12_381_G1 => self.generate_key_on::<Bls12381G1>(&password, keyper_urls, threshold, current_tokens).await,
+            Curve::P256 => self.generate_key_on::<P256>(&password, keyper_urls, threshold, current_tokens).await,
+            Curve::SECP256K1 => self.generate_key_on::<Secp256k1>(&password, keyper_urls, threshold, current_tokens).await,
+            Curve::RISTRETTO255 => self.generate_key_on::<Ristretto255>(&password, keyper_urls, threshold, current_tokens).await,
+        }
+    }
 
-    cipher.encrypt(&nonce, msg).unwrap()
-}
+    async fn generate_key_on<S: CurveSuite>(
+        &self,
+        password: &[u8],
+        keyper_urls: &[String],
+        threshold: usize,
+        current_tokens: &HashMap<String, Vec<u8>>,
+    ) -> Result<(SecretKey, Vec<u8>), Box<dyn Error>> {
+        let mut rng = rand::thread_rng();
+        let pp = JKKX16Scheme::<S>::setup::<_>(&mut rng).unwrap();
 
-fn decrypt_message(ctxt: &[u8], key: &SecretKey) -> aead::Result<Vec<u8>> {
-    let key: &Key<Aes128Gcm> = key.into();
-    let cipher = Aes128Gcm::new(&key);
-    let nonce = Nonce::<U12>::default();
+        let (client_state, prf_input) = 
+            JKKX16Scheme::<S>::client_generate_keygen_request(&pp, self.owner_id.as_bytes(), password, &mut rng)?;
+        let route = keyper::prf_route(keyper::KEYGEN_ROUTE, self.curve);
 
-    cipher.decrypt(&nonce, ctxt)
-}
+        // every keyper takes part in keygen, so that all of their keys are pinned
+        let mut prf_outputs = Vec::new();
+        for url in keyper_urls {
+            prf_outputs.push(self.invoke_prf_service::<S>(url, &route, self.curve, &prf_input).await?);
+        }
 
-fn simulate_prf_locally(input: &PrfInput) -> Result<PrfOutput, Box<dyn Error>> {
-    let seed = [0u8; 32];
-    let mut rng = rand::thread_rng();
-    let pp = JKKX16::setup::<_>(&mut rng).unwrap();
+        let (key, kem_ciphertext) =
+            JKKX16Scheme::<S>::client_keygen(&pp, &client_state, &prf_outputs, keyper_urls.len(), threshold, &mut rng)?;
+
+        // let the keypers recognize our successful reconstructions from now on
+        for (url, prf_output) in keyper_urls.iter().zip(prf_outputs.iter()) {
+            let reset_token = keyper::reset_token(&key, &prf_output.public_key)?;
+            let register_request = RegisterRequest {
+                client_id: self.owner_id.as_bytes().to_vec(),
+                verifier: keyper::limiter::token_verifier(&reset_token).to_vec(),
+                current_token: current_tokens.get(url).cloned().unwrap_or_default(),
+            };
+            self.call_keyper(url, keyper::REGISTER_ROUTE, &register_request).await?;
+
+            // registering leaves the guess of our keygen on the count, for the new token to reset
+            let report = SuccessReport {
+                client_id: self.owner_id.as_bytes().to_vec(),
+                token: reset_token,
+            };
+            self.call_keyper(url, keyper::SUCCESS_ROUTE, &report).await?;
+        }
+
+        let mut kem_ciphertext_serialized = Vec::new();
+        kem_ciphertext.serialize_compressed(&mut kem_ciphertext_serialized)?;
+
+        Ok((key, kem_ciphertext_serialized))
+    }
+
+    /// Reconstructs the vault's key from the responses of its keypers, returning it
+    /// along with the reset token of each keyper that took part, by url.
+    async fn reconstruct_key(&self, vault: &Vault, password: &[u8]) -> Result<(SecretKey, HashMap<String, Vec<u8>>), Box<dyn Error>> {
+        // stretch the password at the cost it was stretched with at keygen
+        let password_stretch = vault.password_stretch.as_ref().map(|params| StretchParameters {
+            memory_kib: params.memory_kib,
+            iterations: params.iterations,
+            parallelism: params.parallelism,
+        });
+        let password = stretch_password(password_stretch.as_ref(), vault.owner.as_bytes(), password)?;
+
+        match vault_curve(vault)? {
+            Curve::BLS12_381_G1 => self.reconstruct_key_on::<Bls12381G1>(vault, &password).await,
+            Curve::P256 => self.reconstruct_key_on::<P256>(vault, &password).await,
+            Curve::SECP256K1 => self.reconstruct_key_on::<Secp256k1>(vault, &password).await,
+            Curve::RISTRETTO255 => self.reconstruct_key_on::<Ristretto255>(vault, &password).await,
+        }
+    }
+
+    async fn reconstruct_key_on<S: CurveSuite>(&self, vault: &Vault, password: &[u8]) -> Result<(SecretKey, HashMap<String, Vec<u8>>), Box<dyn Error>> {
+        let ctxt = Ciphertext::<S::Group>::deserialize_compressed(vault.kem_ciphertext.as_slice())?;
+        let (keyper_urls, _) = self.vault_keypers(vault)?;
+
+        let mut rng = rand::thread_rng();
+        let pp = JKKX16Scheme::<S>::setup::<_>(&mut rng).unwrap();
+
+        let (client_state, prf_input) =
+            JKKX16Scheme::<S>::client_generate_reconstruct_request(&pp, vault.owner.as_bytes(), password, &mut rng)?;
+        let curve = vault_curve(vault)?;
+        let route = keyper::prf_route(keyper::RECONSTRUCT_ROUTE, curve);
+
+        // a threshold number of keypers suffices, so unreachable ones are merely reported
+        let mut responses = Vec::new();
+        for (i, url) in keyper_urls.iter().enumerate() {
+            // ask each keyper for the key version it used when the vault was created
+            let prf_input = PrfInput {
+                key_version: ctxt.server_key(i).map(|pinned| pinned.key_version),
+                ..prf_input.clone()
+            };
+
+            match self.invoke_prf_service::<S>(url, &route, curve, &prf_input).await {
+                Ok(prf_output) => responses.push((i, prf_output)),
+                Err(e) => eprintln!("Keyper {} failed to respond: {}", url, e),
+            }
+        }
+
+        let reconstruction = JKKX16Scheme::<S>::client_reconstruct_robust(&pp, &client_state, &responses, &ctxt)?;
+        for i in reconstruction.inconsistent_servers.iter() {
+            eprintln!("Keyper {} returned an inconsistent response", keyper_urls[*i]);
+        }
+        let key = reconstruction.key;
+
+        // tell the keypers that this was not a failed guess; the secret is recovered
+        // regardless, so a keyper that has lost track of us is merely reported
+        let mut reset_tokens = HashMap::new();
+        for (i, prf_output) in responses.iter() {
+            if reconstruction.inconsistent_servers.contains(i) {
+                continue;
+            }
+
+            let report = SuccessReport {
+                client_id: vault.owner.as_bytes().to_vec(),
+                token: keyper::reset_token(&key, &prf_output.public_key)?,
+            };
+            if let Err(e) = self.call_keyper(&keyper_urls[*i], keyper::SUCCESS_ROUTE, &report).await {
+                eprintln!("Failed to reset the guess counter of keyper {}: {}", keyper_urls[*i], e);
+            }
+            reset_tokens.insert(keyper_urls[*i].clone(), report.token);
+        }
 
-    // serialize the input
-    let mut api_request = Vec::new();
-    input.serialize_compressed(&mut api_request)?;
+        Ok((key, reset_tokens))
+    }
 
-    // we are not really sending the request anywhere, just pretending
+    async fn invoke_prf_service<S: CurveSuite>(
+        &self,
+        url: &str,
+        route: &str,
+        curve: Curve,
+        input: &PrfInput<S::Group>,
+    ) -> Result<PrfOutput<S::Group>, Box<dyn Error>> {
+        // only when the keyper demands a proof of work or a token, obtain one for this very
+        // input; neither covers the other, so a keyper may demand both in turn
+        let mut input = input.clone();
+        let api_response = loop {
+            match self.call_keyper(url, route, &input).await {
+                Err(e) if input.proof_of_work.is_none() && proof_of_work_required(e.as_ref()) => {
+                    let challenge = self.call_keyper(url, keyper::CHALLENGE_ROUTE, &Vec::<u8>::new()).await?;
+                    let challenge = Challenge::deserialize_compressed(challenge.as_slice())?;
+                    if challenge.difficulty > pow::MAX_DIFFICULTY {
+                        return Err(format!("the keyper at {} demands a proof of work of difficulty {}, above {}",
+                            url, challenge.difficulty, pow::MAX_DIFFICULTY).into());
+                    }
+
+                    input.proof_of_work = Some(pow::solve(&challenge, &input.binding()?)?);
+                }
+                Err(e) if input.token.is_none() && token_required(e.as_ref()) => {
+                    input.token = Some(self.obtain_token::<S>(url, curve, &input).await?);
+                }
+                result => break result?,
+            }
+        };
 
-    // deserialize the request on the pretend server
-    let prf_input_deserialized = PrfInput::deserialize_compressed(api_request.as_slice())?;
+        let output = PrfOutput::<S::Group>::deserialize_compressed(api_response.as_slice())?;
+        Ok(output)
+    }
 
-    // process the request
-    let prf_output = JKKX16::server_process_keygen_request(
-        &pp, &seed, input.client_id.as_slice(), &prf_input_deserialized
-    )?;
+    /// Obtains a token from the storage service that pays for the given request to the keyper
+    /// at `url`, and checks it against the token key that the keyper itself publishes.
+    async fn obtain_token<S: CurveSuite>(&self, url: &str, curve: Curve, input: &PrfInput<S::Group>) -> Result<Token<S::Group>, Box<dyn Error>> {
+        let issuer_url = self.token_issuer.as_ref()
+            .ok_or_else(|| format!("the keyper at {} demands a token, but no token issuer is configured", url))?;
+
+        let public_key = self.call_keyper(url, &keyper::prf_route(keyper::TOKEN_KEY_ROUTE, curve), &Vec::<u8>::new()).await?;
+        let public_key = S::Group::deserialize_compressed(public_key.as_slice())?;
+
+        let pp = JKKX16Scheme::<S>::setup::<_>(&mut rand::thread_rng()).unwrap();
+        let (state, token_request) = token::client_generate_token_request::<S, _>(&pp, input, &mut rand::thread_rng())?;
+        let request = IssueTokenRequest {
+            client_id: input.client_id.clone(),
+            keyper_url: url.as_bytes().to_vec(),
+            token_request,
+        };
 
-    // serialize the response
-    let mut api_response = Vec::new();
-    prf_output.serialize_compressed(&mut api_response)?;
+        let route = keyper::prf_route(keyper::tokens::ISSUE_TOKEN_ROUTE, curve);
+        let mut api_request = Vec::new();
+        request.serialize_compressed(&mut api_request)?;
+        let api_response = match &self.local_token_issuer {
+            Some(issuer) => issuer.process_request(&route, api_request.as_slice())?,
+            None => remote::Remote::new(format!("{}/{}", issuer_url, route)).get(&api_request).await?,
+        };
 
-    // pretend to send the response back to the client
+        let issuer_response = PrfOutput::<S::Group>::deserialize_compressed(api_response.as_slice())?;
+        Ok(token::client_finalize_token(&pp, &state, &issuer_response, &public_key)?)
+    }
 
-    // deserialize the response on the client
-    let prf_output = PrfOutput::deserialize_compressed(api_response.as_slice())?;
-    Ok(prf_output)
+    async fn call_keyper(&self, url: &str, route: &str, request: &impl CanonicalSerialize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut api_request = Vec::new();
+        request.serialize_compressed(&mut api_request)?;
+
+        match &self.local_keypers {
+            // we are not really sending the request anywhere, just pretending
+            Some(keypers) => keypers.get(url)
+                .ok_or_else(|| format!("no keyper is simulated at {}", url))?
+                .process_request(route, api_request.as_slice()),
+            None => {
+                let remote = remote::Remote::new(format!("{}/{}", url, route));
+                remote.get(&api_request).await
+            }
+        }
+    }
 }
 
-async fn invoke_prf_service(server_url: &str, input: &PrfInput) -> Result<PrfOutput, Box<dyn Error>> {
-    let mut api_request = Vec::new();
-    input.serialize_compressed(&mut api_request)?;
+/// Tells whether the keyper turned the request away for want of a proof of work,
+/// be it a simulated keyper or a remote one, which answers with 428 Precondition Required.
+fn proof_of_work_required(e: &(dyn Error + 'static)) -> bool {
+    match (e.downcast_ref::<keyper::puzzle::PuzzleError>(), e.downcast_ref::<remote::RemoteError>()) {
+        (Some(keyper::puzzle::PuzzleError::Missing), _) | (Some(keyper::puzzle::PuzzleError::Expired), _) => true,
+        (_, Some(remote)) => remote.status == reqwest::StatusCode::PRECONDITION_REQUIRED,
+        _ => false,
+    }
+}
 
-    let remote = remote::Remote::new(server_url.to_string());
-    let api_response = remote.get(&api_request).await?;
+/// Tells whether the keyper turned the request away for want of a token, be it
+/// a simulated keyper or a remote one, which answers with 402 Payment Required.
+fn token_required(e: &(dyn Error + 'static)) -> bool {
+    match (e.downcast_ref::<TokenError>(), e.downcast_ref::<remote::RemoteError>()) {
+        (Some(TokenError::Missing), _) => true,
+        (_, Some(remote)) => remote.status == reqwest::StatusCode::PAYMENT_REQUIRED,
+        _ => false,
+    }
+}
 
-    let output = PrfOutput::deserialize_compressed(api_response.as_slice())?;
-    Ok(output)
+/// Returns the curve of the vault; vaults that predate the choice of curves use BLS12-381.
+fn vault_curve(vault: &Vault) -> Result<Curve, Box<dyn Error>> {
+    vault.curve.enum_value().map_err(|value| format!("the vault uses an unknown curve {}", value).into())
 }
 
-#[cfg(test)]
-mod tests {
+fn stretch_password(
+    params: Option<&StretchParameters>,
+    client_id: &[u8],
+    password: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    match params {
+        Some(params) => Ok(crypto::ppss::stretch::stretch_password(params, client_id, password)?),
+        None => Ok(password.to_vec()),
+    }
+}
 
-    use base64::{engine::general_purpose::URL_SAFE, Engine as _};
-    use warp::Filter;
-    use tokio::sync::oneshot;
-    use super::*;
+/// Returns the key under which the vault's secret is encrypted.
+fn unwrap_data_key(vault: &Vault, key: &SecretKey) -> Result<SecretKey, Box<dyn Error>> {
+    let data_key = decrypt_message(vault.wrapped_key.as_slice(), key)
+        .map_err(|_| "failed to unwrap the vault's data key")?;
+    Ok(data_key.as_slice().try_into().map_err(|_| "the vault's data key has an invalid length")?)
+}
 
-    fn server_computation(api_request: &[u8]) -> Vec<u8> {
-        let seed = [0u8; 32];
-        let mut rng = rand::thread_rng();
-        let pp = JKKX16::setup::<_>(&mut rng).unwrap();
+fn encrypt_message(msg: &[u8], key: &SecretKey) -> Vec<u8> {
+    let key: &Key<Aes128Gcm> = key.into();
+    let cipher = Aes128Gcm::new(key);
+    let nonce = Nonce::<U12>::default();
 
-        let prf_input_deserialized = PrfInput::deserialize_compressed(api_request).unwrap();
-        // process the request
-        let prf_output = JKKX16::server_process_keygen_request(
-            &pp, &seed, prf_input_deserialized.client_id.as_slice(), &prf_input_deserialized
-        ).unwrap();
+    cipher.encrypt(&nonce, msg).unwrap()
+}
 
-        // serialize the response
-        let mut api_response = Vec::new();
-        prf_output.serialize_compressed(&mut api_response).unwrap();
+fn decrypt_message(ctxt: &[u8], key: &SecretKey) -> aead::Result<Vec<u8>> {
+    let key: &Key<Aes128Gcm> = key.into();
+    let cipher = Aes128Gcm::new(key);
+    let nonce = Nonce::<U12>::default();
 
-        api_response
-    }
+    cipher.decrypt(&nonce, ctxt)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tokio::sync::oneshot;
+    use super::*;
+    use keyper::keyring::KeyRing;
 
     #[tokio::test]
     async fn test_initialize_recover_local_server_mode() {
@@ -182,12 +506,7 @@ mod tests {
         let secret = "topsecret";
         let userid = "alice@gmail.com";
 
-        let decrypt = warp::path!("decrypt" / String)
-            .map(|api_request: String| {
-                println!("Received request: {}", api_request);
-                let api_request = URL_SAFE.decode(api_request.as_bytes()).unwrap();
-                server_computation(api_request.as_slice())
-            });
+        let keyper = Keyper::new(ServerKey::new(1, [0u8; 32]));
 
         let (tx, rx) = oneshot::channel::<()>();
 
@@ -195,7 +514,7 @@ mod tests {
         let addr = ([127, 0, 0, 1], 3030);
 
         // Spawn the server in a separate async task
-        let server = warp::serve(decrypt)
+        let server = warp::serve(keyper.routes())
             .bind_with_graceful_shutdown(addr, async {
                 // Wait for the shutdown signal
                 rx.await.ok();
@@ -206,7 +525,7 @@ mod tests {
         // Wait a bit for the server to start (usually very fast, but good to be safe)
         tokio::time::sleep(std::time::Duration::from_millis(200)).await;
 
-        let client = super::BedrockClient::new("http://127.0.0.1:3030/decrypt", userid);
+        let client = super::BedrockClient::new("http://127.0.0.1:3030", userid);
 
         let vault_encoded = client.initialize(password.as_bytes(), secret.as_bytes()).await.unwrap();
         let recovered = client.recover(vault_encoded, password.as_bytes()).await.unwrap();
@@ -221,6 +540,41 @@ mod tests {
     }
 
 
+    #[tokio::test]
+    async fn test_recover_with_unreachable_keyper_local_server_mode() {
+        let password = b"password";
+        let secret = b"topsecret";
+        let urls = ["http://127.0.0.1:3031", "http://127.0.0.1:3032", "http://127.0.0.1:3033"];
+
+        let mut shutdowns = Vec::new();
+        let mut server_handles = Vec::new();
+        for (i, port) in [3031, 3032, 3033].into_iter().enumerate() {
+            let keyper = Keyper::new(ServerKey::new(1, [i as u8; 32]));
+            let (tx, rx) = oneshot::channel::<()>();
+            let server = warp::serve(keyper.routes())
+                .bind_with_graceful_shutdown(([127, 0, 0, 1], port), async {
+                    rx.await.ok();
+                });
+            shutdowns.push(tx);
+            server_handles.push(tokio::spawn(server.1));
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        let client = super::BedrockClient::with_keypers(&urls, 2, "alice@gmail.com").with_password_stretch(None);
+        let vault_encoded = client.initialize(password, secret).await.unwrap();
+
+        // the last keyper goes down, and refuses connections from then on
+        let _ = shutdowns.pop().unwrap().send(());
+        let _ = server_handles.pop().unwrap().await;
+        assert_eq!(secret, client.recover(&vault_encoded, password).await.unwrap().as_slice());
+
+        for (tx, server_handle) in shutdowns.into_iter().zip(server_handles) {
+            let _ = tx.send(());
+            let _ = server_handle.await;
+        }
+    }
+
+
     #[tokio::test]
     async fn test_initialize_recover_debug_mode() {
         let client = super::BedrockClient::new_debug(
@@ -234,4 +588,219 @@ mod tests {
         assert_eq!(secret, recovered.as_slice());
         println!("recovered {:?}", recovered);
     }
+
+    #[tokio::test]
+    async fn test_rotate_debug_mode() {
+        let mut client = super::BedrockClient::new_debug("", "alice@gmail.com");
+        let password = b"password";
+        let secret = b"topsecret";
+        let old_key = ServerKey::new(1, [0u8; 32]);
+        let new_key = ServerKey::new(2, [1u8; 32]);
+        let records = std::env::temp_dir().join(format!("bedrock-rotate-{}.json", std::process::id()));
+        let keyper = |keys: KeyRing| Keyper::with_limits(keys, Default::default(), None).with_records(&records).unwrap();
+
+        client.local_keypers = Some(HashMap::from([("".to_string(), keyper(KeyRing::new(old_key.clone())))]));
+        let vault_encoded = client.initialize(password, secret).await.unwrap();
+
+        // the keyper restarts with a rotated seed, but keeps serving the old one for a day,
+        // and still knows our reset token, so that rotating is not taken for failed guesses
+        let grace_period = std::time::SystemTime::now() + std::time::Duration::from_secs(86400);
+        let keys = KeyRing::new(new_key.clone()).retire(old_key, grace_period);
+        client.local_keypers = Some(HashMap::from([("".to_string(), keyper(keys))]));
+
+        let rotated = client.rotate(&vault_encoded, password).await.unwrap();
+        let ctxt = Ciphertext::<ark_bls12_381::G1Projective>::deserialize_compressed(
+            Vault::parse_from_bytes(&rotated).unwrap().kem_ciphertext.as_slice()
+        ).unwrap();
+        assert_eq!(Some(2), ctxt.server_key(0).map(|pinned| pinned.key_version));
+
+        // once the old seed is gone, only the rotated vault can be recovered, right away
+        client.local_keypers = Some(HashMap::from([("".to_string(), keyper(KeyRing::new(new_key)))]));
+        assert!(client.recover(&vault_encoded, password).await.is_err());
+        assert_eq!(secret, client.recover(&rotated, password).await.unwrap().as_slice());
+
+        std::fs::remove_file(&records).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_proof_of_work_debug_mode() {
+        let mut client = super::BedrockClient::new_debug("", "alice@gmail.com").with_password_stretch(None);
+        let password = b"password";
+        let secret = b"topsecret";
+
+        let puzzles = keyper::puzzle::PuzzleConfig { difficulty: 8, ..Default::default() };
+        let keyper = Keyper::new(ServerKey::new(1, [0u8; 32])).with_puzzles(puzzles);
+        client.local_keypers = Some(HashMap::from([("".to_string(), keyper.clone())]));
+
+        // the client solves each keyper's puzzle before asking for an evaluation
+        let vault_encoded = client.initialize(password, secret).await.unwrap();
+        assert_eq!(secret, client.recover(&vault_encoded, password).await.unwrap().as_slice());
+
+        // requests without a proof of work are turned away
+        let rng = &mut rand::thread_rng();
+        let pp = JKKX16::setup(rng).unwrap();
+        let (_, prf_input) = JKKX16::client_generate_reconstruct_request(&pp, b"alice@gmail.com", password, rng).unwrap();
+        let mut api_request = Vec::new();
+        prf_input.serialize_compressed(&mut api_request).unwrap();
+        let err = keyper.process_request(keyper::RECONSTRUCT_ROUTE, &api_request).unwrap_err();
+        assert_eq!(Some(&keyper::puzzle::PuzzleError::Missing), err.downcast_ref());
+        assert!(super::proof_of_work_required(err.as_ref()));
+
+        // remote keypers demand a proof of work by their status alone
+        for (status, required) in [(reqwest::StatusCode::PRECONDITION_REQUIRED, true), (reqwest::StatusCode::TOO_MANY_REQUESTS, false)] {
+            let err = crate::remote::RemoteError { status, reason: String::new() };
+            assert_eq!(required, super::proof_of_work_required(&err));
+        }
+
+        // the client refuses puzzles too hard to solve in reasonable time
+        let puzzles = keyper::puzzle::PuzzleConfig { difficulty: pow::MAX_DIFFICULTY + 1, ..Default::default() };
+        let keyper = Keyper::new(ServerKey::new(1, [0u8; 32])).with_puzzles(puzzles);
+        client.local_keypers = Some(HashMap::from([("".to_string(), keyper)]));
+        let err = client.initialize(password, secret).await.unwrap_err();
+        assert!(err.to_string().contains("difficulty 33"));
+    }
+
+    #[tokio::test]
+    async fn test_tokens_debug_mode() {
+        let urls = ["keyper0", "keyper1"];
+        let mut client = super::BedrockClient::with_keypers_debug(&urls, 2, "alice@gmail.com").with_password_stretch(None);
+        let password = b"password";
+        let secret = b"topsecret";
+
+        // both keypers leave guess limiting to the storage service, with a token key shared with each
+        let token_keys: HashMap<String, ServerKey> = urls.iter().enumerate()
+            .map(|(i, url)| (url.to_string(), ServerKey::new(1, [10 + i as u8; 32])))
+            .collect();
+        let keypers = client.local_keypers.take().unwrap().into_iter()
+            .map(|(url, keyper)| { let token_key = token_keys[&url].clone(); (url, keyper.with_tokens(token_key)) })
+            .collect();
+        client.local_keypers = Some(keypers);
+        let vault_encoded = client.initialize(password, secret).await.unwrap();
+
+        // reconstruct requests are turned away without a token, which takes a token issuer
+        assert!(client.recover(&vault_encoded, password).await.is_err());
+
+        // the client pays for each of them with a token from the storage service
+        client.local_token_issuer = Some(TokenIssuer::new(token_keys));
+        let client = client.with_token_issuer("issuer");
+        assert_eq!(secret, client.recover(&vault_encoded, password).await.unwrap().as_slice());
+
+        // remote keypers demand a token by their status alone
+        for (status, required) in [(reqwest::StatusCode::PAYMENT_REQUIRED, true), (reqwest::StatusCode::FORBIDDEN, false)] {
+            let err = crate::remote::RemoteError { status, reason: String::new() };
+            assert_eq!(required, super::token_required(&err));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_change_pin_debug_mode() {
+        let client = super::BedrockClient::new_debug("", "alice@gmail.com");
+        let secret = b"topsecret";
+
+        let vault_encoded = client.initialize(b"123456", secret).await.unwrap();
+        let changed = client.change_pin(&vault_encoded, b"123456", b"654321").await.unwrap();
+
+        // the encrypted secret is carried over, only its key is wrapped anew
+        let (vault, changed_vault) = (Vault::parse_from_bytes(&vault_encoded).unwrap(), Vault::parse_from_bytes(&changed).unwrap());
+        assert_eq!(vault.dem_ciphertext, changed_vault.dem_ciphertext);
+        assert_ne!(vault.wrapped_key, changed_vault.wrapped_key);
+
+        assert_eq!(secret, client.recover(&changed, b"654321").await.unwrap().as_slice());
+        assert!(client.recover(&changed, b"123456").await.is_err());
+
+        // vaults must record their keypers and the wrapping of their data key
+        for strip in [|vault: &mut Vault| vault.keyper_urls.clear(), |vault: &mut Vault| vault.wrapped_key.clear()] {
+            let mut stripped = Vault::parse_from_bytes(&changed).unwrap();
+            strip(&mut stripped);
+            assert!(client.change_pin(stripped.write_to_bytes().unwrap(), b"654321", b"123456").await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reshare_debug_mode() {
+        let urls = ["keyper0", "keyper1", "keyper2", "keyper3", "keyper4"];
+        let mut client = super::BedrockClient::with_keypers_debug(&urls, 3, "alice@gmail.com");
+        let password = b"password";
+        let secret = b"topsecret";
+
+        // start out with a single keyper, then move to a 3-of-4 sharing across the others
+        client.keyper_urls.truncate(1);
+        client.threshold = 1;
+        let vault_encoded = client.initialize(password, secret).await.unwrap();
+
+        let reshared = client.reshare(&vault_encoded, password, &urls[1..], 3).await.unwrap();
+        let vault = Vault::parse_from_bytes(&reshared).unwrap();
+        assert_eq!(urls[1..].to_vec(), vault.keyper_urls);
+        assert_eq!(Vault::parse_from_bytes(&vault_encoded).unwrap().dem_ciphertext, vault.dem_ciphertext);
+
+        // the old keyper can be removed, and any one of the new ones can be down
+        let keypers = client.local_keypers.as_mut().unwrap();
+        for url in ["keyper0", "keyper2"] {
+            keypers.remove(url);
+        }
+        assert_eq!(secret, client.recover(&reshared, password).await.unwrap().as_slice());
+
+        // but not two
+        client.local_keypers.as_mut().unwrap().remove("keyper3");
+        assert!(client.recover(&reshared, password).await.is_err());
+
+        assert!(client.reshare(&reshared, password, &urls, 6).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_curves_debug_mode() {
+        let urls = ["keyper0", "keyper1", "keyper2"];
+        let password = b"password";
+        let secret = b"topsecret";
+        let mut client = super::BedrockClient::with_keypers_debug(&urls, 2, "alice@gmail.com").with_password_stretch(None);
+
+        // keypers track each client's reset token, so every vault gets an owner of its own
+        for (owner, curve) in [("bob@gmail.com", Curve::BLS12_381_G1), ("carol@gmail.com", Curve::P256), ("dave@gmail.com", Curve::SECP256K1), ("erin@gmail.com", Curve::RISTRETTO255)] {
+            client.owner_id = owner.to_string();
+            client.curve = curve;
+            let vault_encoded = client.initialize(password, secret).await.unwrap();
+            assert_eq!(curve, vault_curve(&Vault::parse_from_bytes(&vault_encoded).unwrap()).unwrap());
+            assert_eq!(secret, client.recover(&vault_encoded, password).await.unwrap().as_slice());
+        }
+
+        // a vault is recovered over the curve recorded in it, whatever the client's setting,
+        // and moves to the client's curve when its key is generated anew
+        client.owner_id = "alice@gmail.com".to_string();
+        client.curve = Curve::BLS12_381_G1;
+        let vault_encoded = client.initialize(password, secret).await.unwrap();
+        let client = client.with_curve(Curve::P256);
+        assert_eq!(secret, client.recover(&vault_encoded, password).await.unwrap().as_slice());
+
+        let changed = client.change_pin(&vault_encoded, password, b"123456").await.unwrap();
+        assert_eq!(Curve::P256, vault_curve(&Vault::parse_from_bytes(&changed).unwrap()).unwrap());
+        assert_eq!(secret, client.recover(&changed, b"123456").await.unwrap().as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_password_stretch_debug_mode() {
+        let password = b"password";
+        let secret = b"topsecret";
+        let cheap = StretchParameters { memory_kib: 7 * 1024, iterations: 1, parallelism: 1 };
+
+        // a vault created without stretching is recovered without it, whatever the client's setting
+        let client = super::BedrockClient::new_debug("", "alice@gmail.com").with_password_stretch(None);
+        let vault_encoded = client.initialize(password, secret).await.unwrap();
+        assert!(Vault::parse_from_bytes(&vault_encoded).unwrap().password_stretch.is_none());
+
+        let client = client.with_password_stretch(Some(cheap));
+        assert_eq!(secret, client.recover(&vault_encoded, password).await.unwrap().as_slice());
+
+        // changing the pin upgrades the vault to the client's setting
+        let changed = client.change_pin(&vault_encoded, password, b"123456").await.unwrap();
+        let stretch = Vault::parse_from_bytes(&changed).unwrap().password_stretch.unwrap();
+        assert_eq!((7 * 1024, 1, 1), (stretch.memory_kib, stretch.iterations, stretch.parallelism));
+
+        let client = client.with_password_stretch(Some(StretchParameters { iterations: 2, ..cheap }));
+        assert_eq!(secret, client.recover(&changed, b"123456").await.unwrap().as_slice());
+
+        // a vault demanding terabytes of memory is rejected rather than stretched with
+        let mut vault = Vault::parse_from_bytes(&changed).unwrap();
+        vault.password_stretch.as_mut().unwrap().memory_kib = u32::MAX;
+        assert!(client.recover(vault.write_to_bytes().unwrap(), b"123456").await.is_err());
+    }
 }
@@ -1,4 +1,23 @@
+use std::{error::Error, fmt};
 use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+use reqwest::StatusCode;
+
+/// The server answered with a failure status, which the caller may act upon,
+/// e.g. by solving a puzzle when the server demands a proof of work.
+#[derive(Debug)]
+pub struct RemoteError {
+    pub status: StatusCode,
+    /// the server's explanation of the failure, e.g. that the vault is locked
+    pub reason: String,
+}
+
+impl Error for RemoteError {}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "server replied with {}: {}", self.status, self.reason)
+    }
+}
 
 pub struct Remote {
     pub url: String, // url for reaching the api service
@@ -9,27 +28,41 @@ impl Remote {
         Remote { url, }
     }
 
-    pub async fn get(&self, data: &[u8]) -> Result<Vec<u8>, reqwest::Error> {
+    pub async fn get(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
         // Base64 encode the request in URL-safe mode
-        let encoded_request = URL_SAFE.encode(&data);
+        let encoded_request = URL_SAFE.encode(data);
 
         // Construct the full URL with the Base64 encoded data as a parameter
         let api_url = format!("{}/{}", self.url, encoded_request);
 
-        print!("Sending GET request to: {}\n", api_url);
+        println!("Sending GET request to: {}", api_url);
 
-        // Send the GET request to the API and capture the response
-        let api_response = reqwest::get(api_url)
-            .await
-            .expect("Failed to send request");
-        print!("Received response from server\n");
+        // Send the GET request to the API and capture the response; an unreachable
+        // server is an error of its own, so that the caller can do without it
+        let api_response = reqwest::get(api_url).await?;
+        println!("Received response from server");
 
         // Check if the request was successful
-        if api_response.status().is_success() {
-            print!("SUCCESS\n");
+        let status = api_response.status();
+        if status.is_success() {
+            println!("SUCCESS");
             Ok(api_response.bytes().await?.to_vec())
         } else {
-            Ok(vec![]) // empty means server didnt reply for some reason
+            // the server explains the failure in the body, e.g. when the vault is locked
+            let reason = api_response.text().await.unwrap_or_default();
+            Err(RemoteError { status, reason }.into())
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unreachable_server() {
+        // nothing listens on port 1, so the connection is refused
+        let remote = Remote::new("http://127.0.0.1:1/reconstruct".to_string());
+        assert!(remote.get(b"request").await.is_err());
+    }
+}